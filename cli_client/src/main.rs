@@ -1,8 +1,8 @@
 use argh::FromArgs;
 use confy;
-//use gamechooser_core::*;
+use gamechooser_core as core;
 
-use igdb_api_client::SConfigFile;
+use igdb_api_client::{EResponseCacheMode, SConfigFile, SResponseCache};
 
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand, name = "test")]
@@ -22,11 +22,45 @@ struct SArghsSetTwitchClient {
     client_secret: String,
 }
 
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "query")]
+#[argh(description = "List games in a server database directory matching a query, e.g. \"owned:switch hltb<15\"")]
+struct SArghsQuery {
+    #[argh(positional)]
+    db_path: String,
+
+    #[argh(positional)]
+    query: String,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "set_igdb_cache")]
+#[argh(description = "Set how IGDB responses are cached: off, read_write or replay_only")]
+struct SArghsSetIGDBCache {
+    #[argh(positional)]
+    mode: String,
+
+    #[argh(option)]
+    #[argh(description = "where to keep the cache file")]
+    path: Option<String>,
+}
+
+#[derive(FromArgs, PartialEq, Debug)]
+#[argh(subcommand, name = "export_igdb_fixtures")]
+#[argh(description = "Write every game in the IGDB response cache out as a fixture file")]
+struct SArghsExportIGDBFixtures {
+    #[argh(positional)]
+    out_path: String,
+}
+
 #[derive(FromArgs, PartialEq, Debug)]
 #[argh(subcommand)]
 enum EArghsSubcommands {
     Test(SArghsTest),
     SetTwitchClient(SArghsSetTwitchClient),
+    SetIGDBCache(SArghsSetIGDBCache),
+    ExportIGDBFixtures(SArghsExportIGDBFixtures),
+    Query(SArghsQuery),
 }
 
 #[derive(FromArgs)]
@@ -39,6 +73,64 @@ struct SArghs {
 fn test() {
 }
 
+fn load_json<T: serde::de::DeserializeOwned>(db_path: &str, file_name: &str) -> Result<T, String> {
+    let mut path = std::path::PathBuf::new();
+    path.push(db_path);
+    path.push(file_name);
+    path.set_extension("json");
+
+    let file = std::fs::File::open(&path).map_err(|e| format!("Failed to open {} with: {:?}", path.display(), e))?;
+    let reader = std::io::BufReader::new(file);
+    serde_json::from_reader(reader).map_err(|e| format!("Failed to deserialize {} with: {:?}", path.display(), e))
+}
+
+fn query(args: SArghsQuery) -> Result<(), String> {
+    let filter = core::parse_collection_query(args.query.as_str())
+        .map_err(|e| e.to_string())?
+        .into_filter_expr();
+
+    let app_config : core::SConfig = load_json(args.db_path.as_str(), "app_config").unwrap_or_default();
+    let db : core::EDatabase = load_json(args.db_path.as_str(), "database")?;
+    let db = db.to_latest_version();
+
+    let session_states = core::SGameSessionState::build_map(&db.sessions);
+    let no_sessions = core::SGameSessionState::default();
+
+    let mut count = 0;
+    for game in &db.games {
+        let session_state = session_states.get(&game.internal_id).unwrap_or(&no_sessions);
+        if filter.game_passes(&app_config, game, session_state) {
+            println!("{}", game.game_info.title());
+            count += 1;
+        }
+    }
+
+    println!("{} matching game(s)", count);
+
+    Ok(())
+}
+
+fn set_igdb_cache(args: SArghsSetIGDBCache) -> Result<(), String> {
+    let mode = match args.mode.as_str() {
+        "off" => EResponseCacheMode::Off,
+        "read_write" => EResponseCacheMode::ReadWrite,
+        "replay_only" => EResponseCacheMode::ReplayOnly,
+        other => return Err(format!("Unknown cache mode \"{}\", expected off, read_write or replay_only", other)),
+    };
+
+    let mut cfg : SConfigFile = confy::load("gamechooser2_igdb_api_client").map_err(|e| e.to_string())?;
+    cfg.set_cache(mode, args.path.as_deref());
+    confy::store("gamechooser2_igdb_api_client", cfg).map_err(|e| e.to_string())
+}
+
+fn export_igdb_fixtures(args: SArghsExportIGDBFixtures) -> Result<(), String> {
+    let cfg : SConfigFile = confy::load("gamechooser2_igdb_api_client").map_err(|e| e.to_string())?;
+    let cache = SResponseCache::load(cfg.cache_config().clone()).map_err(|e| e.to_string())?;
+    let fixtures = cache.export_fixtures().map_err(|e| e.to_string())?;
+
+    std::fs::write(args.out_path.as_str(), fixtures).map_err(|e| format!("Failed to write {} with: {:?}", args.out_path, e))
+}
+
 fn main() {
     let arghs: SArghs = argh::from_env();
     match arghs.subcommand {
@@ -50,5 +142,23 @@ fn main() {
             cfg.set_twitch_client(stc.client_id.as_str(), stc.client_secret.as_str());
             confy::store("gamechooser2_igdb_api_client", cfg).unwrap()
         }
+        EArghsSubcommands::SetIGDBCache(sic) => {
+            if let Err(e) = set_igdb_cache(sic) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        EArghsSubcommands::ExportIGDBFixtures(eif) => {
+            if let Err(e) = export_igdb_fixtures(eif) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        EArghsSubcommands::Query(q) => {
+            if let Err(e) = query(q) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
     }
 }
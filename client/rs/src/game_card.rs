@@ -6,7 +6,7 @@ use web_sys::{
     HtmlDivElement,
     //HtmlElement,
     HtmlImageElement,
-    //HtmlInputElement,
+    HtmlInputElement,
     //HtmlLabelElement,
     //HtmlLiElement,
     HtmlParagraphElement,
@@ -25,6 +25,7 @@ enum EGame {
 
 pub struct SGameCard {
     game: EGame, // copy
+    release_date: core::EReleaseDate, // for the user's platform and region preferences
 
     pub main_div: HtmlDivElement,
     _columns_div: HtmlDivElement,
@@ -35,6 +36,7 @@ pub struct SGameCard {
     show_release_date: bool,
     show_how_long_to_beat: bool,
     show_igdb_link: bool,
+    show_igdb_metadata: bool,
     show_via: bool,
     show_own_info: bool,
     show_tag_info: bool,
@@ -46,14 +48,6 @@ pub struct SCompactGameCard {
     pub main_div: HtmlDivElement,
 }
 
-#[derive(Clone, Copy)]
-pub enum ECompactGameCardBadge {
-    None,
-    ReleaseDate,
-    HLTBSearch,
-    AliveState,
-}
-
 impl EGame {
     fn game_info(&self) -> &core::EGameInfo {
         match self {
@@ -76,6 +70,13 @@ impl EGame {
         }
     }
 
+    fn release_date(&self, config: &core::SConfig) -> core::EReleaseDate {
+        match self {
+            Self::GameInfo(gi) => gi.release_date(),
+            Self::CollectionGame(cg) => cg.release_date(config),
+        }
+    }
+
     fn alive(&self, config: &core::SConfig) -> Option<bool> {
         match self {
             Self::GameInfo(_) => None,
@@ -85,7 +86,7 @@ impl EGame {
 }
 
 impl SGameCard {
-    fn new_internal(game: EGame) -> Result<Self, JsError> {
+    fn new_internal(game: EGame, release_date: core::EReleaseDate) -> Result<Self, JsError> {
         let document = document();
 
         let main_div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
@@ -146,6 +147,7 @@ impl SGameCard {
 
         Ok(Self {
             game,
+            release_date,
             main_div,
             _columns_div: columns_div,
             info_column_div,
@@ -154,6 +156,7 @@ impl SGameCard {
             show_release_date: false,
             show_how_long_to_beat: false,
             show_igdb_link: false,
+            show_igdb_metadata: false,
             show_own_info: false,
             show_tag_info: false,
             show_via: false,
@@ -161,17 +164,18 @@ impl SGameCard {
     }
 
     pub fn new_from_game_info(game_info: &core::EGameInfo) -> Result<Self, JsError> {
-        Self::new_internal(EGame::GameInfo(game_info.clone()))
+        Self::new_internal(EGame::GameInfo(game_info.clone()), game_info.release_date())
     }
 
-    pub fn new_from_collection_game(collection_game: &core::SCollectionGame) -> Result<Self, JsError> {
-        Self::new_internal(EGame::CollectionGame(collection_game.clone()))
+    pub fn new_from_collection_game(config: &core::SConfig, collection_game: &core::SCollectionGame) -> Result<Self, JsError> {
+        Self::new_internal(EGame::CollectionGame(collection_game.clone()), collection_game.release_date(config))
     }
 
     pub fn show_all(&mut self) -> &mut Self {
         self.show_release_date = true;
         self.show_how_long_to_beat = true;
         self.show_igdb_link = true;
+        self.show_igdb_metadata = true;
         self.show_own_info = true;
         self.show_tag_info = true;
         self.show_via = true;
@@ -194,6 +198,12 @@ impl SGameCard {
         self
     }
 
+    #[allow(dead_code)]
+    pub fn show_igdb_metadata(&mut self) -> &mut Self {
+        self.show_igdb_metadata = true;
+        self
+    }
+
     pub fn show_own_info(&mut self) -> &mut Self {
         self.show_own_info = true;
         self
@@ -227,7 +237,7 @@ impl SGameCard {
 
         // game_info elements
         if self.show_release_date {
-            if let core::EReleaseDate::Known(d) = self.game.game_info().release_date() {
+            if let core::EReleaseDate::Known(d) = self.release_date {
                 let release_date_p = document.create_element_typed::<HtmlParagraphElement>().to_jserr()?;
                 release_date_p.set_inner_text(format!("Release date: {:?}", d).as_str());
                 self.generated_info_div.append_child(&release_date_p).to_jserr()?;
@@ -249,6 +259,13 @@ impl SGameCard {
             }
         }
 
+        if self.show_igdb_metadata {
+            if let Some(metadata) = self.game.game_info().igdb_metadata() {
+                create_igdb_metadata(metadata, &self.generated_info_div)?;
+            }
+            create_platform_release_dates(self.game.game_info().platform_release_dates(), &self.generated_info_div)?;
+        }
+
         // custom_info elements
         if let Some(custom_info) = &self.game.custom_info() {
             if self.show_via {
@@ -279,7 +296,7 @@ impl SGameCard {
 }
 
 impl SCompactGameCard {
-    fn new_internal(config: &core::SConfig, game: EGame, badge: ECompactGameCardBadge) -> Result<Self, JsError> {
+    fn new_internal(config: &core::SConfig, game: EGame, badge: core::ECompactGameCardBadge) -> Result<Self, JsError> {
         let document = document();
 
         let main_div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
@@ -317,9 +334,9 @@ impl SCompactGameCard {
         info_column_div.append_child(&generated_info_div).to_jserr()?;
 
         match badge {
-            ECompactGameCardBadge::None => (),
-            ECompactGameCardBadge::ReleaseDate => {
-                if let core::EReleaseDate::Known(date) = game.game_info().release_date() {
+            core::ECompactGameCardBadge::None => (),
+            core::ECompactGameCardBadge::ReleaseDate => {
+                if let core::EReleaseDate::Known(date) = game.release_date(config) {
                     let div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
                     div.set_class_name("compact_game_card_float_over_cover_container");
                     cover_div.append_child(&div).to_jserr()?;
@@ -330,7 +347,7 @@ impl SCompactGameCard {
                     div.append_child(&span).to_jserr()?;
                 }
             },
-            ECompactGameCardBadge::HLTBSearch => {
+            core::ECompactGameCardBadge::HLTBSearch => {
                 let div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
                 div.set_class_name("compact_game_card_float_over_cover_container");
                 cover_div.append_child(&div).to_jserr()?;
@@ -339,7 +356,7 @@ impl SCompactGameCard {
                 a.set_class_name("compact_game_card_float_over_cover_tag");
                 div.append_child(&a).to_jserr()?;
             }
-            ECompactGameCardBadge::AliveState => {
+            core::ECompactGameCardBadge::AliveState => {
                 if let Some(alive_inner) = game.alive(config) {
                     let div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
                     div.set_class_name("compact_game_card_float_over_cover_container");
@@ -398,9 +415,25 @@ impl SCompactGameCard {
         })
     }
 
-    pub fn new_from_collection_game(config: &core::SConfig, collection_game: &core::SCollectionGame, badge: ECompactGameCardBadge) -> Result<Self, JsError> {
+    pub fn new_from_collection_game(config: &core::SConfig, collection_game: &core::SCollectionGame, badge: core::ECompactGameCardBadge) -> Result<Self, JsError> {
         Self::new_internal(config, EGame::CollectionGame(collection_game.clone()), badge)
     }
+
+    // -- checkbox in the top left corner for picking games for a bulk edit
+    pub fn add_select_checkbox(&self, internal_id: u32, selected: bool) -> Result<(), JsError> {
+        let checkbox = document().create_element_typed::<HtmlInputElement>().to_jserr()?;
+        checkbox.set_type("checkbox");
+        checkbox.set_class_name("compact_game_card_select");
+        checkbox.set_id(format!("compact_game_card_select_{}", internal_id).as_str());
+        checkbox.set_checked(selected);
+
+        let onchange_body = format!("full_collection_toggle_selected({}, this.checked);", internal_id);
+        let onchange = Function::new_no_args(onchange_body.as_str());
+        checkbox.set_onchange(Some(&onchange));
+
+        self.main_div.append_child(&checkbox).to_jserr()?;
+        Ok(())
+    }
 }
 
 // HELPER FUNCTIONS
@@ -415,6 +448,92 @@ fn create_igdb_link(igdb: &core::SGameInfoIGDB) -> Result<HtmlAnchorElement, JsE
     Ok(a)
 }
 
+fn create_external_link(text: &str, url: &str) -> Result<HtmlAnchorElement, JsError> {
+    let a = document().create_element_typed::<HtmlAnchorElement>().to_jserr()?;
+    a.set_inner_text(text);
+    a.set_href(url);
+    a.set_target("_blank");
+    a.set_rel("noopener noreferrer");
+    Ok(a)
+}
+
+fn create_igdb_metadata(metadata: &core::SIGDBMetadata, output_div: &HtmlDivElement) -> Result<(), JsError> {
+    let document = document();
+
+    if let Some(summary) = &metadata.summary {
+        let summary_p = document.create_element_typed::<HtmlParagraphElement>().to_jserr()?;
+        summary_p.set_class_name("game_card_summary");
+        summary_p.set_inner_text(summary.as_str());
+        output_div.append_child(&summary_p).to_jserr()?;
+    }
+
+    let rating = metadata.aggregated_rating.map(|r| format!("{}/100", r)).into_iter().collect::<Vec<String>>();
+    let lines = [
+        ("Genres", &metadata.genres),
+        ("Themes", &metadata.themes),
+        ("Modes", &metadata.game_modes),
+        ("Platforms", &metadata.platforms),
+        ("Franchises", &metadata.franchises),
+        ("Developers", &metadata.developers),
+        ("Publishers", &metadata.publishers),
+        ("Critic rating", &rating),
+    ];
+    for (label, values) in lines {
+        if !values.is_empty() {
+            let p = document.create_element_typed::<HtmlParagraphElement>().to_jserr()?;
+            p.set_inner_text(format!("{}: {}", label, values.join(", ")).as_str());
+            output_div.append_child(&p).to_jserr()?;
+        }
+    }
+
+    if !metadata.websites.is_empty() {
+        let websites_div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
+        websites_div.set_class_name("game_card_websites");
+        for website in &metadata.websites {
+            let a = create_external_link(format!("{} ⧉", website.kind).as_str(), website.url.as_str())?;
+            websites_div.append_child(&a).to_jserr()?;
+        }
+        output_div.append_child(&websites_div).to_jserr()?;
+    }
+
+    let screenshot_urls = metadata.screenshot_urls();
+    if !screenshot_urls.is_empty() {
+        let screenshots_div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
+        screenshots_div.set_class_name("game_card_screenshots");
+        for url in screenshot_urls {
+            let a = create_external_link("", url.as_str())?;
+            let img_elem = document.create_element_typed::<HtmlImageElement>().to_jserr()?;
+            img_elem.set_src(url.as_str());
+            a.append_child(&img_elem).to_jserr()?;
+            screenshots_div.append_child(&a).to_jserr()?;
+        }
+        output_div.append_child(&screenshots_div).to_jserr()?;
+    }
+
+    Ok(())
+}
+
+fn create_platform_release_dates(releases: &[core::SPlatformReleaseDate], output_div: &HtmlDivElement) -> Result<(), JsError> {
+    if releases.is_empty() {
+        return Ok(());
+    }
+
+    let texts : Vec<String> = releases.iter().map(|r| {
+        let date = match &r.date {
+            core::EReleaseDate::Known(d) => d.format("%Y-%m-%d").to_string(),
+            core::EReleaseDate::UnknownReleased => String::from("released"),
+            core::EReleaseDate::UnknownUnreleased => String::from("TBA"),
+        };
+        format!("{} ({}) {}", r.platform, r.region, date)
+    }).collect();
+
+    let p = document().create_element_typed::<HtmlParagraphElement>().to_jserr()?;
+    p.set_inner_text(format!("Releases: {}", texts.join(", ")).as_str());
+    output_div.append_child(&p).to_jserr()?;
+
+    Ok(())
+}
+
 fn create_hltb_search_link(game_info: &core::EGameInfo) -> Result<HtmlAnchorElement, JsError> {
     let a = document().create_element_typed::<HtmlAnchorElement>().to_jserr()?;
     let url_encoded_title = urlencoding::encode(game_info.title());
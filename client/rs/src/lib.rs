@@ -4,7 +4,7 @@ mod stats;
 mod web;
 mod server_api;
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::RwLock;
 
 //use console_error_panic_hook;
@@ -14,6 +14,7 @@ use js_sys::{Function};
 use wasm_bindgen::prelude::*;
 use web_sys::{
     Element,
+    EventSource,
     //HtmlAnchorElement,
     HtmlButtonElement,
     HtmlDivElement,
@@ -21,7 +22,8 @@ use web_sys::{
     HtmlImageElement,
     HtmlInputElement,
     //HtmlLabelElement,
-    //HtmlLiElement,
+    HtmlLiElement,
+    HtmlOptionElement,
     HtmlParagraphElement,
     HtmlSelectElement,
     //HtmlSpanElement,
@@ -29,7 +31,8 @@ use web_sys::{
 };
 
 use gamechooser_core as core;
-use game_card::{SGameCard, SCompactGameCard, ECompactGameCardBadge};
+use core::ECompactGameCardBadge;
+use game_card::{SGameCard, SCompactGameCard};
 use session_card::{SSessionCard};
 use web::{document, TToJsError, TErgonomicDocument, create_checkbox};
 
@@ -53,6 +56,8 @@ enum ERandomizerMode {
 struct SGameRandomizerSession {
     mode: ERandomizerMode,
     shuffled_internal_ids: Vec<u32>,
+    // -- as they were when choosing started, so only games that changed get saved
+    original_choose_states: HashMap<u32, core::SGameChooseState>,
     cur_idx: usize,
 }
 
@@ -80,6 +85,22 @@ struct SAppState {
     game_edit: EGameEdit,
 
     game_randomizer: EGameRandomizer,
+
+    filter_presets: Option<Vec<core::SFilterPreset>>,
+
+    // -- requests for the next page of each paged list, None when everything is shown
+    full_collection_next_page: Option<(core::SGetGamesRequest, ECompactGameCardBadge)>,
+
+    // -- what the full collection screen is showing, and which of those games are picked for a bulk edit
+    full_collection_filter: Option<core::EGameFilterRequest>,
+    full_collection_shown: Vec<u32>,
+    full_collection_selected: HashSet<u32>,
+    session_screen_next_page: Option<core::SGetSessionsRequest>,
+
+    subscribed_to_changes: bool,
+
+    // -- the server sends the day's release notice again whenever the change stream reconnects
+    release_day_shown: Option<chrono::naive::NaiveDate>,
 }
 
 #[allow(dead_code)]
@@ -89,6 +110,9 @@ enum ETagQuery {
     False,
 }
 
+const FULL_COLLECTION_PAGE_SIZE: usize = 60;
+const SESSIONS_PAGE_SIZE: usize = 20;
+
 static APP: Lazy<RwLock<SAppState>> = Lazy::new(|| RwLock::new(SAppState::new()));
 
 impl SAppState {
@@ -101,6 +125,14 @@ impl SAppState {
             details_screen_game: None,
             game_edit: EGameEdit::None,
             game_randomizer: EGameRandomizer::Uninit,
+            filter_presets: None,
+            full_collection_next_page: None,
+            full_collection_filter: None,
+            full_collection_shown: Vec::new(),
+            full_collection_selected: HashSet::new(),
+            session_screen_next_page: None,
+            subscribed_to_changes: false,
+            release_day_shown: None,
         }
     }
 
@@ -185,6 +217,8 @@ pub async fn initial_load() -> Result<(), JsError> {
     let sl = SShowLoadingHelper::new();
     if server_api::check_logged_in().await {
         drop(sl);
+        subscribe_to_changes()?;
+        restore_job_progress().await?;
         show_sessions().await?;
     }
     else {
@@ -223,9 +257,10 @@ pub fn show_add() -> Result<(), JsError> {
 }
 
 #[wasm_bindgen]
-pub fn show_randomizer() -> Result<(), JsError> {
+pub async fn show_randomizer() -> Result<(), JsError> {
     div("randomizer_game_div")?.style().set_property("display", "none").to_jserr()?;
-    swap_section_div("randomizer_div")
+    swap_section_div("randomizer_div")?;
+    populate_randomizer_igdb_facets().await
 }
 
 #[wasm_bindgen]
@@ -343,6 +378,8 @@ pub async fn login_screen_submit() -> Result<(), JsError> {
     let sl = SShowLoadingHelper::new();
     if server_api::login(sec_input.value().as_str()).await.to_jserr().is_ok() {
         drop(sl);
+        subscribe_to_changes()?;
+        restore_job_progress().await?;
         show_sessions().await?;
     }
 
@@ -527,21 +564,33 @@ fn populate_img(id: &str, src: Option<&str>) -> Result<(), JsError> {
 }
 
 async fn view_details(game: core::SCollectionGame) -> Result<(), JsError> {
-    let mut card = SGameCard::new_from_collection_game(&game)?;
+    let config = {
+        let app = APP.try_read().map_err(|_| JsError::new("Should never actually have contention"))?;
+        app.config().to_jserr()?
+    };
+
+    let mut card = SGameCard::new_from_collection_game(&config, &game)?;
     card.show_all().regen()?;
 
     let card_div = div("game_details_card")?;
     card_div.set_inner_html("");
     card_div.append_child(&card.main_div).to_jserr()?;
 
+    div("game_details_explanation")?.set_inner_html("");
+
     let _sl = SShowLoadingHelper::new();
-    let sessions = match server_api::get_sessions(
-        Some(game.internal_id),
-        false,
-        false,
-        None,
-    ).await {
-        Ok(s) => s,
+    let request = core::SGetSessionsRequest {
+        filter: core::SSessionFilter {
+            game_id: Some(game.internal_id),
+            active_only: false,
+            memorable_only: false,
+            year: None,
+        },
+        sort: core::SSessionSort::default(),
+        page: core::SPageRequest::default(),
+    };
+    let sessions = match server_api::get_sessions(&request).await {
+        Ok(page) => page.items,
         Err(e) => {
             show_error(e)?;
             return Ok(());
@@ -654,6 +703,37 @@ fn edit_screen_populate_choose_state(choose_state: &core::SGameChooseState) -> R
     Ok(())
 }
 
+// -- inputs that can be flagged by core field validation, keyed by SFieldError::field
+const EDIT_SCREEN_FIELD_INPUTS: [(&str, &str); 3] = [
+    (core::FIELD_TITLE, "game_edit_title"),
+    (core::FIELD_HOW_LONG_TO_BEAT, "game_edit_how_long_to_beat_hours"),
+    (core::FIELD_NEXT_VALID_PROPOSAL_DATE, "game_edit_next_valid_proposal_date"),
+];
+
+fn edit_screen_clear_field_errors() -> Result<(), JsError> {
+    for (_, id) in EDIT_SCREEN_FIELD_INPUTS {
+        let input = document().get_typed_element_by_id::<HtmlElement>(id).to_jserr()?;
+        input.set_class_name("");
+        input.set_title("");
+    }
+
+    Ok(())
+}
+
+fn edit_screen_show_field_errors(fields: &[core::SFieldError]) -> Result<(), JsError> {
+    edit_screen_clear_field_errors()?;
+
+    for field in fields {
+        if let Some((_, id)) = EDIT_SCREEN_FIELD_INPUTS.iter().find(|(name, _)| *name == field.field.as_str()) {
+            let input = document().get_typed_element_by_id::<HtmlElement>(id).to_jserr()?;
+            input.set_class_name("invalid_field");
+            input.set_title(field.message.as_str());
+        }
+    }
+
+    Ok(())
+}
+
 fn edit_screen_update_text() -> Result<(), JsError> {
 
     let app = APP.try_read().expect("Should never actually have contention");
@@ -666,6 +746,7 @@ fn edit_screen_update_text() -> Result<(), JsError> {
     Ok(())
 }
 fn edit_game(game: core::SCollectionGame) -> Result<(), JsError> {
+    edit_screen_clear_field_errors()?;
     edit_screen_populate_game_info(&game.game_info)?;
     edit_screen_populate_how_long_to_beat(&game.how_long_to_beat)?;
     edit_screen_populate_custom_info(&game.custom_info)?;
@@ -687,6 +768,7 @@ fn edit_game(game: core::SCollectionGame) -> Result<(), JsError> {
 }
 
 fn add_game(game: core::SAddCollectionGame) -> Result<(), JsError> {
+    edit_screen_clear_field_errors()?;
     edit_screen_populate_game_info(&game.game_info)?;
     edit_screen_populate_how_long_to_beat(&game.how_long_to_beat)?;
     edit_screen_populate_custom_info(&game.custom_info)?;
@@ -788,6 +870,26 @@ fn select_value(id: &str) -> Result<String, JsError> {
 }
 
 
+// -- values of the selected options, empty if the select isn't on the page
+fn multi_select_values(id: &str) -> Result<Vec<String>, JsError> {
+    let select = match document().get_typed_element_by_id::<HtmlSelectElement>(id) {
+        Ok(s) => s,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    let mut values = Vec::new();
+    for i in 0..select.length() {
+        if let Some(option) = select.item(i) {
+            let option = option.dyn_into::<HtmlOptionElement>().or(Err(JsError::new(format!("{} has a non-option child", id).as_str())))?;
+            if option.selected() {
+                values.push(option.value());
+            }
+        }
+    }
+
+    Ok(values)
+}
+
 fn checkbox_value(id: &str) -> Result<bool, JsError> {
     Ok(document().get_typed_element_by_id::<HtmlInputElement>(id).to_jserr()?.checked())
 }
@@ -854,29 +956,69 @@ fn update_choose_state_from_edit_screen(choose_state: &mut core::SGameChooseStat
     Ok(())
 }
 
-async fn edit_screen_submit_edit_helper(game: &mut core::SCollectionGame) -> Result<(), JsError> {
-    update_game_info_from_edit_screen(&mut game.game_info)?;
-    update_how_long_to_beat_from_edit_screen(&mut game.how_long_to_beat)?;
-    update_custom_info_from_edit_screen(&mut game.custom_info)?;
-    update_choose_state_from_edit_screen(&mut game.choose_state)?;
+// -- returns false if the server rejected the change, in which case we stay on the edit screen
+fn edit_screen_handle_submit_result(result: Result<(), core::EError>) -> Result<bool, JsError> {
+    match result {
+        Ok(()) => Ok(true),
+        Err(e) => {
+            if let core::EError::Validation(fields) = &e {
+                edit_screen_show_field_errors(fields)?;
+            }
+            show_error(e)?;
+            Ok(false)
+        },
+    }
+}
 
-    let _sl = SShowLoadingHelper::new();
-    if let Err(e) = server_api::edit_game(game.clone()).await {
-        show_error(e)?;
+// -- only sends the sections that changed, and updates game to the server's copy on success
+async fn edit_screen_submit_edit_helper(game: &mut core::SCollectionGame) -> Result<bool, JsError> {
+    let mut edited = game.clone();
+    update_game_info_from_edit_screen(&mut edited.game_info)?;
+    update_how_long_to_beat_from_edit_screen(&mut edited.how_long_to_beat)?;
+    update_custom_info_from_edit_screen(&mut edited.custom_info)?;
+    update_choose_state_from_edit_screen(&mut edited.choose_state)?;
+
+    let request = core::SGamePatchRequest{
+        internal_id: game.internal_id,
+        expected_revision: Some(game.revision),
+        patch: core::SGamePatch::diff(game, &edited),
+    };
+    if request.patch.is_empty() {
+        return Ok(true);
+    }
+
+    let sl = SShowLoadingHelper::new();
+    let result = server_api::patch_game(&request).await;
+    drop(sl);
+
+    match result {
+        Ok(updated) => {
+            *game = updated;
+            Ok(true)
+        },
+        Err(core::EError::Conflict(_)) => {
+            // -- changed on another device since we loaded it, offer to start over from the latest copy
+            let message = "This game was changed on another device since you opened it. Reload it and redo your edits?";
+            if web::window().confirm_with_message(message).to_jserr()? {
+                let _sl = SShowLoadingHelper::new();
+                match server_api::get_game(game.internal_id).await {
+                    Ok(fresh) => *game = fresh,
+                    Err(e) => show_error(e)?,
+                }
+            }
+            Ok(false)
+        },
+        Err(e) => edit_screen_handle_submit_result(Err(e)),
     }
-    Ok(())
 }
 
-async fn edit_screen_submit_add_helper(mut game: core::SAddCollectionGame) -> Result<(), JsError> {
+async fn edit_screen_submit_add_helper(mut game: core::SAddCollectionGame) -> Result<bool, JsError> {
     update_game_info_from_edit_screen(&mut game.game_info)?;
     update_how_long_to_beat_from_edit_screen(&mut game.how_long_to_beat)?;
     update_custom_info_from_edit_screen(&mut game.custom_info)?;
 
     let _sl = SShowLoadingHelper::new();
-    if let Err(e) = server_api::add_game(game.clone()).await {
-        show_error(e)?;
-    }
-    Ok(())
+    edit_screen_handle_submit_result(server_api::add_game(game).await)
 }
 
 #[wasm_bindgen]
@@ -945,8 +1087,13 @@ pub async fn edit_screen_submit() -> Result<(), JsError> {
             p.set_inner_text("ERROR: edit screen had no valid game");
         },
         EGameEdit::Add(add_game) => {
-            match edit_screen_submit_add_helper(add_game).await {
-                Ok(_) => p.set_inner_text("Successfully added game"),
+            match edit_screen_submit_add_helper(add_game.clone()).await {
+                Ok(true) => p.set_inner_text("Successfully added game"),
+                Ok(false) => {
+                    let mut app = APP.try_write().expect("Should never actually have contention");
+                    app.game_edit = EGameEdit::Add(add_game);
+                    return Ok(());
+                },
                 Err(e) => {
                     p.set_inner_text("Failed to add game.");
                     return Err(e);
@@ -954,11 +1101,39 @@ pub async fn edit_screen_submit() -> Result<(), JsError> {
             }
         },
         EGameEdit::Edit(internal_id) => {
-            let mut app = APP.try_write().expect("Should never actually have contention");
-            let game = cached_collection_game_by_id_mut(&mut app.collection_game_cache, internal_id).ok_or(JsError::new("Submitting edits to game that isn't in cache"))?;
-
-            match edit_screen_submit_edit_helper(game).await {
-                Ok(_) => p.set_inner_text("Successfully edited game"),
+            // -- edit a copy, so the cache only changes if the server accepts the patch
+            let mut game = {
+                let mut app = APP.try_write().expect("Should never actually have contention");
+                cached_collection_game_by_id_mut(&mut app.collection_game_cache, internal_id)
+                    .ok_or(JsError::new("Submitting edits to game that isn't in cache"))?
+                    .clone()
+            };
+
+            match edit_screen_submit_edit_helper(&mut game).await {
+                Ok(true) => {
+                    let mut app = APP.try_write().expect("Should never actually have contention");
+                    app.collection_game_cache.insert(internal_id, game);
+                    p.set_inner_text("Successfully edited game");
+                },
+                Ok(false) => {
+                    // -- game may have been reloaded after a conflict, refill the screen if so
+                    let reloaded = {
+                        let mut app = APP.try_write().expect("Should never actually have contention");
+                        let cached_revision = cached_collection_game_by_id_mut(&mut app.collection_game_cache, internal_id).map(|g| g.revision);
+                        if cached_revision != Some(game.revision) {
+                            app.collection_game_cache.insert(internal_id, game.clone());
+                            true
+                        }
+                        else {
+                            app.game_edit = EGameEdit::Edit(internal_id);
+                            false
+                        }
+                    };
+                    if reloaded {
+                        edit_game(game)?;
+                    }
+                    return Ok(());
+                },
                 Err(e) => {
                     p.set_inner_text("Failed to edit game.");
                     return Err(e);
@@ -971,18 +1146,21 @@ pub async fn edit_screen_submit() -> Result<(), JsError> {
     Ok(())
 }
 
-fn populate_sessions_screen_list(sessions: Vec<core::SSessionAndCollectionGame>) -> Result<(), JsError> {
+fn populate_sessions_screen_list(sessions: Vec<core::SSessionAndCollectionGame>, append: bool) -> Result<(), JsError> {
     let document = document();
 
     let output_elem = document.get_typed_element_by_id::<HtmlDivElement>("session_screen_session_list").to_jserr()?;
-    output_elem.set_inner_html("");
+    if !append {
+        output_elem.set_inner_html("");
+    }
 
+    let config = APP.try_read().expect("Should never actually have contention").config().to_jserr()?;
     for session in &sessions {
         let session_div = document.create_element_typed::<HtmlDivElement>().to_jserr()?;
         output_elem.append_child(&session_div).to_jserr()?;
 
         // create the game card
-        let mut game_card = SGameCard::new_from_collection_game(&session.collection_game)?;
+        let mut game_card = SGameCard::new_from_collection_game(&config, &session.collection_game)?;
         game_card.show_release_date().regen()?;
 
         session_div.append_child(&game_card.main_div).to_jserr()?;
@@ -996,7 +1174,9 @@ fn populate_sessions_screen_list(sessions: Vec<core::SSessionAndCollectionGame>)
     // -- cache results for later use
     {
         let mut app = APP.try_write().expect("Should never actually have contention.");
-        app.session_screen_sessions.clear();
+        if !append {
+            app.session_screen_sessions.clear();
+        }
         for session_and_game in sessions {
             app.session_screen_sessions.push(session_and_game.session);
             let internal_id = session_and_game.collection_game.internal_id;
@@ -1013,11 +1193,12 @@ fn populate_collection_screen_game_list(games: Vec<core::SCollectionGame>) -> Re
     let output_elem = doc.get_typed_element_by_id::<HtmlDivElement>("collection_screen_game_list").to_jserr()?;
     output_elem.set_inner_html("");
 
+    let config = APP.try_read().expect("Should never actually have contention").config().to_jserr()?;
     for game in &games {
         let collection_item_div = doc.create_element_typed::<HtmlDivElement>().to_jserr()?;
         output_elem.append_child(&collection_item_div).to_jserr()?;
 
-        let mut game_card = SGameCard::new_from_collection_game(&game)?;
+        let mut game_card = SGameCard::new_from_collection_game(&config, &game)?;
 
         game_card
             .show_release_date()
@@ -1049,17 +1230,27 @@ fn populate_collection_screen_game_list(games: Vec<core::SCollectionGame>) -> Re
     Ok(())
 }
 
-fn populate_full_collection_screen_game_list(app: &mut SAppState, games: Vec<core::SCollectionGame>, badge: ECompactGameCardBadge) -> Result<(), JsError> {
+fn populate_full_collection_screen_game_list(app: &mut SAppState, games: Vec<core::SCollectionGame>, badge: ECompactGameCardBadge, append: bool) -> Result<(), JsError> {
     let doc = document();
 
     let output_elem = doc.get_typed_element_by_id::<HtmlDivElement>("full_collection_screen_game_list").to_jserr()?;
-    output_elem.set_inner_html("");
+    if !append {
+        output_elem.set_inner_html("");
+
+        // -- keep the selection for games that are still shown, e.g. after a refresh
+        let shown : HashSet<u32> = games.iter().map(|g| g.internal_id).collect();
+        app.full_collection_selected.retain(|id| shown.contains(id));
+        app.full_collection_shown.clear();
+    }
 
     let config = app.config().to_jserr()?;
     for game in &games {
         let game_card = SCompactGameCard::new_from_collection_game(&config, &game, badge)?;
+        game_card.add_select_checkbox(game.internal_id, app.full_collection_selected.contains(&game.internal_id))?;
         output_elem.append_child(&game_card.main_div).to_jserr()?;
+        app.full_collection_shown.push(game.internal_id);
     }
+    update_bulk_selection_count(app)?;
 
     // -- cache results for later use
     {
@@ -1078,10 +1269,10 @@ fn show_result(msg: &str) -> Result<(), JsError> {
     Ok(())
 }
 
-fn show_error(e: String) -> Result<(), JsError> {
+fn show_error<E: std::fmt::Display>(e: E) -> Result<(), JsError> {
     element("popup_overlay")?.style().set_property("display", "block").to_jserr()?;
     element("error_message")?.style().set_property("display", "block").to_jserr()?;
-    element("error_message_content")?.set_inner_text(e.as_str());
+    element("error_message_content")?.set_inner_text(e.to_string().as_str());
     Ok(())
 }
 
@@ -1102,22 +1293,85 @@ pub async fn session_screen_apply_filter() -> Result<(), JsError> {
         None
     };
 
+    let request = core::SGetSessionsRequest {
+        filter: core::SSessionFilter {
+            game_id: None,
+            active_only: checkbox_value("sessions_screen_filter_active")?,
+            memorable_only: checkbox_value("sessions_screen_filter_memorable")?,
+            year,
+        },
+        sort: core::SSessionSort {
+            key: core::ESessionSortKey::StartDate,
+            descending: true,
+        },
+        page: core::SPageRequest::first(SESSIONS_PAGE_SIZE),
+    };
+
+    load_sessions_screen_page(request, false).await
+}
+
+#[wasm_bindgen]
+pub async fn session_screen_show_more() -> Result<(), JsError> {
+    let next_page = {
+        let app = APP.try_read().expect("Should never actually have contention.");
+        app.session_screen_next_page.clone()
+    };
+
+    match next_page {
+        Some(request) => load_sessions_screen_page(request, true).await,
+        None => Ok(()),
+    }
+}
+
+async fn load_sessions_screen_page(request: core::SGetSessionsRequest, append: bool) -> Result<(), JsError> {
     let sl = SShowLoadingHelper::new();
-    let sessions = match server_api::get_sessions(
-        None,
-        checkbox_value("sessions_screen_filter_active")?,
-        checkbox_value("sessions_screen_filter_memorable")?,
-        year,
-    ).await {
-        Ok(s) => s,
+    let page = match server_api::get_sessions(&request).await {
+        Ok(p) => p,
         Err(e) => {
             show_error(e)?;
             return Ok(());
         }
     };
-
     drop(sl);
-    populate_sessions_screen_list(sessions)?;
+
+    let next_page = next_page_request(&page, request.page).map(|next| core::SGetSessionsRequest {
+        page: next,
+        ..request
+    });
+    update_show_more_button("session_screen_show_more", &page)?;
+
+    {
+        let mut app = APP.try_write().expect("Should never actually have contention.");
+        app.session_screen_next_page = next_page;
+    }
+
+    populate_sessions_screen_list(page.items, append)?;
+
+    Ok(())
+}
+
+// -- the request for the page after this one, if there is one
+fn next_page_request<T>(page: &core::SPage<T>, previous: core::SPageRequest) -> Option<core::SPageRequest> {
+    if !page.has_more() {
+        return None;
+    }
+
+    Some(core::SPageRequest {
+        offset: page.offset + page.items.len(),
+        limit: previous.limit,
+    })
+}
+
+fn update_show_more_button<T>(id: &str, page: &core::SPage<T>) -> Result<(), JsError> {
+    let button = document().get_typed_element_by_id::<HtmlButtonElement>(id).to_jserr()?;
+    if page.has_more() {
+        let remaining = page.total - page.offset - page.items.len();
+        button.set_inner_text(format!("Show more ({} remaining)", remaining).as_str());
+        button.style().set_property("display", "block").to_jserr()?;
+    }
+    else {
+        button.style().set_property("display", "none").to_jserr()?;
+    }
 
     Ok(())
 }
@@ -1164,8 +1418,8 @@ pub async fn collection_screen_search() -> Result<(), JsError> {
 #[wasm_bindgen]
 pub async fn update_igdb_games() -> Result<(), JsError> {
     let sl = SShowLoadingHelper::new();
-    match server_api::update_igdb_games().await {
-        Ok(g) => g,
+    let job = match server_api::start_igdb_update().await {
+        Ok(j) => j,
         Err(e) => {
             show_error(e)?;
             return Ok(());
@@ -1173,76 +1427,228 @@ pub async fn update_igdb_games() -> Result<(), JsError> {
     };
     drop(sl);
 
+    show_job_progress(&job)
+}
+
+fn show_job_progress(job: &core::SJob) -> Result<(), JsError> {
+    let failed = if job.errors.is_empty() {
+        String::new()
+    }
+    else {
+        let titles : Vec<&str> = job.errors.iter().map(|e| e.title.as_str()).collect();
+        format!(", {} failed: {}", job.errors.len(), titles.join(", "))
+    };
+
+    let text = match &job.state {
+        core::EJobState::Running => match &job.current_title {
+            Some(title) => format!("Updating \"{}\" ({} of {}){}", title, job.done + 1, job.total, failed),
+            None => format!("Updating from IGDB ({} of {}){}", job.done, job.total, failed),
+        },
+        core::EJobState::Finished => format!("IGDB update finished, {} of {} games checked{}", job.done, job.total, failed),
+        core::EJobState::Failed{message} => format!("IGDB update failed: {}", message),
+    };
+
+    element("collection_screen_igdb_progress")?.set_inner_text(text.as_str());
     Ok(())
 }
 
-async fn enter_full_collection_screen() -> Result<(), JsError> {
-    let mut app = APP.try_write().expect("Should never actually have contention");
+// -- jobs keep running on the server across page reloads, pick up the latest one
+async fn restore_job_progress() -> Result<(), JsError> {
+    let jobs = match server_api::get_jobs().await {
+        Ok(j) => j,
+        Err(e) => {
+            weblog!("could not load jobs: {}", e);
+            return Ok(());
+        }
+    };
 
-    use gamechooser_core::{SCollectionGameFilter, SCollectionGameSessionStateFilter, SCollectionGameAndSessionStateFilter};
+    if let Some(job) = jobs.iter().filter(|j| j.kind == core::EJobKind::IGDBUpdate).last() {
+        show_job_progress(job)?;
+    }
 
-    enum ESortFor {
-        None,
-        NotReleased,
-        NewestReleases,
+    Ok(())
+}
+
+// -- listens to the server's change stream so edits made on other devices show up here
+fn subscribe_to_changes() -> Result<(), JsError> {
+    let mut app = APP.try_write().expect("Should never actually have contention.");
+    if app.subscribed_to_changes {
+        return Ok(());
     }
-    let mut sort_for = ESortFor::None;
-    let mut badge = ECompactGameCardBadge::None;
 
-    let filter : SCollectionGameAndSessionStateFilter = match select_value("full_collection_filter")?.as_str() {
-        "all" => {
-            badge = ECompactGameCardBadge::AliveState;
-            SCollectionGameFilter::new().into()
-        },
-        "live" => SCollectionGameFilter::new().require_alive(true).into(),
-        "owned_dead_unplayed" =>
-            SCollectionGameAndSessionStateFilter::with_session_filter(
-                SCollectionGameFilter::new()
-                    .require_tag_pick_up_and_play(false)
-                    .require_ownership(true)
-                    .require_alive(false),
-                SCollectionGameSessionStateFilter::new()
-                    .max_sessions(0),
-            ),
-        "live_released_no_hltb" => {
-            badge = ECompactGameCardBadge::HLTBSearch;
-            SCollectionGameFilter::new()
-                .require_alive(true)
-                .require_released(true)
-                .require_no_hltb_data().into()
-        },
-        "not_released" => {
-            sort_for = ESortFor::NotReleased;
-            badge = ECompactGameCardBadge::ReleaseDate;
-            SCollectionGameFilter::new().require_released(false).into()
+    let event_source = EventSource::new("/events").to_jserr()?;
+    let onmessage = Function::new_with_args("event", "handle_change_event(event.data)");
+    event_source.set_onmessage(Some(&onmessage));
+
+    app.subscribed_to_changes = true;
+    Ok(())
+}
+
+fn section_visible(id: &str) -> Result<bool, JsError> {
+    let display = div(id)?.style().get_property_value("display").to_jserr()?;
+    Ok(display == "block")
+}
+
+#[wasm_bindgen]
+pub async fn handle_change_event(data: String) -> Result<(), JsError> {
+    let event : core::EChangeEvent = match serde_json::from_str(data.as_str()) {
+        Ok(e) => e,
+        Err(e) => {
+            weblog!("could not parse change event: {:?}", e);
+            return Ok(());
+        }
+    };
+
+    if let core::EChangeEvent::JobUpdated{job} = &event {
+        return show_job_progress(job);
+    }
+
+    if let core::EChangeEvent::ReleaseDay{date, games} = &event {
+        match APP.try_write() {
+            Ok(mut app) if app.release_day_shown != Some(*date) => app.release_day_shown = Some(*date),
+            _ => return Ok(()),
+        }
+
+        let titles : Vec<&str> = games.iter().map(|g| g.game_info.title()).collect();
+        let message = format!("Out today: {}", titles.join(", "));
+        web::window().alert_with_message(message.as_str()).to_jserr()?;
+        return Ok(());
+    }
+
+    // -- another screen is mid-request, it will show fresh data when it finishes
+    let details_game = match APP.try_write() {
+        Ok(mut app) => {
+            if let Some(game) = event.game() {
+                // -- our own changes arrive here too, those are already in the cache
+                if app.collection_game_cache.get(&game.internal_id).map_or(false, |g| g.revision >= game.revision) {
+                    return Ok(());
+                }
+                app.collection_game_cache.insert(game.internal_id, game.clone());
+            }
+
+            match app.details_screen_game {
+                Some(internal_id) => app.collection_game_cache.get(&internal_id).cloned(),
+                None => None,
+            }
         },
-        "new_releases" => {
-            sort_for = ESortFor::NewestReleases;
-            badge = ECompactGameCardBadge::ReleaseDate;
-            SCollectionGameAndSessionStateFilter::with_session_filter(
-                SCollectionGameFilter::new()
-                    .require_alive(true)
-                    .require_released(true).into(),
-                SCollectionGameSessionStateFilter::new()
-                    .max_sessions(0),
-            )
+        Err(_) => return Ok(()),
+    };
+
+    // -- refresh whatever is on screen, but never the edit screen so in progress edits are kept
+    match &event {
+        core::EChangeEvent::SessionStarted{..} | core::EChangeEvent::SessionFinished{..} => {
+            if section_visible("sessions_div")? {
+                enter_sessions_screen().await?;
+            }
         },
-        "to_buy" => {
-            SCollectionGameFilter::new()
-                .require_alive(true)
-                .require_released(true)
-                .require_ownership(false)
-                .into()
+        core::EChangeEvent::FilterPresetsChanged => {
+            if let Ok(mut app) = APP.try_write() {
+                app.filter_presets = None;
+            }
+            if section_visible("full_collection_div")? {
+                enter_full_collection_screen().await?;
+            }
         },
-        _ => {
-            show_error(String::from("Invalid value from full_collection_filter select."))?;
+        _ => {},
+    }
+
+    if let Some(game) = event.game() {
+        if section_visible("collection_div")? {
+            let search = document().get_typed_element_by_id::<HtmlInputElement>("collection_search_input").to_jserr()?;
+            if search.value().is_empty() {
+                enter_collection_screen().await?;
+            }
+            else {
+                collection_screen_search().await?;
+            }
+        }
+        else if section_visible("full_collection_div")? {
+            enter_full_collection_screen().await?;
+        }
+        else if section_visible("game_details_div")? {
+            if let Some(details_game) = details_game {
+                if details_game.internal_id == game.internal_id {
+                    view_details(details_game).await?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// -- fills the full collection filter select from the server presets, keeping the current selection if it still exists
+async fn load_filter_presets(app: &mut SAppState) -> Result<(), String> {
+    let presets = server_api::get_filter_presets().await.to_str_err()?;
+
+    let select = document().get_typed_element_by_id::<HtmlSelectElement>("full_collection_filter")?;
+    let previous = select.value();
+    select.set_inner_html("");
+
+    for preset in &presets {
+        let option = HtmlOptionElement::new_with_text_and_value(preset.display_name.as_str(), preset.name.as_str()).to_str_err()?;
+        select.append_child(&option).to_str_err()?;
+    }
+
+    if presets.iter().any(|p| p.name == previous) {
+        select.set_value(previous.as_str());
+    }
+
+    app.filter_presets = Some(presets);
+
+    Ok(())
+}
+
+async fn enter_full_collection_screen() -> Result<(), JsError> {
+    let mut app = APP.try_write().expect("Should never actually have contention");
+
+    if app.filter_presets.is_none() {
+        let sl = SShowLoadingHelper::new();
+        let load_result = load_filter_presets(&mut app).await;
+        drop(sl);
+
+        if let Err(e) = load_result {
+            show_error(e)?;
+            return Ok(());
+        }
+    }
+
+    let selected = select_value("full_collection_filter")?;
+    let preset = match app.filter_presets.as_ref().and_then(|presets| presets.iter().find(|p| p.name == selected)) {
+        Some(p) => p.clone(),
+        None => {
+            show_error(format!("No filter preset named \"{}\".", selected))?;
             return Ok(());
         },
     };
 
+    // -- a typed query replaces the selected preset's filter
+    let query = document().get_typed_element_by_id::<HtmlInputElement>("full_collection_query").to_jserr()?.value();
+    let filter = if query.trim().is_empty() {
+        core::EGameFilterRequest::Expression(preset.filter.clone())
+    }
+    else {
+        core::EGameFilterRequest::Query(query)
+    };
+
+    app.full_collection_filter = Some(filter.clone());
+    populate_bulk_action_select()?;
+
+    let request = core::SGetGamesRequest::new(filter)
+        .sort(preset.sort)
+        .page(core::SPageRequest::first(FULL_COLLECTION_PAGE_SIZE));
+
+    load_full_collection_page(&mut app, request, preset.badge, false).await?;
+
+    swap_section_div("full_collection_div")?;
+
+    Ok(())
+}
+
+async fn load_full_collection_page(app: &mut SAppState, request: core::SGetGamesRequest, badge: ECompactGameCardBadge, append: bool) -> Result<(), JsError> {
     let sl = SShowLoadingHelper::new();
-    let mut games = match server_api::get_games(filter).await {
-        Ok(g) => g,
+    let page = match server_api::get_games(&request).await {
+        Ok(p) => p,
         Err(e) => {
             show_error(e)?;
             return Ok(());
@@ -1250,29 +1656,260 @@ async fn enter_full_collection_screen() -> Result<(), JsError> {
     };
     drop(sl);
 
-    match sort_for {
-        ESortFor::None => (),
-        ESortFor::NotReleased => {
-            games.sort_by(|a, b| a.game_info.release_date().cmp(&b.game_info.release_date()));
+    app.full_collection_next_page = next_page_request(&page, request.page).map(|next| (request.page(next), badge));
+    update_show_more_button("full_collection_show_more", &page)?;
+
+    populate_full_collection_screen_game_list(app, page.items, badge, append)?;
+
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub async fn full_collection_show_more() -> Result<(), JsError> {
+    let mut app = APP.try_write().expect("Should never actually have contention");
+
+    if let Some((request, badge)) = app.full_collection_next_page.take() {
+        load_full_collection_page(&mut app, request, badge, true).await?;
+    }
+
+    Ok(())
+}
+
+enum EBulkAction {
+    Edit(core::SBulkEdit),
+    SetVia,
+    SetHLTBHours,
+}
+
+// -- the option values in the bulk action select are indices into this list
+fn bulk_actions() -> Vec<(String, EBulkAction)> {
+    let mut actions = Vec::new();
+
+    for tag in core::EGameTag::ALL {
+        actions.push((format!("Tag {}", tag.name()), EBulkAction::Edit(core::SBulkEdit::new().tag(tag, true))));
+        actions.push((format!("Untag {}", tag.name()), EBulkAction::Edit(core::SBulkEdit::new().tag(tag, false))));
+    }
+
+    core::SOwn::default().each(|_, store| {
+        actions.push((format!("Owned on {}", store), EBulkAction::Edit(core::SBulkEdit::new().own(store, true))));
+    });
+    core::SOwn::default().each(|_, store| {
+        actions.push((format!("Not owned on {}", store), EBulkAction::Edit(core::SBulkEdit::new().own(store, false))));
+    });
+
+    actions.push((String::from("Archive"), EBulkAction::Edit(core::SBulkEdit::new().archived(true))));
+    actions.push((String::from("Unarchive"), EBulkAction::Edit(core::SBulkEdit::new().archived(false))));
+    actions.push((String::from("Set via"), EBulkAction::SetVia));
+    actions.push((String::from("Set HLTB hours"), EBulkAction::SetHLTBHours));
+    actions.push((String::from("Set HLTB unknown"), EBulkAction::Edit(core::SBulkEdit::new().how_long_to_beat(core::EHowLongToBeat::Unknown))));
+    actions.push((String::from("Set cannot be beaten"), EBulkAction::Edit(core::SBulkEdit::new().how_long_to_beat(core::EHowLongToBeat::CannotBeBeaten))));
+    actions.push((String::from("Retire"), EBulkAction::Edit(core::SBulkEdit::new().choose_state(core::EChooseStateAction::Retire))));
+    actions.push((String::from("Reset choose state"), EBulkAction::Edit(core::SBulkEdit::new().choose_state(core::EChooseStateAction::Reset))));
+
+    actions
+}
+
+fn populate_bulk_action_select() -> Result<(), JsError> {
+    let select = document().get_typed_element_by_id::<HtmlSelectElement>("full_collection_bulk_action").to_jserr()?;
+    if select.length() > 0 {
+        return Ok(());
+    }
+
+    for (i, (label, _)) in bulk_actions().iter().enumerate() {
+        let option = HtmlOptionElement::new_with_text_and_value(label.as_str(), i.to_string().as_str()).to_jserr()?;
+        select.append_child(&option).to_jserr()?;
+    }
+
+    Ok(())
+}
+
+fn update_bulk_selection_count(app: &SAppState) -> Result<(), JsError> {
+    let text = format!("{} selected", app.full_collection_selected.len());
+    element("full_collection_bulk_count")?.set_inner_text(text.as_str());
+    Ok(())
+}
+
+#[wasm_bindgen]
+pub fn full_collection_toggle_selected(internal_id: u32, selected: bool) -> Result<(), JsError> {
+    let mut app = APP.try_write().expect("Should never actually have contention");
+
+    if selected {
+        app.full_collection_selected.insert(internal_id);
+    }
+    else {
+        app.full_collection_selected.remove(&internal_id);
+    }
+
+    update_bulk_selection_count(&app)
+}
+
+fn set_all_shown_selected(selected: bool) -> Result<(), JsError> {
+    let mut app = APP.try_write().expect("Should never actually have contention");
+
+    if selected {
+        let shown = app.full_collection_shown.clone();
+        app.full_collection_selected.extend(shown);
+    }
+    else {
+        app.full_collection_selected.clear();
+    }
+
+    for internal_id in &app.full_collection_shown {
+        let id = format!("compact_game_card_select_{}", internal_id);
+        document().get_typed_element_by_id::<HtmlInputElement>(id.as_str()).to_jserr()?.set_checked(selected);
+    }
+
+    update_bulk_selection_count(&app)
+}
+
+#[wasm_bindgen]
+pub fn full_collection_select_all_shown() -> Result<(), JsError> {
+    set_all_shown_selected(true)
+}
+
+#[wasm_bindgen]
+pub fn full_collection_clear_selection() -> Result<(), JsError> {
+    set_all_shown_selected(false)
+}
+
+// -- all_matching applies to every game the current filter or query matches, not just the loaded pages
+#[wasm_bindgen]
+pub async fn full_collection_bulk_apply(all_matching: bool) -> Result<(), JsError> {
+    let index : usize = select_value("full_collection_bulk_action")?.parse().unwrap_or(0);
+    let (label, action) = match bulk_actions().into_iter().nth(index) {
+        Some(a) => a,
+        None => return Ok(()),
+    };
+
+    let value = document().get_typed_element_by_id::<HtmlInputElement>("full_collection_bulk_value").to_jserr()?.value();
+    let edit = match action {
+        EBulkAction::Edit(edit) => edit,
+        EBulkAction::SetVia => core::SBulkEdit::new().via(value.trim()),
+        EBulkAction::SetHLTBHours => match value.trim().parse::<u16>() {
+            Ok(hours) => core::SBulkEdit::new().how_long_to_beat(core::EHowLongToBeat::Manual(hours)),
+            Err(_) => {
+                show_error(format!("\"{}\" is not a number of hours.", value))?;
+                return Ok(());
+            },
+        },
+    };
+
+    let (target, confirm_message) = {
+        let app = APP.try_read().expect("Should never actually have contention");
+        if all_matching {
+            let filter = match &app.full_collection_filter {
+                Some(f) => f.clone(),
+                None => return Ok(()),
+            };
+            (core::EBulkEditTarget::Filter(filter), format!("{} for every game matching the current filter?", label))
+        }
+        else {
+            if app.full_collection_selected.is_empty() {
+                show_error("No games selected.")?;
+                return Ok(());
+            }
+            let ids : Vec<u32> = app.full_collection_selected.iter().cloned().collect();
+            let message = format!("{} for {} selected games?", label, ids.len());
+            (core::EBulkEditTarget::Games(ids), message)
+        }
+    };
+
+    if !web::window().confirm_with_message(confirm_message.as_str()).to_jserr()? {
+        return Ok(());
+    }
+
+    let request = core::SBulkEditRequest{ target, edit };
+    let sl = SShowLoadingHelper::new();
+    let result = server_api::bulk_edit_games(&request).await;
+    drop(sl);
+
+    if let Err(e) = result {
+        show_error(e)?;
+        return Ok(());
+    }
+
+    {
+        let mut app = APP.try_write().expect("Should never actually have contention");
+        app.full_collection_selected.clear();
+    }
+
+    enter_full_collection_screen().await
+}
+
+#[wasm_bindgen]
+pub async fn full_collection_save_query_as_preset() -> Result<(), JsError> {
+    let query = document().get_typed_element_by_id::<HtmlInputElement>("full_collection_query").to_jserr()?.value();
+
+    let filter = match core::parse_collection_query(query.as_str()) {
+        Ok(q) => q.into_filter_expr(),
+        Err(e) => {
+            show_error(e.to_string())?;
+            return Ok(());
         },
-        ESortFor::NewestReleases => {
-            use gamechooser_core::EReleaseDate::*;
+    };
+
+    let display_name = match web::window().prompt_with_message("Name for this filter preset:").to_jserr()? {
+        Some(n) if !n.trim().is_empty() => n,
+        _ => return Ok(()),
+    };
 
-            games.retain(|g| match g.game_info.release_date() {
-                UnknownUnreleased => false,
-                UnknownReleased => false,
-                Known(_) => true,
-            });
+    // -- key is derived from the display name, matching the style of the seeded presets
+    let name : String = display_name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
 
-            games.sort_by(|a, b| a.game_info.release_date().cmp(&b.game_info.release_date()).reverse());
+    let preset = core::SFilterPreset::new(name.as_str(), display_name.trim(), filter);
+
+    let sl = SShowLoadingHelper::new();
+    match server_api::add_filter_preset(&preset).await {
+        Ok(()) => {},
+        Err(core::EError::Conflict(_)) => {
+            show_error(format!("There is already a preset called \"{}\", pick a different name.", display_name.trim()))?;
+            return Ok(());
+        },
+        Err(e) => {
+            show_error(e)?;
+            return Ok(());
         },
     }
+    drop(sl);
 
-    populate_full_collection_screen_game_list(&mut app, games, badge)?;
+    {
+        let mut app = APP.try_write().expect("Should never actually have contention");
+        app.filter_presets = None;
+    }
 
-    swap_section_div("full_collection_div")?;
+    document().get_typed_element_by_id::<HtmlInputElement>("full_collection_query").to_jserr()?.set_value("");
+    enter_full_collection_screen().await?;
+    document().get_typed_element_by_id::<HtmlSelectElement>("full_collection_filter").to_jserr()?.set_value(name.as_str());
+    enter_full_collection_screen().await
+}
 
-    Ok(())
+#[wasm_bindgen]
+pub async fn full_collection_delete_preset() -> Result<(), JsError> {
+    let name = select_value("full_collection_filter")?;
+
+    let confirm_message = format!("Delete filter preset \"{}\"?", name);
+    if !web::window().confirm_with_message(confirm_message.as_str()).to_jserr()? {
+        return Ok(());
+    }
+
+    let sl = SShowLoadingHelper::new();
+    if let Err(e) = server_api::delete_filter_preset(name.as_str()).await {
+        show_error(e)?;
+        return Ok(());
+    }
+    drop(sl);
+
+    {
+        let mut app = APP.try_write().expect("Should never actually have contention");
+        app.filter_presets = None;
+    }
+
+    enter_full_collection_screen().await
 }
 
 fn cached_collection_game_by_id(app: &SAppState, internal_id: u32) -> Option<core::SCollectionGame> {
@@ -1335,7 +1972,8 @@ pub async fn start_session(internal_id: u32) -> Result<(), JsError> {
     let sl = SShowLoadingHelper::new();
     match server_api::start_session(game.internal_id).await {
         Ok(_) => p.set_inner_text("Successfully started session."),
-        Err(e) => p.set_inner_text(e.as_str()),
+        Err(core::EError::Conflict(_)) => p.set_inner_text("This game already has a session in progress."),
+        Err(e) => p.set_inner_text(e.to_string().as_str()),
     }
     drop(sl);
 
@@ -1363,7 +2001,7 @@ pub async fn session_screen_finish_session(internal_id: u32) -> Result<(), JsErr
 
         result
     };
-    session_opt.ok_or(JsError::new("Somehow finishing session not in the list."))?;
+    let session = session_opt.ok_or(JsError::new("Somehow finishing session not in the list."))?;
 
     let memorable_checkbox_id = format!("session_screen_memorable_{}", internal_id);
     let memorable = checkbox_value(memorable_checkbox_id.as_str())?;
@@ -1376,8 +2014,16 @@ pub async fn session_screen_finish_session(internal_id: u32) -> Result<(), JsErr
 
     let p = document().get_typed_element_by_id::<HtmlParagraphElement>("result_message").to_jserr()?;
     let sl = SShowLoadingHelper::new();
-    match server_api::finish_session(internal_id, memorable, retire, set_ignore_passes).await {
+    match server_api::finish_session(internal_id, session.revision, memorable, retire, set_ignore_passes).await {
         Ok(_) => p.set_inner_text("Successfully finished session."),
+        Err(core::EError::Conflict(_)) => {
+            drop(sl);
+            let message = "This session was changed on another device. Reload the session list and try again?";
+            if web::window().confirm_with_message(message).to_jserr()? {
+                show_sessions().await?;
+            }
+            return Ok(());
+        },
         Err(_) => p.set_inner_text("Failed to finish session."),
     }
     drop(sl);
@@ -1390,17 +2036,55 @@ pub async fn session_screen_finish_session(internal_id: u32) -> Result<(), JsErr
 async fn commit_randomizer_choose_states(session: &SGameRandomizerSession, app: &SAppState) -> Result<(), JsError> {
     let sl = SShowLoadingHelper::new();
 
-    // -- build list of collection games to send to server
-    let mut collection_games = Vec::with_capacity(session.shuffled_internal_ids.len());
-    for internal_id in session.shuffled_internal_ids.iter().copied() {
+    // -- only choose_state is sent, so edits made elsewhere during the session are kept. Games past
+    // -- cur_idx were never shown, and unchanged games are skipped so their revisions stay put.
+    let mut patches = Vec::new();
+    for internal_id in session.shuffled_internal_ids.iter().take(session.cur_idx + 1).copied() {
         let game = cached_collection_game_by_id(app, internal_id).ok_or(JsError::new("randomizer game not in cache"))?;
-        collection_games.push(game);
+        if session.original_choose_states.get(&internal_id) == Some(&game.choose_state) {
+            continue;
+        }
+
+        patches.push(core::SGamePatchRequest{
+            internal_id,
+            expected_revision: Some(game.revision),
+            patch: core::SGamePatch::new().choose_state(game.choose_state),
+        });
     }
 
-    if let Err(e) = server_api::update_choose_state(&collection_games).await {
-        show_error(e)?;
+    if patches.is_empty() {
         return Ok(());
     }
+
+    match server_api::patch_games(&patches).await {
+        Ok(()) => {},
+        Err(core::EError::Conflict(_)) => {
+            drop(sl);
+            let message = "Some of these games were changed on another device while choosing. Reload them and save your choices anyway?";
+            if !web::window().confirm_with_message(message).to_jserr()? {
+                return Ok(());
+            }
+
+            let _sl = SShowLoadingHelper::new();
+            for patch in &mut patches {
+                match server_api::get_game(patch.internal_id).await {
+                    Ok(fresh) => patch.expected_revision = Some(fresh.revision),
+                    Err(e) => {
+                        show_error(e)?;
+                        return Ok(());
+                    }
+                }
+            }
+            if let Err(e) = server_api::patch_games(&patches).await {
+                show_error(e)?;
+            }
+            return Ok(());
+        },
+        Err(e) => {
+            show_error(e)?;
+            return Ok(());
+        },
+    }
     drop(sl);
 
     Ok(())
@@ -1425,7 +2109,8 @@ async fn populate_randomizer_choose_screen() -> Result<(), JsError> {
             let card_div = div("randomizer_game_card")?;
             card_div.set_inner_text("");
 
-            let mut game_card = SGameCard::new_from_collection_game(&game)?;
+            let config = app.config().to_jserr()?;
+            let mut game_card = SGameCard::new_from_collection_game(&config, &game)?;
             game_card
                 .show_igdb_link()
                 .show_via()
@@ -1510,15 +2195,95 @@ pub async fn randomizer_pick_up_and_play_mode_changed() -> Result<(), JsError> {
     Ok(())
 }
 
-#[wasm_bindgen]
-pub async fn randomizer_screen_start() -> Result<(), JsError> {
-    let mode = if checkbox_value("randomizer_pick_up_and_play_mode")? {
-        ERandomizerMode::PickUpAndPlay
+fn randomizer_mode() -> Result<ERandomizerMode, JsError> {
+    if checkbox_value("randomizer_pick_up_and_play_mode")? {
+        Ok(ERandomizerMode::PickUpAndPlay)
     }
     else {
-        ERandomizerMode::GameChooseAlg
+        Ok(ERandomizerMode::GameChooseAlg)
+    }
+}
+
+fn igdb_facet_select_id(facet: core::EIGDBFacet, include: bool) -> String {
+    let facet_id = match facet {
+        core::EIGDBFacet::Genre => "genre",
+        core::EIGDBFacet::Theme => "theme",
+        core::EIGDBFacet::GameMode => "game_mode",
     };
+    let action = if include { "include" } else { "exclude" };
+    format!("randomizer_screen_igdb_{}_{}", facet_id, action)
+}
+
+// -- rebuilds the genre, theme and game mode choices from what's in the collection, keeping anything already selected
+async fn populate_randomizer_igdb_facets() -> Result<(), JsError> {
+    let sl = SShowLoadingHelper::new();
+    let all_facet_options = match server_api::get_igdb_facet_options().await {
+        Ok(f) => f,
+        Err(e) => {
+            show_error(e)?;
+            return Ok(());
+        }
+    };
+    drop(sl);
+
+    let mut previous_selections = HashMap::new();
+    for facet in core::EIGDBFacet::ALL {
+        for include in [true, false] {
+            let id = igdb_facet_select_id(facet, include);
+            let values : HashSet<String> = multi_select_values(id.as_str())?.into_iter().collect();
+            previous_selections.insert(id, values);
+        }
+    }
 
+    let doc = document();
+    let facets_div = div("randomizer_screen_igdb_facets")?;
+    facets_div.set_inner_html("");
+
+    for facet_options in &all_facet_options {
+        if facet_options.options.is_empty() {
+            continue;
+        }
+
+        let p = doc.create_element_typed::<HtmlParagraphElement>().to_jserr()?;
+        let title = match facet_options.facet {
+            core::EIGDBFacet::Genre => "Genres",
+            core::EIGDBFacet::Theme => "Themes",
+            core::EIGDBFacet::GameMode => "Game modes",
+        };
+        p.set_inner_text(format!("{}: ", title).as_str());
+
+        for include in [true, false] {
+            let id = igdb_facet_select_id(facet_options.facet, include);
+
+            let label = doc.create_element("label").to_jserr()?;
+            label.set_attribute("for", id.as_str()).to_jserr()?;
+            label.set_text_content(Some(if include { " require " } else { " exclude " }));
+            p.append_child(&label).to_jserr()?;
+
+            let select = doc.create_element_typed::<HtmlSelectElement>().to_jserr()?;
+            select.set_id(id.as_str());
+            select.set_multiple(true);
+            select.set_class_name("igdb_facet_select");
+
+            let selected = previous_selections.get(&id);
+            for option in &facet_options.options {
+                let text = format!("{} ({})", option.value, option.game_count);
+                let option_elem = HtmlOptionElement::new_with_text_and_value(text.as_str(), option.value.as_str()).to_jserr()?;
+                option_elem.set_selected(selected.is_some_and(|s| s.contains(&option.value)));
+                select.append_child(&option_elem).to_jserr()?;
+            }
+
+            p.append_child(&select).to_jserr()?;
+        }
+
+        facets_div.append_child(&p).to_jserr()?;
+    }
+
+    Ok(())
+}
+
+// -- builds the filter from the current randomizer screen options
+fn randomizer_filter(mode: &ERandomizerMode) -> Result<core::SCollectionGameAndSessionStateFilter, JsError> {
     let filter = match mode {
         ERandomizerMode::PickUpAndPlay => {
             core::SCollectionGameFilter::new().require_tag_pick_up_and_play(true).into()
@@ -1573,6 +2338,15 @@ pub async fn randomizer_screen_start() -> Result<(), JsError> {
                 },
             }
 
+            for facet in core::EIGDBFacet::ALL {
+                for value in multi_select_values(igdb_facet_select_id(facet, true).as_str())? {
+                    game_filter = game_filter.require_igdb_facet(facet, value.as_str());
+                }
+                for value in multi_select_values(igdb_facet_select_id(facet, false).as_str())? {
+                    game_filter = game_filter.exclude_igdb_facet(facet, value.as_str());
+                }
+            }
+
             let mut session_state_filter = core::SCollectionGameSessionStateFilter::new()
                 .required_active_session(false);
             if checkbox_value("randomizer_screen_only_firsts")? {
@@ -1583,9 +2357,18 @@ pub async fn randomizer_screen_start() -> Result<(), JsError> {
         }
     };
 
+    Ok(filter)
+}
+
+#[wasm_bindgen]
+pub async fn randomizer_screen_start() -> Result<(), JsError> {
+    let mode = randomizer_mode()?;
+    let filter = randomizer_filter(&mode)?;
+
     let sl = SShowLoadingHelper::new();
-    let games = match server_api::get_games(filter).await {
-        Ok(gs) => gs,
+    let request = core::SGetGamesRequest::new(core::EGameFilterRequest::Legacy(filter));
+    let games = match server_api::get_games(&request).await {
+        Ok(page) => page.items,
         Err(e) => {
             show_error(e)?;
             return Ok(());
@@ -1599,6 +2382,7 @@ pub async fn randomizer_screen_start() -> Result<(), JsError> {
         let mut app = APP.try_write().expect("Should never actually have contention.");
 
         let mut internal_id_list = Vec::with_capacity(games.len());
+        let mut original_choose_states = HashMap::with_capacity(games.len());
 
         let mut shuffled_indices = Vec::with_capacity(games.len());
         for i in 0..games.len() {
@@ -1613,11 +2397,13 @@ pub async fn randomizer_screen_start() -> Result<(), JsError> {
             let internal_id = game.internal_id;
             app.collection_game_cache.insert(internal_id, game.clone());
             internal_id_list.push(internal_id);
+            original_choose_states.insert(internal_id, game.choose_state);
         }
 
         app.game_randomizer = EGameRandomizer::Choosing(SGameRandomizerSession{
             mode,
             shuffled_internal_ids: internal_id_list,
+            original_choose_states,
             cur_idx: 0,
         });
     }
@@ -1787,6 +2573,64 @@ pub async fn game_details_edit() -> Result<(), JsError> {
     Ok(())
 }
 
+#[wasm_bindgen]
+pub async fn game_details_explain_randomizer_filter() -> Result<(), JsError> {
+    let internal_id = {
+        let app = APP.try_read().expect("Should never actually have contention.");
+        match app.details_screen_game {
+            Some(id) => id,
+            None => {
+                show_error(String::from("No game on details screen to explain."))?;
+                return Ok(());
+            }
+        }
+    };
+
+    let filter = randomizer_filter(&randomizer_mode()?)?;
+
+    let sl = SShowLoadingHelper::new();
+    let explanation = match server_api::explain_filter(internal_id, filter).await {
+        Ok(e) => e,
+        Err(e) => {
+            show_error(e)?;
+            return Ok(());
+        }
+    };
+    drop(sl);
+
+    let doc = document();
+
+    let output_elem = div("game_details_explanation")?;
+    output_elem.set_inner_html("");
+
+    let summary_p = doc.create_element_typed::<HtmlParagraphElement>().to_jserr()?;
+    if explanation.passed {
+        summary_p.set_inner_text("Selectable with the current randomizer options.");
+    }
+    else {
+        summary_p.set_inner_text("Not selectable with the current randomizer options:");
+    }
+    output_elem.append_child(&summary_p).to_jserr()?;
+
+    let criteria_ul = doc.create_element_typed::<HtmlUListElement>().to_jserr()?;
+    output_elem.append_child(&criteria_ul).to_jserr()?;
+
+    for criterion in &explanation.criteria {
+        let li = doc.create_element_typed::<HtmlLiElement>().to_jserr()?;
+        let mark = if criterion.passed { "✓" } else { "✗" };
+        li.set_inner_text(format!("{} {}: {}", mark, criterion.criterion, criterion.detail).as_str());
+        if criterion.passed {
+            li.set_class_name("filter_criterion_passed");
+        }
+        else {
+            li.set_class_name("filter_criterion_failed");
+        }
+        criteria_ul.append_child(&li).to_jserr()?;
+    }
+
+    Ok(())
+}
+
 #[wasm_bindgen]
 pub async fn game_details_reset() -> Result<(), JsError> {
     let internal_id = {
@@ -1813,3 +2657,52 @@ pub async fn game_details_reset() -> Result<(), JsError> {
 
     Ok(())
 }
+
+// -- re-fetches the game on the details screen from IGDB, and saves it only if the changes are confirmed
+#[wasm_bindgen]
+pub async fn game_details_refresh_from_igdb() -> Result<(), JsError> {
+    let internal_id = {
+        let app = APP.try_read().expect("Should never actually have contention.");
+        match app.details_screen_game {
+            Some(id) => id,
+            None => {
+                show_error(String::from("No game on details screen to refresh."))?;
+                return Ok(());
+            }
+        }
+    };
+
+    let sl = SShowLoadingHelper::new();
+    let preview = server_api::preview_igdb_refresh(internal_id).await;
+    drop(sl);
+
+    let preview = match preview {
+        Ok(p) => p,
+        Err(e) => {
+            show_error(e)?;
+            return Ok(());
+        }
+    };
+
+    if preview.changes.is_empty() {
+        web::window().alert_with_message("Already up to date with IGDB.").to_jserr()?;
+        return Ok(());
+    }
+
+    let mut message = String::from("Save these changes from IGDB?\n");
+    for change in &preview.changes {
+        message.push_str(format!("\n{}: \"{}\" -> \"{}\"", change.field, change.before, change.after).as_str());
+    }
+    if !web::window().confirm_with_message(message.as_str()).to_jserr()? {
+        return Ok(());
+    }
+
+    let sl = SShowLoadingHelper::new();
+    let result = server_api::apply_igdb_refresh(&core::SIGDBRefreshRequest::from_preview(&preview)).await;
+    drop(sl);
+
+    match result {
+        Ok(game) => view_details(game).await,
+        Err(e) => show_error(e),
+    }
+}
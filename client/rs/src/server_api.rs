@@ -13,44 +13,52 @@ macro_rules! weblog {
     }
 }
 
-async fn check_err(resp: &Response) -> Result<(), String> {
+// -- decode the server's JSON error, falling back to a generic error for responses that aren't one
+// -- (e.g. rocket's own 404 page)
+async fn check_err(resp: &Response) -> Result<(), core::EError> {
     if !resp.ok() {
-        let text_promise = resp.text().to_str_err()?;
-        let text = JsFuture::from(text_promise).await.to_str_err()?;
+        let text_promise = resp.text().to_core_err()?;
+        let text = JsFuture::from(text_promise).await.to_core_err()?.as_string().unwrap_or_default();
+
+        if let Ok(e) = serde_json::from_str::<core::EError>(text.as_str()) {
+            return Err(e);
+        }
 
         let msg = format!(
             "Server responded with status {} and message \"{}\"",
             resp.status(),
-            text.as_string().unwrap_or(String::from("<NO MESSAGE>")),
+            if text.is_empty() { "<NO MESSAGE>" } else { text.as_str() },
         );
-        return Err(msg);
+        return Err(core::EError::Internal(msg));
     }
 
     Ok(())
 }
 
-pub(super) async fn search_igdb(title: &str, games_only: bool) -> Result<Vec<core::SSearchIGDBResult>, String> {
+pub(super) async fn search_igdb(title: &str, games_only: bool) -> Result<Vec<core::SSearchIGDBResult>, core::EError> {
     let window = window();
 
     let mut opts = RequestInit::new();
     opts.method("POST");
     opts.mode(RequestMode::Cors);
 
-    let origin = window.location().origin().to_str_err()?;
+    let origin = window.location().origin().to_core_err()?;
     let url = format!("{}/search_igdb/{}/{}", origin.as_str(), title, games_only);
-    let request = Request::new_with_str_and_init(&url, &opts).to_str_err()?;
+    let request = Request::new_with_str_and_init(&url, &opts).to_core_err()?;
 
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_str_err()?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_core_err()?;
     assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().to_str_err()?;
+    let resp: Response = resp_value.dyn_into().to_core_err()?;
+
+    check_err(&resp).await?;
 
-    let json_promise = resp.json().to_str_err()?;
-    let json = JsFuture::from(json_promise).await.to_str_err()?;
+    let json_promise = resp.json().to_core_err()?;
+    let json = JsFuture::from(json_promise).await.to_core_err()?;
 
-    json.into_serde().or(Err(String::from("Failed to serialize json into expected type.")))
+    json.into_serde().or(Err(core::EError::Internal(String::from("Failed to serialize json into expected type."))))
 }
 
-async fn post_data_return_data<S: serde::Serialize, T: serde::de::DeserializeOwned>(route: &str, data: S) -> Result<T, String> {
+async fn post_data_return_data<S: serde::Serialize, T: serde::de::DeserializeOwned>(route: &str, data: S) -> Result<T, core::EError> {
     let window = window();
 
     let data_json = serde_json::to_string(&data).unwrap();
@@ -61,33 +69,33 @@ async fn post_data_return_data<S: serde::Serialize, T: serde::de::DeserializeOwn
     opts.mode(RequestMode::Cors);
     opts.body(Some(&body));
 
-    let origin = window.location().origin().to_str_err()?;
+    let origin = window.location().origin().to_core_err()?;
     let url = format!("{}/{}/", origin.as_str(), route);
-    let request = Request::new_with_str_and_init(&url, &opts).to_str_err()?;
+    let request = Request::new_with_str_and_init(&url, &opts).to_core_err()?;
 
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_str_err()?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_core_err()?;
     assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().to_str_err()?;
+    let resp: Response = resp_value.dyn_into().to_core_err()?;
 
     check_err(&resp).await?;
 
-    let json_promise = resp.json().to_str_err()?;
-    let json = JsFuture::from(json_promise).await.to_str_err()?;
+    let json_promise = resp.json().to_core_err()?;
+    let json = JsFuture::from(json_promise).await.to_core_err()?;
 
     match json.into_serde() {
         Ok(d) => Ok(d),
-        Err(_) => Err(String::from("Failed to serialize json into expected type")),
+        Err(_) => Err(core::EError::Internal(String::from("Failed to serialize json into expected type"))),
     }
 }
 
-async fn post_return_data<T: serde::de::DeserializeOwned>(route: &str, url_data: Option<&str>) -> Result<T, String> {
+async fn post_return_data<T: serde::de::DeserializeOwned>(route: &str, url_data: Option<&str>) -> Result<T, core::EError> {
     let window = window();
 
     let mut opts = RequestInit::new();
     opts.method("POST");
     opts.mode(RequestMode::Cors);
 
-    let origin = window.location().origin().to_str_err()?;
+    let origin = window.location().origin().to_core_err()?;
     let url = {
         if let Some(d) = url_data {
             format!("{}/{}/{}", origin.as_str(), route, d)
@@ -96,24 +104,24 @@ async fn post_return_data<T: serde::de::DeserializeOwned>(route: &str, url_data:
             format!("{}/{}/", origin.as_str(), route)
         }
     };
-    let request = Request::new_with_str_and_init(&url, &opts).to_str_err()?;
+    let request = Request::new_with_str_and_init(&url, &opts).to_core_err()?;
 
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_str_err()?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_core_err()?;
     assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().to_str_err()?;
+    let resp: Response = resp_value.dyn_into().to_core_err()?;
 
     check_err(&resp).await?;
 
-    let json_promise = resp.json().to_str_err()?;
-    let json = JsFuture::from(json_promise).await.to_str_err()?;
+    let json_promise = resp.json().to_core_err()?;
+    let json = JsFuture::from(json_promise).await.to_core_err()?;
 
     match json.into_serde() {
         Ok(d) => Ok(d),
-        Err(_) => Err(String::from("Failed to serialize json into expected type")),
+        Err(_) => Err(core::EError::Internal(String::from("Failed to serialize json into expected type"))),
     }
 }
 
-async fn post_data<T: serde::Serialize>(route: &str, data: T) -> Result<(), String> {
+async fn post_data<T: serde::Serialize>(route: &str, data: T) -> Result<(), core::EError> {
     let window = window();
 
     let data_json = serde_json::to_string(&data).unwrap();
@@ -124,27 +132,27 @@ async fn post_data<T: serde::Serialize>(route: &str, data: T) -> Result<(), Stri
     opts.mode(RequestMode::Cors);
     opts.body(Some(&body));
 
-    let origin = window.location().origin().to_str_err()?;
+    let origin = window.location().origin().to_core_err()?;
     let url = format!("{}/{}/", origin.as_str(), route);
-    let request = Request::new_with_str_and_init(&url, &opts).to_str_err()?;
+    let request = Request::new_with_str_and_init(&url, &opts).to_core_err()?;
 
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_str_err()?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_core_err()?;
     assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().to_str_err()?;
+    let resp: Response = resp_value.dyn_into().to_core_err()?;
 
     check_err(&resp).await?;
 
     Ok(())
 }
 
-async fn post(route: &str, url_data: Option<&str>) -> Result<(), String> {
+async fn post(route: &str, url_data: Option<&str>) -> Result<(), core::EError> {
     let window = window();
 
     let mut opts = RequestInit::new();
     opts.method("POST");
     opts.mode(RequestMode::Cors);
 
-    let origin = window.location().origin().to_str_err()?;
+    let origin = window.location().origin().to_core_err()?;
     let url = {
         if let Some(d) = url_data {
             format!("{}/{}/{}", origin.as_str(), route, d)
@@ -153,86 +161,116 @@ async fn post(route: &str, url_data: Option<&str>) -> Result<(), String> {
             format!("{}/{}/", origin.as_str(), route)
         }
     };
-    let request = Request::new_with_str_and_init(&url, &opts).to_str_err()?;
+    let request = Request::new_with_str_and_init(&url, &opts).to_core_err()?;
 
-    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_str_err()?;
+    let resp_value = JsFuture::from(window.fetch_with_request(&request)).await.to_core_err()?;
     assert!(resp_value.is_instance_of::<Response>());
-    let resp: Response = resp_value.dyn_into().to_str_err()?;
+    let resp: Response = resp_value.dyn_into().to_core_err()?;
 
     check_err(&resp).await?;
 
     Ok(())
 }
 
-pub(super) async fn get_config() -> Result<core::SConfig, String> {
+pub(super) async fn get_config() -> Result<core::SConfig, core::EError> {
     post_return_data("get_config", None).await
 }
 
-pub(super) async fn add_game(game: core::SAddCollectionGame) -> Result<(), String> {
+pub(super) async fn add_game(game: core::SAddCollectionGame) -> Result<(), core::EError> {
     post_data("add_game", game).await?;
     Ok(())
 }
 
-pub(super) async fn edit_game(game: core::SCollectionGame) -> Result<(), String> {
-    post_data("edit_game", game).await?;
-    Ok(())
+pub(super) async fn get_game(internal_id: u32) -> Result<core::SCollectionGame, core::EError> {
+    let data_str = format!("{}", internal_id);
+    post_return_data("get_game", Some(data_str.as_str())).await
+}
+
+// -- returns the game as the server stored it, including any sections patched by other devices
+pub(super) async fn patch_game(request: &core::SGamePatchRequest) -> Result<core::SCollectionGame, core::EError> {
+    post_data_return_data("patch_game", request).await
+}
+
+pub(super) async fn patch_games(patches: &Vec<core::SGamePatchRequest>) -> Result<(), core::EError> {
+    post_data("patch_games", patches).await
 }
 
-pub(super) async fn get_recent_collection_games() -> Result<Vec<core::SCollectionGame>, String> {
+pub(super) async fn bulk_edit_games(request: &core::SBulkEditRequest) -> Result<Vec<u32>, core::EError> {
+    post_data_return_data("bulk_edit_games", request).await
+}
+
+pub(super) async fn get_recent_collection_games() -> Result<Vec<core::SCollectionGame>, core::EError> {
     post_return_data("get_recent_collection_games", None).await
 }
 
-pub(super) async fn update_igdb_games() -> Result<(), String> {
-    post("update_igdb_games", None).await?;
-    Ok(())
+pub(super) async fn preview_igdb_refresh(internal_id: u32) -> Result<core::SIGDBRefreshPreview, core::EError> {
+    let data_str = format!("{}", internal_id);
+    post_return_data("preview_igdb_refresh", Some(data_str.as_str())).await
 }
 
-pub(super) async fn search_collection(query: &str) -> Result<Vec<core::SCollectionGame>, String> {
-    post_return_data("search_collection", Some(query)).await
+pub(super) async fn apply_igdb_refresh(request: &core::SIGDBRefreshRequest) -> Result<core::SCollectionGame, core::EError> {
+    post_data_return_data("apply_igdb_refresh", request).await
 }
 
-pub(super) async fn get_sessions(
-    game_id: Option<u32>,
-    active_only: bool,
-    memorable_only: bool,
-    year: Option<u32>,
-) -> Result<Vec<core::SSessionAndCollectionGame>, String> {
+pub(super) async fn start_igdb_update() -> Result<core::SJob, core::EError> {
+    post_return_data("start_igdb_update", None).await
+}
 
-    let filter = core::SSessionFilter {
-        game_id,
-        active_only,
-        memorable_only,
-        year,
-    };
+pub(super) async fn get_jobs() -> Result<Vec<core::SJob>, core::EError> {
+    post_return_data("get_jobs", None).await
+}
 
-    post_data_return_data("get_sessions", filter).await
+pub(super) async fn search_collection(query: &str) -> Result<Vec<core::SCollectionGame>, core::EError> {
+    let encoded = urlencoding::encode(query);
+    post_return_data("search_collection", Some(&encoded)).await
 }
 
-pub(super) async fn start_session(internal_id: u32) -> Result<(), String> {
+pub(super) async fn get_sessions(request: &core::SGetSessionsRequest) -> Result<core::SPage<core::SSessionAndCollectionGame>, core::EError> {
+    post_data_return_data("get_sessions", request).await
+}
+
+pub(super) async fn start_session(internal_id: u32) -> Result<(), core::EError> {
     let data_str = format!("{}", internal_id);
     post("start_session", Some(data_str.as_str())).await
 }
 
-pub(super) async fn finish_session(internal_id: u32, memorable: bool, retire: bool, set_ignore_passes: bool) -> Result<(), String> {
-    let data_str = format!("{}/{}/{}/{}", internal_id, memorable, retire, set_ignore_passes);
+pub(super) async fn finish_session(internal_id: u32, revision: u32, memorable: bool, retire: bool, set_ignore_passes: bool) -> Result<(), core::EError> {
+    let data_str = format!("{}/{}/{}/{}?revision={}", internal_id, memorable, retire, set_ignore_passes, revision);
     post("finish_session", Some(data_str.as_str())).await
 }
 
-pub(super) async fn get_games(filter: core::SCollectionGameAndSessionStateFilter) -> Result<Vec<core::SCollectionGame>, String> {
-    post_data_return_data("get_games", filter).await
+pub(super) async fn get_games(request: &core::SGetGamesRequest) -> Result<core::SPage<core::SCollectionGame>, core::EError> {
+    post_data_return_data("get_games", request).await
 }
 
-pub(super) async fn update_choose_state(games: &Vec<core::SCollectionGame>) -> Result<(), String> {
-    post_data("update_choose_state", games).await?;
-    Ok(())
+pub(super) async fn get_filter_presets() -> Result<Vec<core::SFilterPreset>, core::EError> {
+    post_return_data("get_filter_presets", None).await
+}
+
+pub(super) async fn add_filter_preset(preset: &core::SFilterPreset) -> Result<(), core::EError> {
+    post_data("add_filter_preset", preset).await
+}
+
+pub(super) async fn delete_filter_preset(name: &str) -> Result<(), core::EError> {
+    let encoded = urlencoding::encode(name);
+    post("delete_filter_preset", Some(&encoded)).await
 }
 
-pub(super) async fn reset_choose_state(game: &core::SCollectionGame) -> Result<(), String> {
+pub(super) async fn explain_filter(game_internal_id: u32, filter: core::SCollectionGameAndSessionStateFilter) -> Result<core::SFilterExplanation, core::EError> {
+    let route = format!("explain_filter/{}", game_internal_id);
+    post_data_return_data(route.as_str(), filter).await
+}
+
+pub(super) async fn reset_choose_state(game: &core::SCollectionGame) -> Result<(), core::EError> {
     let data_str = format!("{}", game.internal_id);
     post("reset_choose_state", Some(data_str.as_str())).await
 }
 
-pub(super) async fn simple_stats() -> Result<core::SSimpleStats, String> {
+pub(super) async fn get_igdb_facet_options() -> Result<Vec<core::SIGDBFacetOptions>, core::EError> {
+    post_return_data("get_igdb_facet_options", None).await
+}
+
+pub(super) async fn simple_stats() -> Result<core::SSimpleStats, core::EError> {
     post_return_data("simple_stats", None).await
 }
 
@@ -240,7 +278,7 @@ pub(super) async fn check_logged_in() -> bool {
     post("check_logged_in", None).await.is_ok()
 }
 
-pub(super) async fn login(secret: &str) -> Result<(), String> {
+pub(super) async fn login(secret: &str) -> Result<(), core::EError> {
     let secret_str = format!("{}", secret);
     post_return_data("login", Some(secret_str.as_str())).await
 }
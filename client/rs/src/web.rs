@@ -1,4 +1,5 @@
 use wasm_bindgen::{JsCast, JsValue, JsError};
+use gamechooser_core as core;
 use web_sys::{
     HtmlAnchorElement,
     HtmlButtonElement,
@@ -9,6 +10,7 @@ use web_sys::{
     HtmlLabelElement,
     HtmlLiElement,
     HtmlParagraphElement,
+    HtmlSelectElement,
     HtmlSpanElement,
     HtmlUListElement,
     SvgElement,
@@ -30,6 +32,7 @@ pub trait TToJsError {
 
     fn to_jserr(self) -> Result<Self::OkType, JsError>;
     fn to_str_err(self) -> Result<Self::OkType, String>;
+    fn to_core_err(self) -> Result<Self::OkType, core::EError>;
 }
 
 // -- Implementations start here
@@ -74,6 +77,11 @@ impl TDOMElement for HtmlParagraphElement {
         "p"
     }
 }
+impl TDOMElement for HtmlSelectElement {
+    fn tag() -> &'static str {
+        "select"
+    }
+}
 impl TDOMElement for HtmlSpanElement {
     fn tag() -> &'static str {
         "span"
@@ -117,6 +125,10 @@ impl<T> TToJsError for Result<T, JsValue> {
             }
         }
     }
+
+    fn to_core_err(self) -> Result<Self::OkType, core::EError> {
+        self.to_str_err().map_err(core::EError::Internal)
+    }
 }
 
 impl<T> TToJsError for Result<T, String> {
@@ -134,6 +146,26 @@ impl<T> TToJsError for Result<T, String> {
     fn to_str_err(self) -> Result<Self::OkType, String> {
         self
     }
+
+    fn to_core_err(self) -> Result<Self::OkType, core::EError> {
+        self.map_err(core::EError::Internal)
+    }
+}
+
+impl<T> TToJsError for Result<T, core::EError> {
+    type OkType = T;
+
+    fn to_jserr(self) -> Result<Self::OkType, JsError> {
+        self.map_err(|e| JsError::new(e.to_string().as_str()))
+    }
+
+    fn to_str_err(self) -> Result<Self::OkType, String> {
+        self.map_err(|e| e.to_string())
+    }
+
+    fn to_core_err(self) -> Result<Self::OkType, core::EError> {
+        self
+    }
 }
 
 impl TErgonomicDocument for web_sys::Document {
@@ -0,0 +1,197 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{EHowLongToBeat, SCollectionGame, SOwn};
+use crate::error::{EError, SFieldError};
+use crate::filter_expression::{EGameFilterRequest, EGameTag};
+
+pub const FIELD_OWN: &str = "own";
+
+// -- which games a bulk edit applies to
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EBulkEditTarget {
+    Games(Vec<u32>),
+    Filter(EGameFilterRequest),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EChooseStateAction {
+    Pass,
+    Push,
+    Retire,
+    Reset,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct STagChange {
+    pub tag: EGameTag,
+    pub value: bool,
+}
+
+// -- store is a name as reported by SOwn::each
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SOwnChange {
+    pub store: String,
+    pub value: bool,
+}
+
+// -- changes applied to every targeted game. Unlike SGamePatch, tags and ownership are changed one flag
+// -- at a time, so marking a bundle as owned on one store leaves each game's other stores alone.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SBulkEdit {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<STagChange>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub own: Vec<SOwnChange>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub how_long_to_beat: Option<EHowLongToBeat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub choose_state: Option<EChooseStateAction>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SBulkEditRequest {
+    pub target: EBulkEditTarget,
+    pub edit: SBulkEdit,
+}
+
+impl EChooseStateAction {
+    pub fn apply(&self, game: &mut SCollectionGame) {
+        match self {
+            Self::Pass => game.choose_state.pass(),
+            Self::Push => game.choose_state.push(),
+            Self::Retire => game.choose_state.retire(),
+            Self::Reset => game.choose_state.reset(),
+        }
+    }
+}
+
+impl SBulkEdit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tag(mut self, tag: EGameTag, value: bool) -> Self {
+        self.tags.push(STagChange{ tag, value });
+        self
+    }
+
+    pub fn own(mut self, store: &str, value: bool) -> Self {
+        self.own.push(SOwnChange{ store: store.to_string(), value });
+        self
+    }
+
+    pub fn archived(mut self, archived: bool) -> Self {
+        self.archived = Some(archived);
+        self
+    }
+
+    pub fn via(mut self, via: &str) -> Self {
+        self.via = Some(via.to_string());
+        self
+    }
+
+    pub fn how_long_to_beat(mut self, how_long_to_beat: EHowLongToBeat) -> Self {
+        self.how_long_to_beat = Some(how_long_to_beat);
+        self
+    }
+
+    pub fn choose_state(mut self, action: EChooseStateAction) -> Self {
+        self.choose_state = Some(action);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // -- whether choose_state is the only thing changed
+    pub fn choose_state_only(&self) -> bool {
+        self.choose_state.is_some() && SBulkEdit{ choose_state: None, ..self.clone() }.is_empty()
+    }
+
+    // -- checks store names up front, so apply can't half apply an edit
+    pub fn validate(&self) -> Result<(), EError> {
+        let mut errors = Vec::new();
+
+        for change in &self.own {
+            let mut known = false;
+            SOwn::default().each(|_, name| known = known || name == change.store);
+            if !known {
+                let msg = format!("Unknown store \"{}\"", change.store);
+                errors.push(SFieldError::new(FIELD_OWN, msg.as_str()));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(EError::Validation(errors))
+        }
+    }
+
+    pub fn apply(&self, game: &mut SCollectionGame) {
+        for change in &self.tags {
+            change.tag.set_value(&mut game.custom_info.tags, change.value);
+        }
+        for change in &self.own {
+            game.custom_info.own.each_mut(|owned, name| {
+                if name == change.store {
+                    *owned = change.value;
+                }
+            });
+        }
+        if let Some(archived) = self.archived {
+            game.custom_info.archived = archived;
+        }
+        if let Some(via) = &self.via {
+            game.custom_info.via = via.clone();
+        }
+        if let Some(how_long_to_beat) = &self.how_long_to_beat {
+            game.how_long_to_beat = how_long_to_beat.clone();
+        }
+        if let Some(action) = &self.choose_state {
+            action.apply(game);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_games::custom_game;
+
+    #[test]
+    fn changes_single_flags() {
+        let mut game = custom_game("Celeste", 1);
+        game.custom_info.own.steam = true;
+        let edit = SBulkEdit::new()
+            .tag(EGameTag::PortablePlayable, true)
+            .own("humble", true);
+        edit.apply(&mut game);
+
+        assert!(game.custom_info.tags.portable_playable);
+        assert!(!game.custom_info.tags.couch_playable);
+        assert!(game.custom_info.own.humble);
+        assert!(game.custom_info.own.steam);
+    }
+
+    #[test]
+    fn unknown_store_fails_validation() {
+        let edit = SBulkEdit::new().own("humble", true).own("not a store", true);
+        match edit.validate() {
+            Err(EError::Validation(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].field, FIELD_OWN);
+            },
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert!(SBulkEdit::new().choose_state(EChooseStateAction::Retire).choose_state_only());
+    }
+}
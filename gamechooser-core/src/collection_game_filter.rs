@@ -1,6 +1,8 @@
 use serde::{Serialize, Deserialize};
 
-use crate::{EHowLongToBeat, SCollectionGame, SConfig, SGameTags};
+use crate::{SCollectionGame, SConfig};
+use crate::filter_expression::{EGameFilterExpr, SGameSessionState};
+use crate::igdb_facets::{EIGDBFacet, SIGDBFacetFilter, SIGDBFacetValue};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SGameTagsFilter {
@@ -25,6 +27,9 @@ pub struct SCollectionGameFilter {
     pub max_hltb_hours: Option<u16>,
 
     pub require_not_archived: bool,
+
+    #[serde(default)]
+    pub igdb_facets: SIGDBFacetFilter,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -40,6 +45,20 @@ pub struct SCollectionGameAndSessionStateFilter {
     pub session_state_filter: Option<SCollectionGameSessionStateFilter>,
 }
 
+// -- result of testing a single clause of a filter against a game
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SFilterCriterionResult {
+    pub criterion: String,
+    pub passed: bool,
+    pub detail: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SFilterExplanation {
+    pub passed: bool,
+    pub criteria: Vec<SFilterCriterionResult>,
+}
+
 // opinionated defaults based on my usual interests
 impl Default for SGameTagsFilter {
     fn default() -> Self {
@@ -64,6 +83,7 @@ impl Default for SCollectionGameFilter {
             require_no_hltb_data: false,
             max_hltb_hours: None,
             require_not_archived: true,
+            igdb_facets: SIGDBFacetFilter::default(),
         }
     }
 }
@@ -78,28 +98,6 @@ impl SGameTagsFilter {
             pick_up_and_play: None,
         }
     }
-
-    pub fn tags_pass(&self, game_tags: &SGameTags) -> bool {
-        let mut result = true;
-
-        if let Some(couch) = self.couch_playable {
-            result = result && couch == game_tags.couch_playable;
-        }
-        if let Some(portable) = self.portable_playable {
-            result = result && portable == game_tags.portable_playable;
-        }
-        if let Some(jp) = self.japanese_practice {
-            result = result && jp == game_tags.japanese_practice;
-        }
-        if let Some(retro) = self.retro {
-            result = result && retro == game_tags.retro;
-        }
-        if let Some(puap) = self.pick_up_and_play {
-            result = result && puap == game_tags.pick_up_and_play;
-        }
-
-        return result;
-    }
 }
 
 impl SCollectionGameFilter {
@@ -113,6 +111,7 @@ impl SCollectionGameFilter {
             require_no_hltb_data: false,
             max_hltb_hours: None,
             require_not_archived: true,
+            igdb_facets: SIGDBFacetFilter::default(),
         }
     }
 
@@ -176,59 +175,19 @@ impl SCollectionGameFilter {
         self
     }
 
-    pub fn game_passes(&self, cfg: &SConfig, game: &SCollectionGame) -> bool {
-        let mut result = true;
-
-        // test tags
-        result = result && self.tags.tags_pass(&game.custom_info.tags);
-
-        if let Some(req_released) = self.require_released {
-            result = result && req_released == game.game_info.released();
-        }
-
-        // test alive state
-        if let Some(req_alive) = self.required_alive_state {
-            let mut is_alive = true;
-            is_alive = is_alive && (game.choose_state.ignore_passes || game.choose_state.passes <= cfg.live_max_passes);
-            is_alive = is_alive && !game.choose_state.retired;
-
-            result = result && req_alive == is_alive;
-        }
-
-        // test after valid date
-        if self.require_is_after_valid_date {
-            let today = chrono::offset::Local::now().naive_local().date();
-            result = result && game.choose_state.next_valid_proposal_date <= today;
-        }
-
-        // test ownership state
-        if let Some(owned) = self.required_ownership_state {
-            result = result && owned == game.custom_info.own.owned();
-        }
-
-        // test games that have no hltb data
-        if self.require_no_hltb_data {
-            result = result && match game.how_long_to_beat {
-                EHowLongToBeat::Unknown => true,
-                EHowLongToBeat::Manual(_) => false,
-                EHowLongToBeat::CannotBeBeaten => false,
-            };
-        }
-
-        // test max time to beat
-        if let Some(max_hours) = self.max_hltb_hours {
-            result = result && match game.how_long_to_beat {
-                EHowLongToBeat::Unknown => false,
-                EHowLongToBeat::Manual(game_hours) => game_hours <= max_hours,
-                EHowLongToBeat::CannotBeBeaten => false,
-            };
-        }
+    pub fn require_igdb_facet(mut self, facet: EIGDBFacet, value: &str) -> Self {
+        self.igdb_facets.include.push(SIGDBFacetValue::new(facet, value));
+        self
+    }
 
-        if self.require_not_archived {
-            result = result && !game.custom_info.archived;
-        }
+    pub fn exclude_igdb_facet(mut self, facet: EIGDBFacet, value: &str) -> Self {
+        self.igdb_facets.exclude.push(SIGDBFacetValue::new(facet, value));
+        self
+    }
 
-        result
+    // -- tested through the equivalent expression, so a filter and its explanation can't disagree
+    pub fn game_passes(&self, cfg: &SConfig, game: &SCollectionGame) -> bool {
+        EGameFilterExpr::from(self.clone()).game_passes(cfg, game, &SGameSessionState::default())
     }
 }
 
@@ -256,24 +215,14 @@ impl SCollectionGameSessionStateFilter {
         self
     }
 
-    pub fn game_passes(&self, game: &SCollectionGame, mut session_count: u16, has_active_session: bool) -> bool {
-        let mut result = true;
-
-        if game.custom_info.phantom_session {
-            session_count = session_count + 1;
-        }
-
-        if let Some(min) = self.min_sessions {
-            result = result && session_count >= min;
-        }
-        if let Some(max) = self.max_sessions {
-            result = result && session_count <= max;
-        }
-        if let Some(required_active_session) = self.required_active_session {
-            result = result && has_active_session == required_active_session;
-        }
-
-        return result;
+    // -- session predicates don't look at the config
+    pub fn game_passes(&self, game: &SCollectionGame, session_count: u16, has_active_session: bool) -> bool {
+        let session_state = SGameSessionState {
+            session_count,
+            has_active_session,
+            last_session_date: None,
+        };
+        EGameFilterExpr::from(self.clone()).game_passes(&SConfig::default(), game, &session_state)
     }
 }
 
@@ -293,8 +242,94 @@ impl SCollectionGameAndSessionStateFilter {
     }
 }
 
+impl SFilterCriterionResult {
+    pub fn new(criterion: String, passed: bool, detail: String) -> Self {
+        Self {
+            criterion,
+            passed,
+            detail,
+        }
+    }
+}
+
 impl From<SCollectionGameFilter> for SCollectionGameAndSessionStateFilter {
     fn from(f: SCollectionGameFilter) -> Self {
         SCollectionGameAndSessionStateFilter::new(f, None)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EGameInfo, EReleaseDate};
+    use crate::test_games::{collection_game, custom_game};
+
+    #[test]
+    fn explain_agrees_with_game_passes() {
+        let cfg = SConfig::default();
+        let mut game = custom_game("Test Game", 1);
+        game.choose_state.passes = 3;
+        game.custom_info.tags.retro = true;
+
+        let filter = SCollectionGameFilter::default();
+        let explanation = EGameFilterExpr::from(filter.clone()).explain(&cfg, &game, &SGameSessionState::default());
+
+        assert_eq!(explanation.passed, filter.game_passes(&cfg, &game));
+        assert!(!explanation.passed);
+
+        let failed : Vec<&str> = explanation.criteria.iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.detail.as_str())
+            .collect();
+        assert!(failed.contains(&"tag retro=true but filter requires false"));
+        assert!(failed.contains(&"passes 3 > live_max_passes 2 (alive=false, required true)"));
+    }
+
+    #[test]
+    fn igdb_facets_include_and_exclude() {
+        use crate::SIGDBMetadata;
+
+        let cfg = SConfig::default();
+        let metadata = SIGDBMetadata {
+            genres: vec![String::from("Adventure")],
+            themes: vec![String::from("Horror")],
+            game_modes: vec![String::from("Co-operative")],
+            ..Default::default()
+        };
+        let game_info = EGameInfo::new_igdb(1, "slug", None, "Test Game", EReleaseDate::UnknownReleased).with_igdb_metadata(metadata);
+        let game = collection_game(game_info, 1);
+        let custom = custom_game("Test Game", 2);
+
+        let coop = SCollectionGameFilter::new().require_igdb_facet(EIGDBFacet::GameMode, "co-operative");
+        assert!(coop.game_passes(&cfg, &game));
+        assert!(!coop.game_passes(&cfg, &custom));
+
+        let no_horror = SCollectionGameFilter::new().exclude_igdb_facet(EIGDBFacet::Theme, "Horror");
+        assert!(!no_horror.game_passes(&cfg, &game));
+        assert!(no_horror.game_passes(&cfg, &custom));
+
+        let expr : EGameFilterExpr = no_horror.into();
+        let state = SGameSessionState::default();
+        let explanation = expr.explain(&cfg, &game, &state);
+        let failed = explanation.criteria.iter().find(|c| !c.passed).unwrap();
+        assert_eq!(failed.criterion, "not theme Horror");
+        assert_eq!(failed.detail, "theme values [Horror] include Horror");
+
+        assert!(!expr.game_passes(&cfg, &game, &state));
+        assert!(expr.game_passes(&cfg, &custom, &state));
+    }
+
+    #[test]
+    fn explain_session_state_counts_phantom_session() {
+        let mut game = custom_game("Test Game", 1);
+        game.custom_info.phantom_session = true;
+
+        let filter = SCollectionGameSessionStateFilter::new().max_sessions(0);
+        let explanation = EGameFilterExpr::from(filter.clone()).explain(&SConfig::default(), &game, &SGameSessionState::default());
+        let criteria = explanation.criteria;
+
+        assert_eq!(criteria.len(), 1);
+        assert!(!criteria[0].passed);
+        assert_eq!(filter.game_passes(&game, 0, false), criteria[0].passed);
+    }
+}
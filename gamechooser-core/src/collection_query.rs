@@ -0,0 +1,638 @@
+use serde::{Serialize, Deserialize};
+
+use crate::SOwn;
+use crate::filter_expression::{EGameTag, EGameFilterExpr, EGameFilterPredicate};
+
+/* Query syntax
+
+Terms separated by whitespace must all match. `OR` (or `|`) between terms matches either side,
+and binds looser than the implicit AND. Parentheses group, a leading `-` negates a term or group.
+
+    tag:<couch|portable|jp|retro|puap>
+    owned:<yes|no|store>           e.g. owned:switch, owned:steam
+    released:<yes|no>   alive:<yes|no>   archived:<yes|no>   active:<yes|no>   ready:<yes|no>
+    hltb<15   hltb>=8   hltb=10   hltb:none
+    sessions=0   sessions>2
+    added>2024-01-01   added<=2024-06-30
+    title:<text>
+
+Any other word (or "quoted text") is matched against the title.
+*/
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SQueryParseError {
+    pub message: String,
+    pub query: String,
+
+    // -- byte range of the offending token in query
+    pub start: usize,
+    pub end: usize,
+}
+
+// -- result of parsing a query: top-level title words are kept separate so collection search can
+// -- fuzzy match on them, everything else is in filter
+#[derive(Clone, Debug)]
+pub struct SCollectionQuery {
+    pub title_terms: Vec<String>,
+    pub filter: EGameFilterExpr,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum ETokenKind {
+    Word(String),
+    Quoted(String),
+    Minus,
+    Or,
+    LParen,
+    RParen,
+}
+
+#[derive(Clone, Debug)]
+struct SToken {
+    kind: ETokenKind,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EComparison {
+    Colon,
+    Equal,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+}
+
+// -- '-' and '(' recurse, so a query nested deeper than any person would write is an error rather
+// -- than a stack overflow
+const MAX_NESTING: usize = 32;
+
+struct SParser<'a> {
+    query: &'a str,
+    tokens: Vec<SToken>,
+    pos: usize,
+    depth: usize,
+}
+
+// -- a parsed term before top-level title words are pulled out
+enum ETerm {
+    Title(String),
+    Expr(EGameFilterExpr),
+}
+
+impl SQueryParseError {
+    fn new(query: &str, message: String, start: usize, end: usize) -> Self {
+        Self {
+            message,
+            query: query.to_string(),
+            start,
+            end,
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.query[self.start..self.end]
+    }
+}
+
+impl std::fmt::Display for SQueryParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let indent = self.query[..self.start].chars().count();
+        let width = std::cmp::max(1, self.query[self.start..self.end].chars().count());
+
+        writeln!(f, "{}", self.message)?;
+        writeln!(f, "{}", self.query)?;
+        write!(f, "{}{}", " ".repeat(indent), "^".repeat(width))
+    }
+}
+
+impl std::error::Error for SQueryParseError {}
+
+fn tokenize(query: &str) -> Result<Vec<SToken>, SQueryParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = query.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+
+        let kind_and_end = match c {
+            '(' => (ETokenKind::LParen, start + 1),
+            ')' => (ETokenKind::RParen, start + 1),
+            '|' => (ETokenKind::Or, start + 1),
+            '-' if chars.peek().is_some_and(|(_, n)| !n.is_whitespace()) => (ETokenKind::Minus, start + 1),
+            '"' => {
+                let mut text = String::new();
+                let mut end = None;
+                for (i, qc) in chars.by_ref() {
+                    if qc == '"' {
+                        end = Some(i + 1);
+                        break;
+                    }
+                    text.push(qc);
+                }
+
+                match end {
+                    Some(e) => (ETokenKind::Quoted(text), e),
+                    None => return Err(SQueryParseError::new(query, String::from("Unterminated quote"), start, query.len())),
+                }
+            },
+            _ => {
+                let mut word = String::from(c);
+                let mut end = start + c.len_utf8();
+                while let Some((i, wc)) = chars.peek().copied() {
+                    if wc.is_whitespace() || wc == '(' || wc == ')' || wc == '"' {
+                        break;
+                    }
+                    word.push(wc);
+                    end = i + wc.len_utf8();
+                    chars.next();
+                }
+
+                if word == "OR" {
+                    (ETokenKind::Or, end)
+                }
+                else {
+                    (ETokenKind::Word(word), end)
+                }
+            },
+        };
+
+        tokens.push(SToken{
+            kind: kind_and_end.0,
+            start,
+            end: kind_and_end.1,
+        });
+    }
+
+    Ok(tokens)
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "yes" | "y" | "true" => Some(true),
+        "no" | "n" | "false" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_tag(value: &str) -> Option<EGameTag> {
+    match value {
+        "couch" | "couch_playable" => Some(EGameTag::CouchPlayable),
+        "portable" | "portable_playable" => Some(EGameTag::PortablePlayable),
+        "jp" | "japanese_practice" => Some(EGameTag::JapanesePractice),
+        "retro" => Some(EGameTag::Retro),
+        "puap" | "pick_up_and_play" => Some(EGameTag::PickUpAndPlay),
+        _ => None,
+    }
+}
+
+// -- compare ignoring case and punctuation, so "battlenet" finds "battle.net"
+fn normalize_store_name(name: &str) -> String {
+    name.chars().filter(|c| c.is_alphanumeric()).flat_map(|c| c.to_lowercase()).collect()
+}
+
+fn store_names() -> Vec<String> {
+    let mut names = Vec::new();
+    SOwn::default().each(|_, name| names.push(name.to_string()));
+    names
+}
+
+fn and_all(leaves: Vec<EGameFilterPredicate>) -> EGameFilterExpr {
+    if leaves.len() == 1 {
+        return EGameFilterExpr::Leaf(leaves.into_iter().next().expect("checked length"));
+    }
+
+    EGameFilterExpr::And(leaves.into_iter().map(EGameFilterExpr::Leaf).collect())
+}
+
+impl<'a> SParser<'a> {
+    fn error<T>(&self, message: String, token: &SToken) -> Result<T, SQueryParseError> {
+        Err(SQueryParseError::new(self.query, message, token.start, token.end))
+    }
+
+    fn peek(&self) -> Option<&SToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<SToken> {
+        let result = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        result
+    }
+
+    fn enter_nesting(&mut self, token: &SToken) -> Result<(), SQueryParseError> {
+        if self.depth >= MAX_NESTING {
+            return self.error(format!("Nested more than {} levels deep", MAX_NESTING), token);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn parse_or(&mut self) -> Result<Vec<Vec<ETerm>>, SQueryParseError> {
+        let mut alternatives = vec![self.parse_and()?];
+
+        while let Some(token) = self.peek() {
+            if token.kind != ETokenKind::Or {
+                break;
+            }
+
+            let or_token = self.next().expect("peeked");
+            if alternatives[0].is_empty() {
+                return self.error(String::from("Expected a term before OR"), &or_token);
+            }
+
+            let alternative = self.parse_and()?;
+            if alternative.is_empty() {
+                return self.error(String::from("Expected a term after OR"), &or_token);
+            }
+            alternatives.push(alternative);
+        }
+
+        Ok(alternatives)
+    }
+
+    fn parse_and(&mut self) -> Result<Vec<ETerm>, SQueryParseError> {
+        let mut terms = Vec::new();
+
+        while let Some(token) = self.peek() {
+            if token.kind == ETokenKind::Or || token.kind == ETokenKind::RParen {
+                break;
+            }
+
+            terms.push(self.parse_unary()?);
+        }
+
+        Ok(terms)
+    }
+
+    fn parse_unary(&mut self) -> Result<ETerm, SQueryParseError> {
+        let token = self.next().expect("caller checked for a token");
+
+        match token.kind.clone() {
+            ETokenKind::Minus => {
+                if self.peek().is_none() {
+                    return self.error(String::from("Expected a term after '-'"), &token);
+                }
+                self.enter_nesting(&token)?;
+                let inner = self.parse_unary()?;
+                self.depth -= 1;
+                Ok(ETerm::Expr(EGameFilterExpr::negate(Self::term_to_expr(inner))))
+            },
+            ETokenKind::LParen => {
+                self.enter_nesting(&token)?;
+                let alternatives = self.parse_or()?;
+                self.depth -= 1;
+                match self.next() {
+                    Some(SToken{kind: ETokenKind::RParen, ..}) => (),
+                    _ => return self.error(String::from("Unclosed '('"), &token),
+                }
+
+                if alternatives.len() == 1 && alternatives[0].is_empty() {
+                    return self.error(String::from("Empty group"), &token);
+                }
+
+                Ok(ETerm::Expr(Self::alternatives_to_expr(alternatives)))
+            },
+            ETokenKind::RParen => self.error(String::from("Unmatched ')'"), &token),
+            ETokenKind::Or => self.error(String::from("Unexpected OR"), &token),
+            ETokenKind::Quoted(text) => Ok(ETerm::Title(text)),
+            ETokenKind::Word(word) => self.parse_word(word.as_str(), &token),
+        }
+    }
+
+    fn parse_word(&self, word: &str, token: &SToken) -> Result<ETerm, SQueryParseError> {
+        let op_idx = match word.find([':', '<', '>', '=']) {
+            Some(i) if i > 0 => i,
+            _ => return Ok(ETerm::Title(word.to_string())),
+        };
+
+        let key = word[..op_idx].to_lowercase();
+        let rest = &word[op_idx..];
+        let (comparison, value) = if let Some(v) = rest.strip_prefix("<=") {
+            (EComparison::LessEqual, v)
+        } else if let Some(v) = rest.strip_prefix(">=") {
+            (EComparison::GreaterEqual, v)
+        } else if let Some(v) = rest.strip_prefix('<') {
+            (EComparison::Less, v)
+        } else if let Some(v) = rest.strip_prefix('>') {
+            (EComparison::Greater, v)
+        } else if let Some(v) = rest.strip_prefix('=') {
+            (EComparison::Equal, v)
+        } else {
+            (EComparison::Colon, &rest[1..])
+        };
+
+        if value.is_empty() {
+            // -- titles like "Metroid: Fusion" shouldn't trip up the parser
+            if comparison == EComparison::Colon {
+                return Ok(ETerm::Title(word.to_string()));
+            }
+            return self.error(format!("Missing value after '{}'", key), token);
+        }
+
+        let value_lower = value.to_lowercase();
+
+        let require_colon = |name: &str| -> Result<(), SQueryParseError> {
+            if comparison != EComparison::Colon && comparison != EComparison::Equal {
+                return self.error(format!("'{}' only supports ':'", name), token);
+            }
+            Ok(())
+        };
+
+        let parse_yes_no = |name: &str| -> Result<bool, SQueryParseError> {
+            require_colon(name)?;
+            match parse_bool(value_lower.as_str()) {
+                Some(b) => Ok(b),
+                None => self.error(format!("Expected yes or no for '{}', got '{}'", name, value), token),
+            }
+        };
+
+        use EGameFilterPredicate::*;
+
+        let expr = match key.as_str() {
+            "tag" => {
+                require_colon("tag")?;
+                match parse_tag(value_lower.as_str()) {
+                    Some(tag) => EGameFilterExpr::leaf(Tag{tag, value: true}),
+                    None => return self.error(format!("Unknown tag '{}', expected one of couch, portable, jp, retro, puap", value), token),
+                }
+            },
+            "owned" | "own" => {
+                require_colon("owned")?;
+                if let Some(b) = parse_bool(value_lower.as_str()) {
+                    EGameFilterExpr::leaf(Owned(b))
+                }
+                else {
+                    let normalized = normalize_store_name(value);
+                    let names = store_names();
+                    match names.iter().find(|n| normalize_store_name(n) == normalized) {
+                        Some(name) => EGameFilterExpr::leaf(OwnedOn(name.clone())),
+                        None => return self.error(format!("Unknown store '{}', expected yes, no, or one of: {}", value, names.join(", ")), token),
+                    }
+                }
+            },
+            "released" => EGameFilterExpr::leaf(Released(parse_yes_no("released")?)),
+            "alive" => EGameFilterExpr::leaf(Alive(parse_yes_no("alive")?)),
+            "archived" => EGameFilterExpr::leaf(Archived(parse_yes_no("archived")?)),
+            "active" => EGameFilterExpr::leaf(ActiveSession(parse_yes_no("active")?)),
+            "ready" => {
+                if parse_yes_no("ready")? {
+                    EGameFilterExpr::leaf(AfterValidDate)
+                }
+                else {
+                    EGameFilterExpr::negate(EGameFilterExpr::leaf(AfterValidDate))
+                }
+            },
+            "title" => {
+                require_colon("title")?;
+                EGameFilterExpr::leaf(TitleContains(value.to_string()))
+            },
+            "hltb" => {
+                if comparison == EComparison::Colon && (value_lower == "none" || value_lower == "unknown") {
+                    EGameFilterExpr::leaf(NoHLTBData)
+                }
+                else {
+                    let hours = self.parse_number(value, "hltb", token)?;
+                    and_all(self.range_predicates(comparison, hours, "hltb", token, MinHLTBHours, MaxHLTBHours)?)
+                }
+            },
+            "sessions" => {
+                let count = self.parse_number(value, "sessions", token)?;
+                and_all(self.range_predicates(comparison, count, "sessions", token, MinSessions, MaxSessions)?)
+            },
+            "added" => {
+                let date = match chrono::naive::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+                    Ok(d) => d,
+                    Err(_) => return self.error(format!("Expected a YYYY-MM-DD date for 'added', got '{}'", value), token),
+                };
+
+                let out_of_range = || format!("'added' date {} is out of range", date);
+                let leaves = match comparison {
+                    EComparison::Colon | EComparison::Equal => vec![MinAddedDate(date), MaxAddedDate(date)],
+                    EComparison::Less => match date.pred_opt() {
+                        Some(d) => vec![MaxAddedDate(d)],
+                        None => return self.error(out_of_range(), token),
+                    },
+                    EComparison::LessEqual => vec![MaxAddedDate(date)],
+                    EComparison::Greater => match date.succ_opt() {
+                        Some(d) => vec![MinAddedDate(d)],
+                        None => return self.error(out_of_range(), token),
+                    },
+                    EComparison::GreaterEqual => vec![MinAddedDate(date)],
+                };
+                and_all(leaves)
+            },
+            _ => {
+                return self.error(format!("Unknown filter '{}' (put it in quotes to search titles)", key), token);
+            },
+        };
+
+        Ok(ETerm::Expr(expr))
+    }
+
+    fn parse_number(&self, value: &str, name: &str, token: &SToken) -> Result<u16, SQueryParseError> {
+        match value.parse::<u16>() {
+            Ok(n) => Ok(n),
+            Err(_) => self.error(format!("Expected a number for '{}', got '{}'", name, value), token),
+        }
+    }
+
+    fn range_predicates(
+        &self,
+        comparison: EComparison,
+        value: u16,
+        name: &str,
+        token: &SToken,
+        min: fn(u16) -> EGameFilterPredicate,
+        max: fn(u16) -> EGameFilterPredicate,
+    ) -> Result<Vec<EGameFilterPredicate>, SQueryParseError> {
+        let result = match comparison {
+            EComparison::Colon | EComparison::Equal => vec![min(value), max(value)],
+            EComparison::Less => {
+                if value == 0 {
+                    return self.error(format!("'{}<0' can never match", name), token);
+                }
+                vec![max(value - 1)]
+            },
+            EComparison::LessEqual => vec![max(value)],
+            EComparison::Greater => vec![min(value.saturating_add(1))],
+            EComparison::GreaterEqual => vec![min(value)],
+        };
+
+        Ok(result)
+    }
+
+    fn term_to_expr(term: ETerm) -> EGameFilterExpr {
+        match term {
+            ETerm::Title(text) => EGameFilterExpr::leaf(EGameFilterPredicate::TitleContains(text)),
+            ETerm::Expr(e) => e,
+        }
+    }
+
+    fn terms_to_expr(terms: Vec<ETerm>) -> EGameFilterExpr {
+        let mut exprs : Vec<EGameFilterExpr> = terms.into_iter().map(Self::term_to_expr).collect();
+        if exprs.len() == 1 {
+            return exprs.pop().expect("checked length");
+        }
+
+        EGameFilterExpr::And(exprs)
+    }
+
+    fn alternatives_to_expr(mut alternatives: Vec<Vec<ETerm>>) -> EGameFilterExpr {
+        if alternatives.len() == 1 {
+            return Self::terms_to_expr(alternatives.pop().expect("checked length"));
+        }
+
+        EGameFilterExpr::Or(alternatives.into_iter().map(Self::terms_to_expr).collect())
+    }
+}
+
+pub fn parse_collection_query(query: &str) -> Result<SCollectionQuery, SQueryParseError> {
+    let mut parser = SParser {
+        query,
+        tokens: tokenize(query)?,
+        pos: 0,
+        depth: 0,
+    };
+
+    let mut alternatives = parser.parse_or()?;
+
+    if let Some(token) = parser.peek() {
+        let token = token.clone();
+        return parser.error(String::from("Unmatched ')'"), &token);
+    }
+
+    // -- title words only get pulled out for fuzzy matching if they aren't part of an OR
+    let mut title_terms = Vec::new();
+    let filter = if alternatives.len() == 1 {
+        let mut exprs = Vec::new();
+        for term in alternatives.pop().expect("checked length") {
+            match term {
+                ETerm::Title(t) => title_terms.push(t),
+                ETerm::Expr(e) => exprs.push(e),
+            }
+        }
+        EGameFilterExpr::And(exprs)
+    }
+    else {
+        SParser::alternatives_to_expr(alternatives)
+    };
+
+    Ok(SCollectionQuery {
+        title_terms,
+        filter,
+    })
+}
+
+impl SCollectionQuery {
+    pub fn title_text(&self) -> String {
+        self.title_terms.join(" ")
+    }
+
+    // -- searches hide archived games unless the query says otherwise
+    pub fn mentions_archived(&self) -> bool {
+        self.filter.has_predicate(&|p| matches!(p, EGameFilterPredicate::Archived(_)))
+    }
+
+    // -- the whole query as a filter, with title words required as substrings
+    pub fn into_filter_expr(self) -> EGameFilterExpr {
+        let mut result = self.filter;
+        for term in self.title_terms {
+            result = result.and(EGameFilterExpr::leaf(EGameFilterPredicate::TitleContains(term)));
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EHowLongToBeat, SCollectionGame, SConfig, SGameSessionState};
+    use crate::test_games::custom_game;
+
+    fn passes(query: &str, game: &SCollectionGame, session_count: u16) -> bool {
+        let expr = parse_collection_query(query).unwrap().into_filter_expr();
+        let state = SGameSessionState{ session_count, ..Default::default() };
+        expr.game_passes(&SConfig::default(), game, &state)
+    }
+
+    #[test]
+    fn example_query() {
+        let mut game = custom_game("Celeste", 1);
+        game.custom_info.own.switch = true;
+        game.custom_info.tags.couch_playable = true;
+        game.how_long_to_beat = EHowLongToBeat::Manual(10);
+        game.added_date = Some(chrono::naive::NaiveDate::from_ymd(2024, 3, 1));
+
+        let query = "owned:switch tag:couch -tag:retro hltb<15 sessions=0 released:yes added>2024-01-01";
+        assert!(passes(query, &game, 0));
+        assert!(!passes(query, &game, 1));
+
+        game.custom_info.tags.retro = true;
+        assert!(!passes(query, &game, 0));
+    }
+
+    #[test]
+    fn or_and_groups() {
+        let mut game = custom_game("Celeste", 1);
+        game.custom_info.own.steam = true;
+
+        assert!(passes("owned:switch OR owned:steam", &game, 0));
+        assert!(passes("(hltb<10 | tag:puap) OR owned:battlenet OR celeste", &game, 0));
+        assert!(!passes("-(owned:switch OR owned:steam)", &game, 0));
+    }
+
+    #[test]
+    fn title_terms_are_split_out() {
+        let query = parse_collection_query("metroid: \"zero mission\" owned:yes").unwrap();
+        assert_eq!(query.title_terms, vec![String::from("metroid:"), String::from("zero mission")]);
+    }
+
+    #[test]
+    fn archived_terms_are_found() {
+        assert!(!parse_collection_query("celeste tag:couch").unwrap().mentions_archived());
+        assert!(parse_collection_query("celeste archived:yes").unwrap().mentions_archived());
+        assert!(parse_collection_query("tag:couch OR -(archived:no)").unwrap().mentions_archived());
+    }
+
+    #[test]
+    fn errors_point_at_token() {
+        let err = parse_collection_query("owned:switch tag:fancy hltb<15").unwrap_err();
+        assert_eq!(err.token(), "tag:fancy");
+        assert_eq!(err.to_string().lines().last().unwrap(), "             ^^^^^^^^^");
+
+        let err = parse_collection_query("hltb<lots").unwrap_err();
+        assert_eq!(err.token(), "hltb<lots");
+
+        let err = parse_collection_query("(tag:couch OR").unwrap_err();
+        assert_eq!(err.token(), "OR");
+
+        // -- the OR inside the group is the one missing a term, not the first one in the query
+        let err = parse_collection_query("tag:couch OR tag:retro (OR tag:jp)").unwrap_err();
+        assert_eq!((err.token(), err.start), ("OR", 24));
+
+        let max_date = chrono::naive::MAX_DATE.format("%Y-%m-%d").to_string();
+        let query = format!("added>{}", max_date);
+        assert_eq!(parse_collection_query(query.as_str()).unwrap_err().token(), query);
+
+        assert!(parse_collection_query("tag:couch)").is_err());
+        assert!(parse_collection_query("frobnicate:yes").is_err());
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let query = format!("{}celeste{}", "(".repeat(MAX_NESTING), ")".repeat(MAX_NESTING));
+        assert!(parse_collection_query(query.as_str()).is_ok());
+
+        let query = format!("{}celeste", "-".repeat(100_000));
+        let err = parse_collection_query(query.as_str()).unwrap_err();
+        assert_eq!((err.token(), err.start), ("-", MAX_NESTING));
+
+        let query = format!("tag:couch {}celeste", "(".repeat(100_000));
+        let err = parse_collection_query(query.as_str()).unwrap_err();
+        assert_eq!((err.token(), err.start), ("(", 10 + MAX_NESTING));
+    }
+}
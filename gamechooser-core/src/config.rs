@@ -1,14 +1,107 @@
 use serde::{Serialize, Deserialize};
 
+// -- which games the IGDB update job refreshes. A game is refreshed if any of the refresh rules
+// -- match and none of the skip rules do, except missing covers and metadata which ignore the skips.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SIGDBRefreshPolicy {
+    pub skip_games_with_sessions: bool,
+    pub skip_archived: bool,
+
+    pub refresh_unknown_unreleased: bool,
+    pub refresh_unknown_released: bool,
+
+    // -- refresh games releasing in the future or released in the last N days
+    pub recent_release_days: Option<u32>,
+
+    // -- refresh any game not refreshed in the last N days, including games never refreshed
+    pub stale_after_days: Option<u32>,
+
+    pub refresh_missing_covers: bool,
+
+    // -- backfills summary, genres, platforms etc. for games stored before they were fetched
+    pub refresh_missing_metadata: bool,
+}
+
+// -- IGDB region that covers every other region
+pub const WORLDWIDE_REGION: &str = "Worldwide";
+
+// -- which per-platform releases count when deciding if a game is out. Games without per-platform
+// -- release dates fall back to their overall release date.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SReleasePreferences {
+    // -- IGDB platform names, e.g. "Nintendo Switch" or "PC (Microsoft Windows)"
+    pub platforms: Vec<String>,
+
+    // -- IGDB region names, e.g. "North America" or "Japan". Empty means any region.
+    pub regions: Vec<String>,
+
+    // -- platforms a game is owned on count for that game, and owning a copy counts as released.
+    // -- With no platforms listed and nothing owned, any platform counts.
+    pub include_owned_platforms: bool,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SConfig {
     pub live_max_passes: u16,
+
+    #[serde(default)]
+    pub igdb_refresh: SIGDBRefreshPolicy,
+
+    #[serde(default)]
+    pub release_preferences: SReleasePreferences,
+}
+
+// -- the defaults match the rules update_igdb_games always used
+impl Default for SIGDBRefreshPolicy {
+    fn default() -> Self {
+        Self {
+            skip_games_with_sessions: true,
+            skip_archived: false,
+            refresh_unknown_unreleased: true,
+            refresh_unknown_released: false,
+            recent_release_days: Some(180),
+            stale_after_days: None,
+            refresh_missing_covers: false,
+            refresh_missing_metadata: true,
+        }
+    }
+}
+
+impl Default for SReleasePreferences {
+    fn default() -> Self {
+        Self {
+            platforms: Vec::new(),
+            regions: Vec::new(),
+            include_owned_platforms: true,
+        }
+    }
 }
 
 impl Default for SConfig {
     fn default() -> Self {
         Self{
             live_max_passes: 2,
+            igdb_refresh: SIGDBRefreshPolicy::default(),
+            release_preferences: SReleasePreferences::default(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl SReleasePreferences {
+    pub fn wants_platform(&self, platform: &str, owned_platforms: &[&str]) -> bool {
+        if self.platforms.is_empty() && owned_platforms.is_empty() {
+            return true;
+        }
+
+        self.platforms.iter().any(|p| p.eq_ignore_ascii_case(platform))
+            || owned_platforms.iter().any(|p| p.eq_ignore_ascii_case(platform))
+    }
+
+    pub fn wants_region(&self, region: &str) -> bool {
+        self.regions.is_empty()
+            || region == WORLDWIDE_REGION
+            || self.regions.iter().any(|r| r.eq_ignore_ascii_case(region))
+    }
+}
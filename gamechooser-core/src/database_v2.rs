@@ -1,7 +1,7 @@
 use chrono;
 use serde::{Serialize, Deserialize};
 
-use crate::database_v3;
+use crate::database_v4;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SGameInfoCustom {
@@ -24,8 +24,8 @@ pub enum EGameInfo {
     IGDB(SGameInfoIGDB),
 }
 
-pub type SGameCustomInfo = database_v3::SGameCustomInfo;
-pub type SGameChooseState = database_v3::SGameChooseState;
+pub type SGameCustomInfo = database_v4::SGameCustomInfo;
+pub type SGameChooseState = database_v4::SGameChooseState;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SCollectionGame {
@@ -35,7 +35,7 @@ pub struct SCollectionGame {
     pub choose_state: SGameChooseState,
 }
 
-pub type SSession = database_v3::SSession;
+pub type SSession = database_v4::SSession;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SDatabase {
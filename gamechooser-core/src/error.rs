@@ -0,0 +1,177 @@
+use serde::{Serialize, Deserialize};
+
+// -- machine readable error kind, so clients can react to specific errors without parsing messages
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EErrorCode {
+    NotFound,
+    Conflict,
+    Validation,
+    UpstreamIGDB,
+    Storage,
+    BadRequest,
+    NotAuthenticated,
+    Internal,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SFieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl SFieldError {
+    pub fn new(field: &str, message: &str) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+// -- the error type shared by core, the server and the clients. Goes over the wire as SErrorBody.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "SErrorBody", from = "SErrorBody")]
+pub enum EError {
+    NotFound(String),
+    Conflict(String),
+    Validation(Vec<SFieldError>),
+    UpstreamIGDB(String),
+    Storage(String),
+    BadRequest(String),
+    NotAuthenticated,
+    Internal(String), // -- bugs, bad data and client side failures that don't fit anything else
+}
+
+// -- JSON shape of EError
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SErrorBody {
+    pub code: EErrorCode,
+    pub message: String,
+    #[serde(default)]
+    pub details: Vec<SFieldError>,
+}
+
+impl EError {
+    pub fn game_not_found(internal_id: u32) -> Self {
+        Self::NotFound(format!("Could not find a game with internal_id {}", internal_id))
+    }
+
+    pub fn session_not_found(internal_id: u32) -> Self {
+        Self::NotFound(format!("Could not find a session with internal_id {}", internal_id))
+    }
+
+    pub fn filter_preset_not_found(name: &str) -> Self {
+        Self::NotFound(format!("No filter preset named {}", name))
+    }
+
+    pub fn job_not_found(id: u32) -> Self {
+        Self::NotFound(format!("Could not find a job with id {}", id))
+    }
+
+    pub fn invalid_field(field: &str, message: &str) -> Self {
+        Self::Validation(vec![SFieldError::new(field, message)])
+    }
+
+    pub fn code(&self) -> EErrorCode {
+        match self {
+            Self::NotFound(_) => EErrorCode::NotFound,
+            Self::Conflict(_) => EErrorCode::Conflict,
+            Self::Validation(_) => EErrorCode::Validation,
+            Self::UpstreamIGDB(_) => EErrorCode::UpstreamIGDB,
+            Self::Storage(_) => EErrorCode::Storage,
+            Self::BadRequest(_) => EErrorCode::BadRequest,
+            Self::NotAuthenticated => EErrorCode::NotAuthenticated,
+            Self::Internal(_) => EErrorCode::Internal,
+        }
+    }
+}
+
+impl std::fmt::Display for EError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(msg) => write!(f, "{}", msg),
+            Self::Conflict(msg) => write!(f, "{}", msg),
+            Self::Validation(fields) => {
+                let mut first = true;
+                for field in fields {
+                    if !first {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}: {}", field.field, field.message)?;
+                    first = false;
+                }
+                Ok(())
+            },
+            Self::UpstreamIGDB(msg) => write!(f, "{}", msg),
+            Self::Storage(msg) => write!(f, "{}", msg),
+            Self::BadRequest(msg) => write!(f, "{}", msg),
+            Self::NotAuthenticated => write!(f, "You have not authenticated, please log in."),
+            Self::Internal(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for EError {}
+
+impl From<EError> for SErrorBody {
+    fn from(e: EError) -> Self {
+        let message = e.to_string();
+        let code = e.code();
+        let details = match e {
+            EError::Validation(fields) => fields,
+            _ => Vec::new(),
+        };
+
+        Self {
+            code,
+            message,
+            details,
+        }
+    }
+}
+
+impl From<SErrorBody> for EError {
+    fn from(body: SErrorBody) -> Self {
+        match body.code {
+            EErrorCode::NotFound => Self::NotFound(body.message),
+            EErrorCode::Conflict => Self::Conflict(body.message),
+            EErrorCode::Validation => Self::Validation(body.details),
+            EErrorCode::UpstreamIGDB => Self::UpstreamIGDB(body.message),
+            EErrorCode::Storage => Self::Storage(body.message),
+            EErrorCode::BadRequest => Self::BadRequest(body.message),
+            EErrorCode::NotAuthenticated => Self::NotAuthenticated,
+            EErrorCode::Internal => Self::Internal(body.message),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_round_trip() {
+        let errors = vec![
+            EError::game_not_found(3),
+            EError::Conflict(String::from("already exists")),
+            EError::invalid_field("name", "cannot be empty"),
+            EError::UpstreamIGDB(String::from("timed out")),
+            EError::NotAuthenticated,
+        ];
+
+        for error in errors {
+            let json = serde_json::to_string(&error).unwrap();
+            let decoded : EError = serde_json::from_str(json.as_str()).unwrap();
+            assert_eq!(decoded, error);
+        }
+    }
+
+    #[test]
+    fn json_shape() {
+        let json = serde_json::to_value(EError::invalid_field("query", "unknown key")).unwrap();
+        assert_eq!(json["code"], "validation");
+        assert_eq!(json["message"], "query: unknown key");
+        assert_eq!(json["details"][0]["field"], "query");
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{SCollectionGame, SSession};
+use crate::jobs::SJob;
+
+// -- published after a change is saved, so other open clients can refresh without polling
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EChangeEvent {
+    GameAdded { game: SCollectionGame },
+    GameEdited { game: SCollectionGame },
+    ChooseStateChanged { game: SCollectionGame },
+    SessionStarted { session: SSession },
+    SessionFinished { session: SSession },
+    FilterPresetsChanged,
+    JobUpdated { job: SJob },
+    // -- collection games that come out on date, sent by the server's scheduler and again to
+    // -- clients that connect later that day
+    ReleaseDay { date: chrono::naive::NaiveDate, games: Vec<SCollectionGame> },
+}
+
+impl EChangeEvent {
+    // -- the game as it is after the change, if this event carries one
+    pub fn game(&self) -> Option<&SCollectionGame> {
+        match self {
+            Self::GameAdded { game } => Some(game),
+            Self::GameEdited { game } => Some(game),
+            Self::ChooseStateChanged { game } => Some(game),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{EHowLongToBeat, EReleaseDate, ESessionState, SCollectionGame, SConfig, SGameTags, SSession};
+use crate::collection_game_filter::{SCollectionGameFilter, SCollectionGameSessionStateFilter, SCollectionGameAndSessionStateFilter, SFilterCriterionResult, SFilterExplanation};
+use crate::collection_query::{SQueryParseError, parse_collection_query};
+use crate::igdb_facets::SIGDBFacetValue;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EGameTag {
+    CouchPlayable,
+    PortablePlayable,
+    JapanesePractice,
+    Retro,
+    PickUpAndPlay,
+}
+
+// -- a single criterion, covering everything SCollectionGameFilter and
+// -- SCollectionGameSessionStateFilter can test
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum EGameFilterPredicate {
+    Tag {
+        tag: EGameTag,
+        value: bool,
+    },
+    Released(bool),
+    KnownReleaseDate(bool),
+    Alive(bool),
+    AfterValidDate,
+    Owned(bool),
+    OwnedOn(String), // name as reported by SOwn::each
+    NoHLTBData,
+    MinHLTBHours(u16),
+    MaxHLTBHours(u16),
+    Archived(bool),
+    TitleContains(String), // case insensitive
+    MinAddedDate(chrono::naive::NaiveDate),
+    MaxAddedDate(chrono::naive::NaiveDate),
+    MinSessions(u16),
+    MaxSessions(u16),
+    ActiveSession(bool),
+    IGDBFacet(SIGDBFacetValue), // game has the genre, theme or game mode
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum EGameFilterExpr {
+    And(Vec<EGameFilterExpr>),
+    Or(Vec<EGameFilterExpr>),
+    Not(Box<EGameFilterExpr>),
+    Leaf(EGameFilterPredicate),
+}
+
+// -- session info the server has to gather before a game can be tested
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SGameSessionState {
+    pub session_count: u16,
+    pub has_active_session: bool,
+    pub last_session_date: Option<chrono::naive::NaiveDate>,
+}
+
+// -- what get_games accepts: a full expression, the older flat filter, or a text query
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum EGameFilterRequest {
+    Expression(EGameFilterExpr),
+    Legacy(SCollectionGameAndSessionStateFilter),
+    Query(String),
+}
+
+impl EGameTag {
+    pub const ALL: [EGameTag; 5] = [
+        EGameTag::CouchPlayable,
+        EGameTag::PortablePlayable,
+        EGameTag::JapanesePractice,
+        EGameTag::Retro,
+        EGameTag::PickUpAndPlay,
+    ];
+
+    pub fn value(&self, tags: &SGameTags) -> bool {
+        match self {
+            Self::CouchPlayable => tags.couch_playable,
+            Self::PortablePlayable => tags.portable_playable,
+            Self::JapanesePractice => tags.japanese_practice,
+            Self::Retro => tags.retro,
+            Self::PickUpAndPlay => tags.pick_up_and_play,
+        }
+    }
+
+    // -- matches the names used by SGameTags::each
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::CouchPlayable => "couch",
+            Self::PortablePlayable => "portable",
+            Self::JapanesePractice => "jp practice",
+            Self::Retro => "retro",
+            Self::PickUpAndPlay => "pick up and play",
+        }
+    }
+
+    pub fn set_value(&self, tags: &mut SGameTags, value: bool) {
+        match self {
+            Self::CouchPlayable => tags.couch_playable = value,
+            Self::PortablePlayable => tags.portable_playable = value,
+            Self::JapanesePractice => tags.japanese_practice = value,
+            Self::Retro => tags.retro = value,
+            Self::PickUpAndPlay => tags.pick_up_and_play = value,
+        }
+    }
+}
+
+impl EGameFilterPredicate {
+    pub fn game_passes(&self, cfg: &SConfig, game: &SCollectionGame, session_state: &SGameSessionState) -> bool {
+        match self {
+            Self::Tag{tag, value} => tag.value(&game.custom_info.tags) == *value,
+            Self::Released(val) => game.released(cfg) == *val,
+            Self::KnownReleaseDate(val) => matches!(game.release_date(cfg), EReleaseDate::Known(_)) == *val,
+            Self::Alive(val) => game.choose_state.alive(cfg) == *val,
+            Self::AfterValidDate => {
+                let today = chrono::offset::Local::now().naive_local().date();
+                game.choose_state.next_valid_proposal_date <= today
+            },
+            Self::Owned(val) => game.custom_info.own.owned() == *val,
+            Self::OwnedOn(name) => game.custom_info.own.owned_on(name.as_str()),
+            Self::NoHLTBData => matches!(game.how_long_to_beat, EHowLongToBeat::Unknown),
+            Self::MinHLTBHours(min_hours) => match game.how_long_to_beat.hours_to_beat() {
+                Some(hours) => hours >= *min_hours,
+                None => false,
+            },
+            Self::MaxHLTBHours(max_hours) => match game.how_long_to_beat.hours_to_beat() {
+                Some(hours) => hours <= *max_hours,
+                None => false,
+            },
+            Self::Archived(val) => game.custom_info.archived == *val,
+            Self::TitleContains(text) => game.game_info.title().to_lowercase().contains(text.to_lowercase().as_str()),
+            Self::MinAddedDate(date) => match game.added_date {
+                Some(added) => added >= *date,
+                None => false,
+            },
+            Self::MaxAddedDate(date) => match game.added_date {
+                Some(added) => added <= *date,
+                None => false,
+            },
+            Self::MinSessions(min) => session_state.effective_session_count(game) >= *min,
+            Self::MaxSessions(max) => session_state.effective_session_count(game) <= *max,
+            Self::ActiveSession(val) => session_state.has_active_session == *val,
+            Self::IGDBFacet(facet_value) => facet_value.game_has_value(game),
+        }
+    }
+
+    // -- game_passes plus what was compared, for showing why a game was or wasn't picked
+    pub fn explain(&self, cfg: &SConfig, game: &SCollectionGame, session_state: &SGameSessionState) -> SFilterCriterionResult {
+        let passed = self.game_passes(cfg, game, session_state);
+        let cmp = |passed: bool, pass_cmp: &'static str, fail_cmp: &'static str| if passed { pass_cmp } else { fail_cmp };
+
+        let session_count = session_state.effective_session_count(game);
+        let phantom_note = if game.custom_info.phantom_session { " (including phantom session)" } else { "" };
+        let added = || game.added_date.map(|d| d.to_string()).unwrap_or_else(|| String::from("unknown"));
+
+        let (criterion, detail) = match self {
+            Self::Tag{tag, value} => {
+                let actual = tag.value(&game.custom_info.tags);
+                let detail = if passed {
+                    format!("tag {}={} matches required {}", tag.name(), actual, value)
+                }
+                else {
+                    format!("tag {}={} but filter requires {}", tag.name(), actual, value)
+                };
+                (format!("tag {}", tag.name()), detail)
+            },
+            Self::Released(val) => (String::from("released"),
+                format!("release date {:?}, released={} (required {})", game.release_date(cfg), game.released(cfg), val)),
+            Self::KnownReleaseDate(val) => (String::from("known release date"),
+                format!("release date {:?} (known date required: {})", game.release_date(cfg), val)),
+            Self::Alive(val) => {
+                let choose_state = &game.choose_state;
+                let passes_ok = choose_state.ignore_passes || choose_state.passes <= cfg.live_max_passes;
+                let detail = if choose_state.retired {
+                    String::from("game is retired")
+                }
+                else if !passes_ok {
+                    format!("passes {} > live_max_passes {}", choose_state.passes, cfg.live_max_passes)
+                }
+                else if choose_state.ignore_passes {
+                    String::from("not retired, passes ignored")
+                }
+                else {
+                    format!("not retired, passes {} <= live_max_passes {}", choose_state.passes, cfg.live_max_passes)
+                };
+                (String::from("alive"), format!("{} (alive={}, required {})", detail, choose_state.alive(cfg), val))
+            },
+            Self::AfterValidDate => (String::from("after valid date"),
+                format!("next_valid_proposal_date {} {} today", game.choose_state.next_valid_proposal_date, cmp(passed, "<=", ">"))),
+            Self::Owned(val) => (String::from("ownership"), format!("owned={} (required {})", game.custom_info.own.owned(), val)),
+            Self::OwnedOn(name) => (format!("owned on {}", name), format!("owned on {}={}", name, passed)),
+            Self::NoHLTBData => (String::from("no hltb data"), format!("how_long_to_beat {:?} (required Unknown)", game.how_long_to_beat)),
+            Self::MinHLTBHours(min_hours) => (String::from("min hltb hours"),
+                format!("how_long_to_beat {:?}, filter requires >= {} hours", game.how_long_to_beat, min_hours)),
+            Self::MaxHLTBHours(max_hours) => (String::from("max hltb hours"),
+                format!("how_long_to_beat {:?}, filter requires <= {} hours", game.how_long_to_beat, max_hours)),
+            Self::Archived(val) => (String::from("archived"), format!("archived={} (required {})", game.custom_info.archived, val)),
+            Self::TitleContains(text) => (String::from("title"),
+                format!("title \"{}\" {} \"{}\"", game.game_info.title(), cmp(passed, "contains", "does not contain"), text)),
+            Self::MinAddedDate(date) => (String::from("added after"), format!("added {} (required on or after {})", added(), date)),
+            Self::MaxAddedDate(date) => (String::from("added before"), format!("added {} (required on or before {})", added(), date)),
+            Self::MinSessions(min) => (String::from("min sessions"),
+                format!("sessions {}{} {} min_sessions {}", session_count, phantom_note, cmp(passed, ">=", "<"), min)),
+            Self::MaxSessions(max) => (String::from("max sessions"),
+                format!("sessions {}{} {} max_sessions {}", session_count, phantom_note, cmp(passed, "<=", ">"), max)),
+            Self::ActiveSession(val) => (String::from("active session"),
+                format!("active session={} (required {})", session_state.has_active_session, val)),
+            Self::IGDBFacet(facet_value) => {
+                let name = facet_value.facet.name();
+                let detail = match game.game_info.igdb_metadata() {
+                    None => String::from("game has no IGDB metadata"),
+                    Some(m) => format!("{} values [{}] {} {}", name, facet_value.facet.values(m).join(", "), cmp(passed, "include", "do not include"), facet_value.value),
+                };
+                (format!("{} {}", name, facet_value.value), detail)
+            },
+        };
+
+        SFilterCriterionResult::new(criterion, passed, detail)
+    }
+}
+
+impl EGameFilterExpr {
+    // -- matches every game
+    pub fn all() -> Self {
+        Self::And(Vec::new())
+    }
+
+    pub fn leaf(predicate: EGameFilterPredicate) -> Self {
+        Self::Leaf(predicate)
+    }
+
+    pub fn negate(expr: EGameFilterExpr) -> Self {
+        Self::Not(Box::new(expr))
+    }
+
+    pub fn and(mut self, other: EGameFilterExpr) -> Self {
+        if let Self::And(inner) = &mut self {
+            inner.push(other);
+            return self;
+        }
+
+        Self::And(vec![self, other])
+    }
+
+    pub fn or(mut self, other: EGameFilterExpr) -> Self {
+        if let Self::Or(inner) = &mut self {
+            inner.push(other);
+            return self;
+        }
+
+        Self::Or(vec![self, other])
+    }
+
+    // -- true if any leaf, negated or not, satisfies matches
+    pub fn has_predicate(&self, matches: &impl Fn(&EGameFilterPredicate) -> bool) -> bool {
+        match self {
+            Self::And(exprs) | Self::Or(exprs) => exprs.iter().any(|e| e.has_predicate(matches)),
+            Self::Not(expr) => expr.has_predicate(matches),
+            Self::Leaf(predicate) => matches(predicate),
+        }
+    }
+
+    // -- every leaf tested on its own. Leaves under a Not pass when their predicate doesn't, and leaves
+    // -- that are one of several alternatives are marked, since failing one doesn't fail the filter.
+    pub fn explain(&self, cfg: &SConfig, game: &SCollectionGame, session_state: &SGameSessionState) -> SFilterExplanation {
+        let mut criteria = Vec::new();
+        self.explain_leaves(cfg, game, session_state, false, false, &mut criteria);
+
+        SFilterExplanation {
+            passed: self.game_passes(cfg, game, session_state),
+            criteria,
+        }
+    }
+
+    fn explain_leaves(
+        &self,
+        cfg: &SConfig,
+        game: &SCollectionGame,
+        session_state: &SGameSessionState,
+        negated: bool,
+        alternative: bool,
+        output: &mut Vec<SFilterCriterionResult>,
+    ) {
+        match self {
+            // -- a negated And is an Or of the negated children
+            Self::And(exprs) | Self::Or(exprs) => {
+                let any_of = matches!(self, Self::Or(_)) != negated;
+                let alternative = alternative || (any_of && exprs.len() > 1);
+                for expr in exprs {
+                    expr.explain_leaves(cfg, game, session_state, negated, alternative, output);
+                }
+            },
+            Self::Not(expr) => expr.explain_leaves(cfg, game, session_state, !negated, alternative, output),
+            Self::Leaf(predicate) => {
+                let mut result = predicate.explain(cfg, game, session_state);
+                if negated {
+                    result.passed = !result.passed;
+                    result.criterion = format!("not {}", result.criterion);
+                }
+                if alternative {
+                    result.criterion = format!("one of: {}", result.criterion);
+                }
+                output.push(result);
+            },
+        }
+    }
+
+    pub fn game_passes(&self, cfg: &SConfig, game: &SCollectionGame, session_state: &SGameSessionState) -> bool {
+        match self {
+            Self::And(exprs) => exprs.iter().all(|e| e.game_passes(cfg, game, session_state)),
+            Self::Or(exprs) => exprs.iter().any(|e| e.game_passes(cfg, game, session_state)),
+            Self::Not(expr) => !expr.game_passes(cfg, game, session_state),
+            Self::Leaf(predicate) => predicate.game_passes(cfg, game, session_state),
+        }
+    }
+}
+
+impl SGameSessionState {
+    // -- session state for every game that has at least one session, keyed by internal id
+    pub fn build_map(sessions: &[SSession]) -> HashMap<u32, Self> {
+        let mut result : HashMap<u32, Self> = HashMap::new();
+        for session in sessions {
+            let state = result.entry(session.game_internal_id).or_default();
+            state.session_count += 1;
+
+            if let ESessionState::Ongoing = session.state {
+                state.has_active_session = true;
+            }
+
+            if state.last_session_date.is_none_or(|d| session.start_date > d) {
+                state.last_session_date = Some(session.start_date);
+            }
+        }
+
+        result
+    }
+
+    pub fn effective_session_count(&self, game: &SCollectionGame) -> u16 {
+        if game.custom_info.phantom_session {
+            return self.session_count + 1;
+        }
+
+        self.session_count
+    }
+}
+
+impl From<SCollectionGameFilter> for EGameFilterExpr {
+    fn from(f: SCollectionGameFilter) -> Self {
+        use EGameFilterPredicate::*;
+
+        let mut leaves = Vec::new();
+
+        let tags = [
+            (EGameTag::CouchPlayable, f.tags.couch_playable),
+            (EGameTag::PortablePlayable, f.tags.portable_playable),
+            (EGameTag::JapanesePractice, f.tags.japanese_practice),
+            (EGameTag::Retro, f.tags.retro),
+            (EGameTag::PickUpAndPlay, f.tags.pick_up_and_play),
+        ];
+        for (tag, required) in tags {
+            if let Some(value) = required {
+                leaves.push(Tag{tag, value});
+            }
+        }
+
+        if let Some(val) = f.require_released {
+            leaves.push(Released(val));
+        }
+        if let Some(val) = f.required_alive_state {
+            leaves.push(Alive(val));
+        }
+        if f.require_is_after_valid_date {
+            leaves.push(AfterValidDate);
+        }
+        if let Some(val) = f.required_ownership_state {
+            leaves.push(Owned(val));
+        }
+        if f.require_no_hltb_data {
+            leaves.push(NoHLTBData);
+        }
+        if let Some(val) = f.max_hltb_hours {
+            leaves.push(MaxHLTBHours(val));
+        }
+        if f.require_not_archived {
+            leaves.push(Archived(false));
+        }
+
+        let mut exprs : Vec<EGameFilterExpr> = leaves.into_iter().map(EGameFilterExpr::Leaf).collect();
+        for facet_value in f.igdb_facets.include {
+            exprs.push(EGameFilterExpr::leaf(IGDBFacet(facet_value)));
+        }
+        for facet_value in f.igdb_facets.exclude {
+            exprs.push(EGameFilterExpr::negate(EGameFilterExpr::leaf(IGDBFacet(facet_value))));
+        }
+
+        EGameFilterExpr::And(exprs)
+    }
+}
+
+impl From<SCollectionGameSessionStateFilter> for EGameFilterExpr {
+    fn from(f: SCollectionGameSessionStateFilter) -> Self {
+        use EGameFilterPredicate::*;
+
+        let mut leaves = Vec::new();
+
+        if let Some(min) = f.min_sessions {
+            leaves.push(MinSessions(min));
+        }
+        if let Some(max) = f.max_sessions {
+            leaves.push(MaxSessions(max));
+        }
+        if let Some(val) = f.required_active_session {
+            leaves.push(ActiveSession(val));
+        }
+
+        EGameFilterExpr::And(leaves.into_iter().map(EGameFilterExpr::Leaf).collect())
+    }
+}
+
+impl From<SCollectionGameAndSessionStateFilter> for EGameFilterExpr {
+    fn from(f: SCollectionGameAndSessionStateFilter) -> Self {
+        let game_expr : EGameFilterExpr = f.game_filter.into();
+
+        match f.session_state_filter {
+            Some(session_state_filter) => game_expr.and(session_state_filter.into()),
+            None => game_expr,
+        }
+    }
+}
+
+impl EGameFilterRequest {
+    pub fn into_expression(self) -> Result<EGameFilterExpr, SQueryParseError> {
+        match self {
+            Self::Expression(e) => Ok(e),
+            Self::Legacy(l) => Ok(l.into()),
+            Self::Query(q) => Ok(parse_collection_query(q.as_str())?.into_filter_expr()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EGameInfo, SPlatformReleaseDate, WORLDWIDE_REGION};
+    use crate::test_games::{collection_game, custom_game};
+
+    #[test]
+    fn or_of_tags() {
+        let cfg = SConfig::default();
+        let state = SGameSessionState::default();
+
+        let couch_or_portable = EGameFilterExpr::leaf(EGameFilterPredicate::Tag{tag: EGameTag::CouchPlayable, value: true})
+            .or(EGameFilterExpr::leaf(EGameFilterPredicate::Tag{tag: EGameTag::PortablePlayable, value: true}));
+
+        let mut game = custom_game("Test Game", 1);
+        assert!(!couch_or_portable.game_passes(&cfg, &game, &state));
+
+        game.custom_info.tags.portable_playable = true;
+        assert!(couch_or_portable.game_passes(&cfg, &game, &state));
+        assert!(!EGameFilterExpr::negate(couch_or_portable).game_passes(&cfg, &game, &state));
+    }
+
+    #[test]
+    fn explain_marks_negated_and_alternative_leaves() {
+        let cfg = SConfig::default();
+        let state = SGameSessionState::default();
+        let mut game = custom_game("Test Game", 1);
+        game.custom_info.tags.retro = true;
+
+        let expr = parse_collection_query("archived:no (tag:couch OR -tag:retro)").unwrap().into_filter_expr();
+        let explanation = expr.explain(&cfg, &game, &state);
+        assert!(!explanation.passed);
+
+        let criteria : Vec<(&str, bool)> = explanation.criteria.iter().map(|c| (c.criterion.as_str(), c.passed)).collect();
+        assert_eq!(criteria, vec![("archived", true), ("one of: tag couch", false), ("one of: not tag retro", false)]);
+        assert_eq!(explanation.criteria[2].detail, "tag retro=true matches required true");
+    }
+
+    #[test]
+    fn legacy_filter_conversion_matches() {
+        let cfg = SConfig::default();
+        let mut game = custom_game("Test Game", 1);
+        game.custom_info.own.switch = true;
+
+        let legacy = SCollectionGameAndSessionStateFilter::with_session_filter(
+            SCollectionGameFilter::new().require_ownership(true).require_released(true),
+            SCollectionGameSessionStateFilter::new().max_sessions(0),
+        );
+        let expr : EGameFilterExpr = legacy.clone().into();
+
+        for session_count in 0..2 {
+            let state = SGameSessionState{ session_count, ..Default::default() };
+            let legacy_passes = legacy.game_filter.game_passes(&cfg, &game)
+                && legacy.session_state_filter.as_ref().unwrap().game_passes(&game, session_count, false);
+            assert_eq!(expr.game_passes(&cfg, &game, &state), legacy_passes);
+        }
+
+        game.custom_info.archived = true;
+        assert!(!expr.game_passes(&cfg, &game, &SGameSessionState::default()));
+    }
+
+    #[test]
+    fn known_release_date_follows_platform_preferences() {
+        let release = SPlatformReleaseDate {
+            platform: String::from("PlayStation 4"),
+            region: String::from(WORLDWIDE_REGION),
+            date: EReleaseDate::UnknownUnreleased,
+        };
+        let game_info = EGameInfo::new_igdb(1, "slug", None, "Test Game", EReleaseDate::Known(chrono::NaiveDate::from_ymd(2016, 4, 12)))
+            .with_platform_release_dates(vec![release]);
+        let game = collection_game(game_info, 1);
+        let known = EGameFilterPredicate::KnownReleaseDate(true);
+
+        let mut cfg = SConfig::default();
+        assert!(!known.game_passes(&cfg, &game, &SGameSessionState::default()));
+
+        cfg.release_preferences.platforms = vec![String::from("Nintendo Switch")];
+        assert!(known.game_passes(&cfg, &game, &SGameSessionState::default()));
+    }
+
+    #[test]
+    fn request_accepts_legacy_json() {
+        let legacy : SCollectionGameAndSessionStateFilter = SCollectionGameFilter::new().into();
+        let json = serde_json::to_string(&legacy).unwrap();
+        let request : EGameFilterRequest = serde_json::from_str(json.as_str()).unwrap();
+        assert!(matches!(request, EGameFilterRequest::Legacy(_)));
+
+        let json = serde_json::to_string(&EGameFilterExpr::all()).unwrap();
+        let request : EGameFilterRequest = serde_json::from_str(json.as_str()).unwrap();
+        assert!(matches!(request, EGameFilterRequest::Expression(_)));
+
+        let request : EGameFilterRequest = serde_json::from_str("\"tag:retro\"").unwrap();
+        assert!(matches!(request, EGameFilterRequest::Query(_)));
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Serialize, Deserialize};
+
+use crate::collection_game_filter::{SCollectionGameFilter, SCollectionGameSessionStateFilter, SCollectionGameAndSessionStateFilter};
+use crate::filter_expression::{EGameFilterExpr, EGameFilterPredicate};
+use crate::game_list::{EGameSortKey, SGameSort};
+
+// -- extra info shown over the cover on compact game cards
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ECompactGameCardBadge {
+    None,
+    ReleaseDate,
+    HLTBSearch,
+    AliveState,
+}
+
+// -- a named filter for the full collection screen
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SFilterPreset {
+    pub name: String, // unique key
+    pub display_name: String,
+    pub filter: EGameFilterExpr,
+    pub sort: SGameSort,
+    pub badge: ECompactGameCardBadge,
+}
+
+impl SFilterPreset {
+    pub fn new(name: &str, display_name: &str, filter: EGameFilterExpr) -> Self {
+        Self {
+            name: name.to_string(),
+            display_name: display_name.to_string(),
+            filter,
+            sort: SGameSort::none(),
+            badge: ECompactGameCardBadge::None,
+        }
+    }
+
+    pub fn sort(mut self, sort: SGameSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn badge(mut self, badge: ECompactGameCardBadge) -> Self {
+        self.badge = badge;
+        self
+    }
+}
+
+// -- the presets that used to be hard coded in the client, used to seed databases without any
+pub fn default_filter_presets() -> Vec<SFilterPreset> {
+    let known_release_date = EGameFilterExpr::leaf(EGameFilterPredicate::KnownReleaseDate(true));
+
+    vec![
+        SFilterPreset::new("live", "Only Alive", SCollectionGameFilter::new().require_alive(true).into()),
+        SFilterPreset::new("all", "All", SCollectionGameFilter::new().into())
+            .badge(ECompactGameCardBadge::AliveState),
+        SFilterPreset::new("owned_dead_unplayed", "Owned But Abandoned",
+            SCollectionGameAndSessionStateFilter::with_session_filter(
+                SCollectionGameFilter::new()
+                    .require_tag_pick_up_and_play(false)
+                    .require_ownership(true)
+                    .require_alive(false),
+                SCollectionGameSessionStateFilter::new()
+                    .max_sessions(0),
+            ).into()),
+        SFilterPreset::new("live_released_no_hltb", "Needs HLTB Data",
+            SCollectionGameFilter::new()
+                .require_alive(true)
+                .require_released(true)
+                .require_no_hltb_data().into())
+            .badge(ECompactGameCardBadge::HLTBSearch),
+        SFilterPreset::new("not_released", "Upcoming Releases", SCollectionGameFilter::new().require_released(false).into())
+            .sort(SGameSort::ascending(EGameSortKey::ReleaseDate))
+            .badge(ECompactGameCardBadge::ReleaseDate),
+        SFilterPreset::new("new_releases", "Unplayed New Releases",
+            EGameFilterExpr::from(SCollectionGameAndSessionStateFilter::with_session_filter(
+                SCollectionGameFilter::new()
+                    .require_alive(true)
+                    .require_released(true),
+                SCollectionGameSessionStateFilter::new()
+                    .max_sessions(0),
+            )).and(known_release_date))
+            .sort(SGameSort::descending(EGameSortKey::ReleaseDate))
+            .badge(ECompactGameCardBadge::ReleaseDate),
+        SFilterPreset::new("to_buy", "Shopping List",
+            SCollectionGameFilter::new()
+                .require_alive(true)
+                .require_released(true)
+                .require_ownership(false)
+                .into()),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EReleaseDate, SCollectionGame, SConfig, SGameSessionState};
+    use crate::test_games::{custom_add_game, custom_game};
+
+    #[test]
+    fn default_preset_names_are_unique() {
+        let presets = default_filter_presets();
+        let mut names : Vec<&str> = presets.iter().map(|p| p.name.as_str()).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), presets.len());
+    }
+
+    #[test]
+    fn new_releases_skips_unknown_dates() {
+        let preset = default_filter_presets().into_iter().find(|p| p.name == "new_releases").unwrap();
+        let cfg = SConfig::default();
+        let state = SGameSessionState::default();
+
+        let game = custom_game("Unknown", 1);
+        assert!(!preset.filter.game_passes(&cfg, &game, &state));
+
+        let known = custom_add_game("Known", EReleaseDate::Known(chrono::naive::NaiveDate::from_ymd(2020, 1, 1)));
+        let game = SCollectionGame::new(known, 2);
+        assert!(preset.filter.game_passes(&cfg, &game, &state));
+    }
+}
@@ -0,0 +1,343 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{SCollectionGame, SConfig, SSessionAndCollectionGame, SSessionFilter};
+use crate::filter_expression::{EGameFilterRequest, SGameSessionState};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EGameSortKey {
+    None,
+    Title, // ignores leading articles
+    ReleaseDate,
+    AddedDate,
+    LastSession,
+    HLTB,
+    Passes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SGameSort {
+    pub key: EGameSortKey,
+    pub descending: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ESessionSortKey {
+    None,
+    StartDate,
+    // -- the rest sort by the session's game, same as the matching EGameSortKey
+    Title,
+    ReleaseDate,
+    AddedDate,
+    LastSession,
+    HLTB,
+    Passes,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SSessionSort {
+    pub key: ESessionSortKey,
+    pub descending: bool,
+}
+
+// -- offset pagination, no limit returns everything after offset
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SPageRequest {
+    pub offset: usize,
+    pub limit: Option<usize>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SPage<T> {
+    pub items: Vec<T>,
+    pub offset: usize,
+    pub total: usize, // matching items before paging
+
+    // -- database revision the page was read at
+    #[serde(default)]
+    pub revision: u64,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SGetGamesRequest {
+    pub filter: EGameFilterRequest,
+
+    #[serde(default)]
+    pub sort: SGameSort,
+
+    #[serde(default)]
+    pub page: SPageRequest,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SGetSessionsRequest {
+    pub filter: SSessionFilter,
+
+    #[serde(default)]
+    pub sort: SSessionSort,
+
+    #[serde(default)]
+    pub page: SPageRequest,
+}
+
+// -- lowercase, without a leading "the", "a" or "an"
+pub fn title_sort_key(title: &str) -> String {
+    let lower = title.trim().to_lowercase();
+    for article in ["the ", "a ", "an "] {
+        if let Some(rest) = lower.strip_prefix(article) {
+            return rest.trim_start().to_string();
+        }
+    }
+
+    lower
+}
+
+// -- missing values always sort last, regardless of direction
+fn cmp_options<T: Ord>(a: Option<T>, b: Option<T>, descending: bool) -> Ordering {
+    match (a, b) {
+        (Some(a_inner), Some(b_inner)) => {
+            if descending {
+                b_inner.cmp(&a_inner)
+            }
+            else {
+                a_inner.cmp(&b_inner)
+            }
+        },
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+impl SGameSort {
+    pub fn none() -> Self {
+        Self {
+            key: EGameSortKey::None,
+            descending: false,
+        }
+    }
+
+    pub fn ascending(key: EGameSortKey) -> Self {
+        Self {
+            key,
+            descending: false,
+        }
+    }
+
+    pub fn descending(key: EGameSortKey) -> Self {
+        Self {
+            key,
+            descending: true,
+        }
+    }
+
+    pub fn compare(&self, config: &SConfig, a: &SCollectionGame, b: &SCollectionGame, session_states: &HashMap<u32, SGameSessionState>) -> Ordering {
+        let last_session = |g: &SCollectionGame| session_states.get(&g.internal_id).and_then(|s| s.last_session_date);
+
+        let ordering = match self.key {
+            EGameSortKey::None => Ordering::Equal,
+            EGameSortKey::Title => title_sort_key(a.game_info.title()).cmp(&title_sort_key(b.game_info.title())),
+            EGameSortKey::ReleaseDate => a.release_date(config).cmp(&b.release_date(config)),
+            EGameSortKey::AddedDate => return cmp_options(a.added_date, b.added_date, self.descending),
+            EGameSortKey::LastSession => return cmp_options(last_session(a), last_session(b), self.descending),
+            EGameSortKey::HLTB => return cmp_options(a.how_long_to_beat.hours_to_beat(), b.how_long_to_beat.hours_to_beat(), self.descending),
+            EGameSortKey::Passes => a.choose_state.passes.cmp(&b.choose_state.passes),
+        };
+
+        if self.descending {
+            return ordering.reverse();
+        }
+
+        ordering
+    }
+
+    // -- stable, so games with equal keys keep collection order
+    pub fn sort_games(&self, config: &SConfig, games: &mut [&SCollectionGame], session_states: &HashMap<u32, SGameSessionState>) {
+        if self.key == EGameSortKey::None {
+            return;
+        }
+
+        games.sort_by(|a, b| self.compare(config, a, b, session_states));
+    }
+}
+
+impl Default for SGameSort {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl ESessionSortKey {
+    fn game_key(&self) -> Option<EGameSortKey> {
+        match self {
+            Self::None | Self::StartDate => None,
+            Self::Title => Some(EGameSortKey::Title),
+            Self::ReleaseDate => Some(EGameSortKey::ReleaseDate),
+            Self::AddedDate => Some(EGameSortKey::AddedDate),
+            Self::LastSession => Some(EGameSortKey::LastSession),
+            Self::HLTB => Some(EGameSortKey::HLTB),
+            Self::Passes => Some(EGameSortKey::Passes),
+        }
+    }
+}
+
+impl SSessionSort {
+    // -- stable, so sessions with equal keys keep database order
+    pub fn sort_sessions(&self, config: &SConfig, sessions: &mut [SSessionAndCollectionGame], session_states: &HashMap<u32, SGameSessionState>) {
+        if let Some(key) = self.key.game_key() {
+            let game_sort = SGameSort{ key, descending: self.descending };
+            sessions.sort_by(|a, b| game_sort.compare(config, &a.collection_game, &b.collection_game, session_states));
+        }
+        else if self.key == ESessionSortKey::StartDate {
+            sessions.sort_by(|a, b| {
+                let ordering = a.session.start_date.cmp(&b.session.start_date);
+                if self.descending { ordering.reverse() } else { ordering }
+            });
+        }
+    }
+}
+
+impl Default for SSessionSort {
+    fn default() -> Self {
+        Self {
+            key: ESessionSortKey::None,
+            descending: false,
+        }
+    }
+}
+
+impl SPageRequest {
+    pub fn first(limit: usize) -> Self {
+        Self {
+            offset: 0,
+            limit: Some(limit),
+        }
+    }
+
+    pub fn range(&self, total: usize) -> std::ops::Range<usize> {
+        let start = std::cmp::min(self.offset, total);
+        let end = match self.limit {
+            Some(l) => std::cmp::min(start.saturating_add(l), total),
+            None => total,
+        };
+
+        start..end
+    }
+}
+
+impl<T> SPage<T> {
+    pub fn has_more(&self) -> bool {
+        self.offset + self.items.len() < self.total
+    }
+}
+
+impl<T: Clone> SPage<T> {
+    pub fn from_refs(request: &SPageRequest, all: &[&T]) -> Self {
+        let range = request.range(all.len());
+
+        Self {
+            offset: range.start,
+            items: all[range].iter().map(|item| (*item).clone()).collect(),
+            total: all.len(),
+            revision: 0,
+        }
+    }
+
+    pub fn revision(mut self, revision: u64) -> Self {
+        self.revision = revision;
+        self
+    }
+}
+
+impl SGetGamesRequest {
+    pub fn new(filter: EGameFilterRequest) -> Self {
+        Self {
+            filter,
+            sort: SGameSort::none(),
+            page: SPageRequest::default(),
+        }
+    }
+
+    pub fn sort(mut self, sort: SGameSort) -> Self {
+        self.sort = sort;
+        self
+    }
+
+    pub fn page(mut self, page: SPageRequest) -> Self {
+        self.page = page;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EHowLongToBeat, SSession};
+    use crate::test_games::custom_game;
+
+    #[test]
+    fn title_sort_ignores_articles() {
+        let games = [custom_game("The Witness", 1), custom_game("A Short Hike", 2), custom_game("Celeste", 3)];
+        let mut refs : Vec<&SCollectionGame> = games.iter().collect();
+
+        SGameSort::ascending(EGameSortKey::Title).sort_games(&SConfig::default(), &mut refs, &HashMap::new());
+        let ids : Vec<u32> = refs.iter().map(|g| g.internal_id).collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn missing_hltb_sorts_last() {
+        let mut games = [custom_game("One", 1), custom_game("Two", 2), custom_game("Three", 3)];
+        games[0].how_long_to_beat = EHowLongToBeat::Manual(5);
+        games[2].how_long_to_beat = EHowLongToBeat::Manual(20);
+
+        for (descending, expected) in [(false, vec![1, 3, 2]), (true, vec![3, 1, 2])] {
+            let mut refs : Vec<&SCollectionGame> = games.iter().collect();
+            let sort = SGameSort{ key: EGameSortKey::HLTB, descending };
+            sort.sort_games(&SConfig::default(), &mut refs, &HashMap::new());
+            let ids : Vec<u32> = refs.iter().map(|g| g.internal_id).collect();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[test]
+    fn descending_session_sort_keeps_ties_in_order() {
+        let games = [custom_game("Celeste", 1), custom_game("Braid", 2)];
+        let sessions = || -> Vec<SSessionAndCollectionGame> {
+            [(10, 0), (11, 1), (12, 0)].iter()
+                .map(|&(id, game)| SSessionAndCollectionGame{
+                    session: SSession::new(id, games[game].internal_id),
+                    collection_game: games[game].clone(),
+                })
+                .collect()
+        };
+
+        for (key, expected) in [(ESessionSortKey::StartDate, vec![10, 11, 12]), (ESessionSortKey::Title, vec![10, 12, 11])] {
+            let mut sorted = sessions();
+            SSessionSort{ key, descending: true }.sort_sessions(&SConfig::default(), &mut sorted, &HashMap::new());
+            let ids : Vec<u32> = sorted.iter().map(|s| s.session.internal_id).collect();
+            assert_eq!(ids, expected);
+        }
+    }
+
+    #[test]
+    fn page_ranges() {
+        let items = [1, 2, 3, 4, 5];
+        let refs : Vec<&i32> = items.iter().collect();
+
+        let page = SPage::from_refs(&SPageRequest::first(2), &refs);
+        assert_eq!(page.items, vec![1, 2]);
+        assert_eq!(page.total, 5);
+        assert!(page.has_more());
+
+        let page = SPage::from_refs(&SPageRequest{ offset: 4, limit: Some(2) }, &refs);
+        assert_eq!(page.items, vec![5]);
+        assert!(!page.has_more());
+
+        let page = SPage::from_refs(&SPageRequest{ offset: 10, limit: None }, &refs);
+        assert!(page.items.is_empty());
+        assert_eq!(page.offset, 5);
+    }
+}
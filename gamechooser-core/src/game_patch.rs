@@ -0,0 +1,188 @@
+use serde::{Serialize, Deserialize};
+
+use crate::{EHowLongToBeat, EReleaseDate, SCollectionGame, SGameChooseState, SGameTags, SOwn};
+
+// -- a partial update to a collection game. Each section that is Some replaces that section, anything
+// -- None is left alone, so edits to different sections from different devices don't overwrite each other.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SGamePatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub release_date: Option<EReleaseDate>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub how_long_to_beat: Option<EHowLongToBeat>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<SGameTags>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub own: Option<SOwn>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub via: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub archived: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub choose_state: Option<SGameChooseState>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SGamePatchRequest {
+    pub internal_id: u32,
+
+    // -- the game's revision when the client read it, None skips the check
+    #[serde(default)]
+    pub expected_revision: Option<u32>,
+
+    pub patch: SGamePatch,
+}
+
+// -- EReleaseDate's PartialEq treats the unknown variants loosely, a patch needs to see any change
+fn same_release_date(a: &EReleaseDate, b: &EReleaseDate) -> bool {
+    match (a, b) {
+        (EReleaseDate::UnknownUnreleased, EReleaseDate::UnknownUnreleased) => true,
+        (EReleaseDate::UnknownReleased, EReleaseDate::UnknownReleased) => true,
+        (EReleaseDate::Known(a), EReleaseDate::Known(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn changed<T: Clone + PartialEq>(before: &T, after: &T) -> Option<T> {
+    if before != after {
+        Some(after.clone())
+    }
+    else {
+        None
+    }
+}
+
+impl SGamePatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // -- only the sections that differ between before and after
+    pub fn diff(before: &SCollectionGame, after: &SCollectionGame) -> Self {
+        let before_date = before.game_info.release_date();
+        let after_date = after.game_info.release_date();
+
+        Self {
+            title: changed(&before.game_info.title(), &after.game_info.title()).map(|t| t.to_string()),
+            release_date: if same_release_date(&before_date, &after_date) { None } else { Some(after_date) },
+            how_long_to_beat: changed(&before.how_long_to_beat, &after.how_long_to_beat),
+            tags: changed(&before.custom_info.tags, &after.custom_info.tags),
+            own: changed(&before.custom_info.own, &after.custom_info.own),
+            via: changed(&before.custom_info.via, &after.custom_info.via),
+            archived: changed(&before.custom_info.archived, &after.custom_info.archived),
+            choose_state: changed(&before.choose_state, &after.choose_state),
+        }
+    }
+
+    pub fn title(mut self, title: &str) -> Self {
+        self.title = Some(title.to_string());
+        self
+    }
+
+    pub fn release_date(mut self, release_date: EReleaseDate) -> Self {
+        self.release_date = Some(release_date);
+        self
+    }
+
+    pub fn how_long_to_beat(mut self, how_long_to_beat: EHowLongToBeat) -> Self {
+        self.how_long_to_beat = Some(how_long_to_beat);
+        self
+    }
+
+    pub fn tags(mut self, tags: SGameTags) -> Self {
+        self.tags = Some(tags);
+        self
+    }
+
+    pub fn own(mut self, own: SOwn) -> Self {
+        self.own = Some(own);
+        self
+    }
+
+    pub fn via(mut self, via: &str) -> Self {
+        self.via = Some(via.to_string());
+        self
+    }
+
+    pub fn archived(mut self, archived: bool) -> Self {
+        self.archived = Some(archived);
+        self
+    }
+
+    pub fn choose_state(mut self, choose_state: SGameChooseState) -> Self {
+        self.choose_state = Some(choose_state);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn apply(&self, game: &mut SCollectionGame) {
+        if let Some(title) = &self.title {
+            game.game_info.set_title(title.as_str());
+        }
+        if let Some(release_date) = &self.release_date {
+            game.game_info.set_release_date(release_date.clone());
+        }
+        if let Some(how_long_to_beat) = &self.how_long_to_beat {
+            game.how_long_to_beat = how_long_to_beat.clone();
+        }
+        if let Some(tags) = &self.tags {
+            game.custom_info.tags = tags.clone();
+        }
+        if let Some(own) = &self.own {
+            game.custom_info.own = own.clone();
+        }
+        if let Some(via) = &self.via {
+            game.custom_info.via = via.clone();
+        }
+        if let Some(archived) = self.archived {
+            game.custom_info.archived = archived;
+        }
+        if let Some(choose_state) = self.choose_state {
+            game.choose_state = choose_state;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_games::{custom_add_game, custom_game};
+
+    #[test]
+    fn patches_merge() {
+        let original = custom_game("Outer Wilds", 1);
+
+        // -- two devices edit different sections of the same game
+        let mut device_a = original.clone();
+        device_a.custom_info.via = String::from("a friend");
+        let mut device_b = original.clone();
+        device_b.custom_info.tags.couch_playable = true;
+
+        let patch_a = SGamePatch::diff(&original, &device_a);
+        let patch_b = SGamePatch::diff(&original, &device_b);
+        assert_eq!(patch_a, SGamePatch::new().via("a friend"));
+        assert!(patch_b.via.is_none() && patch_b.tags.is_some());
+
+        let mut stored = original.clone();
+        patch_a.apply(&mut stored);
+        patch_b.apply(&mut stored);
+        assert_eq!(stored.custom_info.via, "a friend");
+        assert!(stored.custom_info.tags.couch_playable);
+    }
+
+    #[test]
+    fn diff_sees_unknown_release_date_changes() {
+        let original = SCollectionGame::new(custom_add_game("Outer Wilds", EReleaseDate::UnknownUnreleased), 1);
+        let mut edited = original.clone();
+        edited.game_info.set_release_date(EReleaseDate::UnknownReleased);
+
+        let patch = SGamePatch::diff(&original, &edited);
+        assert!(matches!(patch.release_date, Some(EReleaseDate::UnknownReleased)));
+        assert!(SGamePatch::diff(&original, &original).is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use crate::{SCollectionGame, SIGDBMetadata};
+
+// -- the IGDB metadata lists games can be filtered on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EIGDBFacet {
+    Genre,
+    Theme,
+    GameMode,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SIGDBFacetValue {
+    pub facet: EIGDBFacet,
+    pub value: String, // IGDB's name, compared case insensitively
+}
+
+// -- a game passes if it has every included value and none of the excluded ones. Games without
+// -- IGDB metadata have no values, so they fail any include but pass every exclude.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SIGDBFacetFilter {
+    #[serde(default)]
+    pub include: Vec<SIGDBFacetValue>,
+    #[serde(default)]
+    pub exclude: Vec<SIGDBFacetValue>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SIGDBFacetOption {
+    pub value: String,
+    pub game_count: u32,
+}
+
+// -- every value of a facet that appears in the collection, for building filter choices
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SIGDBFacetOptions {
+    pub facet: EIGDBFacet,
+    pub options: Vec<SIGDBFacetOption>,
+}
+
+impl EIGDBFacet {
+    pub const ALL: [EIGDBFacet; 3] = [
+        EIGDBFacet::Genre,
+        EIGDBFacet::Theme,
+        EIGDBFacet::GameMode,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Genre => "genre",
+            Self::Theme => "theme",
+            Self::GameMode => "game mode",
+        }
+    }
+
+    pub fn values<'a>(&self, metadata: &'a SIGDBMetadata) -> &'a [String] {
+        match self {
+            Self::Genre => &metadata.genres,
+            Self::Theme => &metadata.themes,
+            Self::GameMode => &metadata.game_modes,
+        }
+    }
+}
+
+impl SIGDBFacetValue {
+    pub fn new(facet: EIGDBFacet, value: &str) -> Self {
+        Self {
+            facet,
+            value: value.to_string(),
+        }
+    }
+
+    pub fn game_has_value(&self, game: &SCollectionGame) -> bool {
+        match game.game_info.igdb_metadata() {
+            Some(metadata) => self.facet.values(metadata).iter().any(|v| v.eq_ignore_ascii_case(self.value.as_str())),
+            None => false,
+        }
+    }
+}
+
+impl SIGDBFacetFilter {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+}
+
+impl SIGDBFacetOptions {
+    // -- one entry per facet, options sorted by name
+    pub fn collect<'a>(games: impl Iterator<Item = &'a SCollectionGame>) -> Vec<Self> {
+        let mut counts : Vec<BTreeMap<&str, u32>> = vec![BTreeMap::new(); EIGDBFacet::ALL.len()];
+
+        for game in games {
+            if let Some(metadata) = game.game_info.igdb_metadata() {
+                for (facet, facet_counts) in EIGDBFacet::ALL.iter().zip(counts.iter_mut()) {
+                    for value in facet.values(metadata) {
+                        *facet_counts.entry(value.as_str()).or_default() += 1;
+                    }
+                }
+            }
+        }
+
+        EIGDBFacet::ALL.iter().zip(counts).map(|(facet, facet_counts)| {
+            Self {
+                facet: *facet,
+                options: facet_counts.into_iter()
+                    .map(|(value, game_count)| SIGDBFacetOption{ value: value.to_string(), game_count })
+                    .collect(),
+            }
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EGameInfo, EReleaseDate};
+    use crate::test_games::{collection_game, custom_game};
+
+    fn igdb_game(id: u32, genres: &[&str], game_modes: &[&str]) -> SCollectionGame {
+        let metadata = SIGDBMetadata {
+            genres: genres.iter().map(|g| g.to_string()).collect(),
+            game_modes: game_modes.iter().map(|m| m.to_string()).collect(),
+            ..Default::default()
+        };
+        let game_info = EGameInfo::new_igdb(id, "slug", None, "Test Game", EReleaseDate::UnknownReleased)
+            .with_igdb_metadata(metadata);
+        collection_game(game_info, id)
+    }
+
+    #[test]
+    fn options_come_from_the_collection() {
+        let games = [
+            igdb_game(1, &["Platform", "Adventure"], &["Single player"]),
+            igdb_game(2, &["Platform"], &["Single player", "Co-operative"]),
+            custom_game("Custom", 3),
+        ];
+
+        let facets = SIGDBFacetOptions::collect(games.iter());
+        assert_eq!(facets.len(), 3);
+
+        let genres : Vec<(&str, u32)> = facets[0].options.iter().map(|o| (o.value.as_str(), o.game_count)).collect();
+        assert_eq!(genres, vec![("Adventure", 1), ("Platform", 2)]);
+        assert!(facets[1].options.is_empty());
+        assert_eq!(facets[2].options.len(), 2);
+
+        let coop = SIGDBFacetValue::new(EIGDBFacet::GameMode, "co-operative");
+        assert!(!coop.game_has_value(&games[0]));
+        assert!(coop.game_has_value(&games[1]));
+        assert!(!coop.game_has_value(&games[2]));
+    }
+}
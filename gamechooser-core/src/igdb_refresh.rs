@@ -0,0 +1,233 @@
+use serde::{Serialize, Deserialize};
+
+use chrono::naive::NaiveDateTime;
+
+use crate::{EGameInfo, EReleaseDate, SCollectionGame, SIGDBMetadata, SPlatformReleaseDate};
+use crate::config::SIGDBRefreshPolicy;
+
+// -- one field that differs between the stored game info and what IGDB returns now
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SFieldChange {
+    pub field: String,
+    pub before: String,
+    pub after: String,
+}
+
+// -- what a single game refresh would change, shown for confirmation before anything is saved
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SIGDBRefreshPreview {
+    pub internal_id: u32,
+    pub revision: u32,
+    pub game_info: EGameInfo,
+    pub changes: Vec<SFieldChange>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SIGDBRefreshRequest {
+    pub internal_id: u32,
+
+    // -- the game's revision when the preview was made, None skips the check
+    #[serde(default)]
+    pub expected_revision: Option<u32>,
+
+    pub game_info: EGameInfo,
+}
+
+fn release_date_text(release_date: &EReleaseDate) -> String {
+    match release_date {
+        EReleaseDate::UnknownUnreleased => String::from("unknown (unreleased)"),
+        EReleaseDate::UnknownReleased => String::from("unknown (released)"),
+        EReleaseDate::Known(date) => date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+fn platform_release_dates_text(dates: &[SPlatformReleaseDate]) -> String {
+    dates.iter()
+        .map(|d| format!("{} ({}) {}", d.platform, d.region, release_date_text(&d.date)))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn push_change(changes: &mut Vec<SFieldChange>, field: &str, before: String, after: String) {
+    if before != after {
+        changes.push(SFieldChange {
+            field: field.to_string(),
+            before,
+            after,
+        });
+    }
+}
+
+// -- each metadata field as display text, everything empty if there is no metadata
+fn metadata_fields(metadata: Option<&SIGDBMetadata>) -> Vec<(&'static str, String)> {
+    let empty = SIGDBMetadata::default();
+    let m = metadata.unwrap_or(&empty);
+    vec![
+        ("summary", m.summary.clone().unwrap_or_default()),
+        ("genres", m.genres.join(", ")),
+        ("themes", m.themes.join(", ")),
+        ("game_modes", m.game_modes.join(", ")),
+        ("platforms", m.platforms.join(", ")),
+        ("franchises", m.franchises.join(", ")),
+        ("developers", m.developers.join(", ")),
+        ("publishers", m.publishers.join(", ")),
+        ("aggregated_rating", m.aggregated_rating.map(|r| r.to_string()).unwrap_or_default()),
+        ("screenshots", m.screenshot_ids.len().to_string()),
+        ("websites", m.websites.iter().map(|w| w.url.as_str()).collect::<Vec<&str>>().join(", ")),
+    ]
+}
+
+pub fn game_info_changes(before: &EGameInfo, after: &EGameInfo) -> Vec<SFieldChange> {
+    let mut changes = Vec::new();
+
+    push_change(&mut changes, "title", before.title().to_string(), after.title().to_string());
+    push_change(&mut changes, "release_date", release_date_text(&before.release_date()), release_date_text(&after.release_date()));
+    push_change(&mut changes, "cover", before.cover_url().unwrap_or_default(), after.cover_url().unwrap_or_default());
+
+    if let (EGameInfo::IGDB(before), EGameInfo::IGDB(after)) = (before, after) {
+        push_change(&mut changes, "slug", before.slug.clone(), after.slug.clone());
+        push_change(&mut changes, "platform_release_dates",
+            platform_release_dates_text(&before.cached_platform_release_dates),
+            platform_release_dates_text(&after.cached_platform_release_dates));
+
+        let before_metadata = metadata_fields(before.metadata.as_deref());
+        for ((field, before), (_, after)) in before_metadata.into_iter().zip(metadata_fields(after.metadata.as_deref())) {
+            push_change(&mut changes, field, before, after);
+        }
+    }
+
+    changes
+}
+
+impl SIGDBRefreshPolicy {
+    pub fn should_refresh(&self, game: &SCollectionGame, has_sessions: bool, now: NaiveDateTime) -> bool {
+        let igdb = match &game.game_info {
+            EGameInfo::IGDB(inner) => inner,
+            EGameInfo::Custom(_) => return false,
+        };
+
+        if self.skip_archived && game.custom_info.archived {
+            return false;
+        }
+
+        // -- backfills come before the session skip, games that have been played are the ones most likely to be looked at
+        if self.refresh_missing_covers && igdb.cached_cover_id.is_none() {
+            return true;
+        }
+        if self.refresh_missing_metadata && igdb.metadata.is_none() {
+            return true;
+        }
+
+        if self.skip_games_with_sessions && has_sessions {
+            return false;
+        }
+
+        if let Some(days) = self.stale_after_days {
+            let stale = match igdb.last_refreshed {
+                Some(last) => now - last > chrono::Duration::days(days as i64),
+                None => true,
+            };
+            if stale {
+                return true;
+            }
+        }
+
+        match &igdb.cached_release_date {
+            EReleaseDate::UnknownUnreleased => self.refresh_unknown_unreleased,
+            EReleaseDate::UnknownReleased => self.refresh_unknown_released,
+            // -- we might falsely believe a game came out if it had a bad date, so recent releases are refreshed too
+            EReleaseDate::Known(date) => match self.recent_release_days {
+                Some(days) => *date >= now.date() - chrono::Duration::days(days as i64),
+                None => false,
+            },
+        }
+    }
+}
+
+impl SIGDBRefreshPreview {
+    pub fn new(game: &SCollectionGame, refreshed: EGameInfo) -> Self {
+        Self {
+            internal_id: game.internal_id,
+            revision: game.revision,
+            changes: game_info_changes(&game.game_info, &refreshed),
+            game_info: refreshed,
+        }
+    }
+}
+
+impl SIGDBRefreshRequest {
+    pub fn from_preview(preview: &SIGDBRefreshPreview) -> Self {
+        Self {
+            internal_id: preview.internal_id,
+            expected_revision: Some(preview.revision),
+            game_info: preview.game_info.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_changed_fields() {
+        let date = chrono::NaiveDate::from_ymd(2023, 2, 10);
+        let before = EGameInfo::new_igdb(7, "hogwarts-legacy", None, "Hogwarts Legacy", EReleaseDate::UnknownUnreleased);
+        let after = EGameInfo::new_igdb(7, "hogwarts-legacy", None, "Hogwarts Legacy", EReleaseDate::Known(date));
+
+        let changes = game_info_changes(&before, &after);
+        assert_eq!(changes, vec![SFieldChange {
+            field: String::from("release_date"),
+            before: String::from("unknown (unreleased)"),
+            after: String::from("2023-02-10"),
+        }]);
+        assert!(game_info_changes(&after, &after).is_empty());
+
+        let metadata = SIGDBMetadata {
+            genres: vec![String::from("Adventure"), String::from("RPG")],
+            ..SIGDBMetadata::default()
+        };
+        let with_metadata = after.clone().with_igdb_metadata(metadata);
+        let changes = game_info_changes(&after, &with_metadata);
+        assert_eq!(changes.len(), 1);
+        assert_eq!((changes[0].field.as_str(), changes[0].after.as_str()), ("genres", "Adventure, RPG"));
+    }
+
+    #[test]
+    fn refresh_policy() {
+        use crate::test_games::collection_game;
+
+        let now = chrono::NaiveDate::from_ymd(2022, 6, 1).and_hms(12, 0, 0);
+        let old_release = EReleaseDate::Known(chrono::NaiveDate::from_ymd(2015, 1, 1));
+        let game_info = EGameInfo::new_igdb(1, "slug", Some(String::from("co1")), "Title", old_release);
+        let mut game = collection_game(game_info, 1);
+
+        let mut policy = SIGDBRefreshPolicy::default();
+        assert!(policy.should_refresh(&game, false, now));
+        assert!(policy.should_refresh(&game, true, now));
+        game.game_info = game.game_info.with_igdb_metadata(SIGDBMetadata::default());
+        assert!(!policy.should_refresh(&game, false, now));
+        assert!(!policy.should_refresh(&game, true, now));
+
+        policy.stale_after_days = Some(30);
+        assert!(policy.should_refresh(&game, false, now));
+        assert!(!policy.should_refresh(&game, true, now));
+
+        game.game_info.set_last_refreshed(now - chrono::Duration::days(2));
+        assert!(!policy.should_refresh(&game, false, now));
+
+        game.game_info.set_release_date(EReleaseDate::UnknownUnreleased);
+        assert!(policy.should_refresh(&game, false, now));
+        game.custom_info.archived = true;
+        policy.skip_archived = true;
+        assert!(!policy.should_refresh(&game, false, now));
+
+        // -- archived games aren't backfilled either
+        let game_info = EGameInfo::new_igdb(2, "slug", None, "Title", EReleaseDate::UnknownReleased);
+        let mut game = collection_game(game_info, 2);
+        game.custom_info.archived = true;
+        assert!(!policy.should_refresh(&game, false, now));
+        policy.skip_archived = false;
+        assert!(policy.should_refresh(&game, false, now));
+    }
+}
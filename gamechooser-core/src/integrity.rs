@@ -0,0 +1,87 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::naive::NaiveDate;
+
+use crate::{ESessionState, SDatabase};
+use crate::validation;
+
+// -- finds problems in the stored data that the service would never create itself, e.g. from hand
+// -- edits to the JSON or old bugs. Returns a description of each problem, empty if all is well.
+pub fn check_integrity(db: &SDatabase, today: NaiveDate) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut game_ids = HashSet::new();
+    let mut igdb_ids = HashMap::new();
+    for game in &db.games {
+        if !game_ids.insert(game.internal_id) {
+            problems.push(format!("More than one game has internal_id {}", game.internal_id));
+        }
+
+        if let Some(igdb_id) = game.game_info.igdb_id() {
+            if let Some(other) = igdb_ids.insert(igdb_id, game.internal_id) {
+                problems.push(format!("Games {} and {} are both IGDB game {}", other, game.internal_id, igdb_id));
+            }
+        }
+
+        if let Err(e) = validation::validate_game(game, today) {
+            problems.push(format!("Game {} \"{}\" is invalid: {}", game.internal_id, game.game_info.title(), e));
+        }
+    }
+
+    let mut session_ids = HashSet::new();
+    let mut ongoing_games = HashSet::new();
+    for session in &db.sessions {
+        if !session_ids.insert(session.internal_id) {
+            problems.push(format!("More than one session has internal_id {}", session.internal_id));
+        }
+
+        if !game_ids.contains(&session.game_internal_id) {
+            problems.push(format!("Session {} is for missing game {}", session.internal_id, session.game_internal_id));
+        }
+
+        match session.state {
+            ESessionState::Ongoing => {
+                if !ongoing_games.insert(session.game_internal_id) {
+                    problems.push(format!("Game {} has more than one ongoing session", session.game_internal_id));
+                }
+            },
+            ESessionState::Finished{end_date, ..} => {
+                if end_date < session.start_date {
+                    problems.push(format!("Session {} ends before it starts", session.internal_id));
+                }
+            },
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SSession;
+    use crate::test_games::custom_game;
+
+    #[test]
+    fn finds_problems() {
+        let today = chrono::offset::Local::now().naive_local().date();
+        let mut db = SDatabase::new();
+        db.games.push(custom_game("Tunic", 1));
+        assert!(check_integrity(&db, today).is_empty());
+
+        let duplicate = db.games[0].clone();
+        db.games.push(duplicate);
+        db.sessions.push(SSession {
+            internal_id: 1,
+            game_internal_id: 2,
+            start_date: today,
+            state: ESessionState::Ongoing,
+            revision: 0,
+        });
+
+        let problems = check_integrity(&db, today);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("internal_id 1"));
+        assert!(problems[1].contains("missing game 2"));
+    }
+}
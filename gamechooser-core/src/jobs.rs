@@ -0,0 +1,155 @@
+use chrono::naive::NaiveDateTime;
+use serde::{Serialize, Deserialize};
+
+use crate::error::EError;
+
+// -- how many finished jobs are kept around for clients that reload mid-job
+const MAX_FINISHED_JOBS: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EJobKind {
+    IGDBUpdate,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum EJobState {
+    Running,
+    Finished,
+    Failed { message: String },
+}
+
+// -- a single item that failed, the job carries on with the rest
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SJobError {
+    pub title: String,
+    pub message: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SJob {
+    pub id: u32,
+    pub kind: EJobKind,
+    pub state: EJobState,
+    pub done: usize,
+    pub total: usize,
+    pub current_title: Option<String>,
+    pub errors: Vec<SJobError>,
+    pub started: NaiveDateTime,
+    pub finished: Option<NaiveDateTime>,
+}
+
+// -- long running work the server does in the background, so requests don't wait on it
+#[derive(Debug, Default)]
+pub struct SJobRegistry {
+    jobs: Vec<SJob>,
+    next_id: u32,
+}
+
+impl SJob {
+    pub fn running(&self) -> bool {
+        self.state == EJobState::Running
+    }
+}
+
+impl SJobRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // -- only one job of each kind runs at a time, starting another returns a conflict
+    pub fn start(&mut self, kind: EJobKind, now: NaiveDateTime) -> Result<&SJob, EError> {
+        if let Some(job) = self.jobs.iter().find(|j| j.kind == kind && j.running()) {
+            return Err(EError::Conflict(format!("Job {} is already running", job.id)));
+        }
+
+        self.trim_finished();
+
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.push(SJob {
+            id,
+            kind,
+            state: EJobState::Running,
+            done: 0,
+            total: 0,
+            current_title: None,
+            errors: Vec::new(),
+            started: now,
+            finished: None,
+        });
+
+        Ok(&self.jobs[self.jobs.len() - 1])
+    }
+
+    fn trim_finished(&mut self) {
+        let finished = self.jobs.iter().filter(|j| !j.running()).count();
+        let mut to_remove = finished.saturating_sub(MAX_FINISHED_JOBS - 1);
+        self.jobs.retain(|j| {
+            if to_remove > 0 && !j.running() {
+                to_remove -= 1;
+                return false;
+            }
+            true
+        });
+    }
+
+    pub fn job(&self, id: u32) -> Result<&SJob, EError> {
+        self.jobs.iter().find(|j| j.id == id).ok_or_else(|| EError::job_not_found(id))
+    }
+
+    pub fn jobs(&self) -> &[SJob] {
+        &self.jobs
+    }
+
+    // -- returns the job after the update
+    pub fn update<F>(&mut self, id: u32, f: F) -> Result<&SJob, EError>
+    where
+        F: FnOnce(&mut SJob),
+    {
+        let job = self.jobs.iter_mut().find(|j| j.id == id).ok_or_else(|| EError::job_not_found(id))?;
+        f(job);
+        Ok(job)
+    }
+
+    pub fn finish(&mut self, id: u32, now: NaiveDateTime, failure: Option<String>) -> Result<&SJob, EError> {
+        self.update(id, |job| {
+            job.current_title = None;
+            job.finished = Some(now);
+            job.state = match failure {
+                Some(message) => EJobState::Failed { message },
+                None => EJobState::Finished,
+            };
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn now() -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(2022, 5, 1).and_hms(12, 0, 0)
+    }
+
+    #[test]
+    fn one_running_job_per_kind() {
+        let mut registry = SJobRegistry::new();
+        let id = registry.start(EJobKind::IGDBUpdate, now()).unwrap().id;
+        assert!(matches!(registry.start(EJobKind::IGDBUpdate, now()), Err(EError::Conflict(_))));
+
+        registry.update(id, |job| job.done += 1).unwrap();
+        registry.finish(id, now(), None).unwrap();
+        assert_eq!(registry.job(id).unwrap().done, 1);
+        assert_eq!(registry.job(id).unwrap().state, EJobState::Finished);
+
+        // -- old finished jobs are dropped as new ones start
+        for _ in 0..(MAX_FINISHED_JOBS + 2) {
+            let next = registry.start(EJobKind::IGDBUpdate, now()).unwrap().id;
+            registry.finish(next, now(), Some(String::from("no session"))).unwrap();
+        }
+        assert_eq!(registry.jobs().len(), MAX_FINISHED_JOBS);
+        assert_eq!(registry.job(id).err(), Some(EError::job_not_found(id)));
+    }
+}
@@ -2,16 +2,51 @@ use chrono;
 use chrono::{Datelike};
 use serde::{Serialize, Deserialize};
 
+mod bulk_edit;
 mod collection_game_filter;
+mod collection_query;
 mod config;
 mod database_v2;
 mod database_v3;
+mod database_v4;
+mod error;
+mod events;
+mod filter_expression;
+mod filter_preset;
+mod game_list;
+mod game_patch;
+mod igdb_facets;
+mod igdb_refresh;
+mod integrity;
+mod jobs;
+mod repository;
+mod schedule;
+mod service;
+#[cfg(test)]
+mod test_games;
+mod validation;
 
-pub use config::SConfig;
-pub use collection_game_filter::{SCollectionGameFilter, SGameTagsFilter, SCollectionGameSessionStateFilter, SCollectionGameAndSessionStateFilter};
+pub use config::{SConfig, SIGDBRefreshPolicy, SReleasePreferences, WORLDWIDE_REGION};
+pub use collection_game_filter::{SCollectionGameFilter, SGameTagsFilter, SCollectionGameSessionStateFilter, SCollectionGameAndSessionStateFilter, SFilterCriterionResult, SFilterExplanation};
+pub use filter_expression::{EGameTag, EGameFilterPredicate, EGameFilterExpr, EGameFilterRequest, SGameSessionState};
+pub use collection_query::{SCollectionQuery, SQueryParseError, parse_collection_query};
+pub use filter_preset::{ECompactGameCardBadge, SFilterPreset, default_filter_presets};
+pub use repository::SCollectionRepository;
+pub use error::{EError, EErrorCode, SErrorBody, SFieldError};
+pub use game_patch::{SGamePatch, SGamePatchRequest};
+pub use igdb_facets::{EIGDBFacet, SIGDBFacetValue, SIGDBFacetFilter, SIGDBFacetOption, SIGDBFacetOptions};
+pub use igdb_refresh::{SFieldChange, SIGDBRefreshPreview, SIGDBRefreshRequest, game_info_changes};
+pub use bulk_edit::{EBulkEditTarget, EChooseStateAction, STagChange, SOwnChange, SBulkEdit, SBulkEditRequest, FIELD_OWN};
+pub use validation::{validate_add_game, validate_game, MAX_HOW_LONG_TO_BEAT_HOURS, FIELD_TITLE, FIELD_HOW_LONG_TO_BEAT, FIELD_NEXT_VALID_PROPOSAL_DATE};
+pub use events::EChangeEvent;
+pub use integrity::check_integrity;
+pub use schedule::SCronSchedule;
+pub use jobs::{EJobKind, EJobState, SJob, SJobError, SJobRegistry};
+pub use service::{SCollectionService, TCollectionStorage};
+pub use game_list::{EGameSortKey, SGameSort, ESessionSortKey, SSessionSort, SPageRequest, SPage, SGetGamesRequest, SGetSessionsRequest, title_sort_key};
 
 // -- latest database version is exported via pub
-pub use database_v3::*;
+pub use database_v4::*;
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct SSearchIGDBResult {
@@ -40,7 +75,7 @@ pub struct SSessionFilter {
     pub year: Option<u32>,
 }
 
-#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
 pub struct SSimpleStats {
     pub total_collection_size: u32,
 
@@ -68,7 +103,8 @@ pub struct SSimpleStats {
 #[derive(Debug, Serialize, Deserialize)]
 pub enum EDatabase {
     V2(database_v2::SDatabase),
-    V3(SDatabase),
+    V3(database_v3::SDatabase),
+    V4(SDatabase),
 }
 
 impl std::ops::Deref for EDatabase {
@@ -76,7 +112,7 @@ impl std::ops::Deref for EDatabase {
 
     fn deref(&self) -> &Self::Target {
         #[allow(irrefutable_let_patterns)]
-        if let Self::V3(inner) = self {
+        if let Self::V4(inner) = self {
             return inner;
         }
         panic!("Trying to deref on database that is not of current version.");
@@ -86,7 +122,7 @@ impl std::ops::Deref for EDatabase {
 impl std::ops::DerefMut for EDatabase {
     fn deref_mut(&mut self) -> &mut Self::Target {
         #[allow(irrefutable_let_patterns)]
-        if let Self::V3(inner) = self {
+        if let Self::V4(inner) = self {
             return inner;
         }
         panic!("Trying to deref on database that is not of current version.");
@@ -111,8 +147,42 @@ impl SCollectionGame {
             how_long_to_beat: add.how_long_to_beat,
             custom_info: add.custom_info,
             choose_state: Default::default(),
+            added_date: Some(chrono::offset::Local::now().naive_local().date()),
+            revision: 0,
+        }
+    }
+
+    // -- earliest release on a platform and in a region the config cares about. Owning a copy on a
+    // -- platform means it is out there, and games with no matching release use the overall date.
+    pub fn release_date(&self, config: &SConfig) -> EReleaseDate {
+        let releases = self.game_info.platform_release_dates();
+        if releases.is_empty() {
+            return self.game_info.release_date();
+        }
+
+        let prefs = &config.release_preferences;
+        let owned_platforms = if prefs.include_owned_platforms {
+            self.custom_info.own.igdb_platforms()
+        }
+        else {
+            Vec::new()
+        };
+
+        let earliest = releases.iter()
+            .filter(|r| prefs.wants_platform(r.platform.as_str(), &owned_platforms) && prefs.wants_region(r.region.as_str()))
+            .map(|r| r.date.clone())
+            .min();
+
+        match earliest {
+            Some(date) if date.released() || owned_platforms.is_empty() => date,
+            _ if !owned_platforms.is_empty() => EReleaseDate::UnknownReleased,
+            _ => self.game_info.release_date(),
         }
     }
+
+    pub fn released(&self, config: &SConfig) -> bool {
+        self.release_date(config).released()
+    }
 }
 
 impl SSessionFilter {
@@ -172,22 +242,116 @@ impl SGameChooseState {
 
 impl EDatabase {
     pub fn new() -> Self {
-        Self::V3(SDatabase::new())
+        Self::V4(SDatabase::new())
     }
 
     pub fn to_latest_version(self) -> Self {
         match self {
-            EDatabase::V2(v2) => EDatabase::V3(SDatabase::from_v2(v2)),
-            EDatabase::V3(_) => self,
+            EDatabase::V2(v2) => EDatabase::V4(SDatabase::from_v3(database_v3::SDatabase::from_v2(v2))),
+            EDatabase::V3(v3) => EDatabase::V4(SDatabase::from_v3(v3)),
+            EDatabase::V4(_) => self,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use test_games::collection_game;
+
     #[test]
     fn it_works() {
         let result = 2 + 2;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn v3_database_upgrades() {
+        let v3 = serde_json::json!({"V3": {
+            "games": [{
+                "internal_id": 4,
+                "game_info": {"IGDB": {"id": 26226, "slug": "celeste", "cached_title": "Celeste",
+                    "cached_release_date": {"Known": "2018-01-25"}, "cached_cover_id": null,
+                    "last_refreshed": "2022-06-01T12:00:00"}},
+                "custom_info": {"via": "", "tags": SGameTags::default(), "own": SOwn::default()},
+                "choose_state": {"next_valid_proposal_date": "2022-06-01", "retired": false, "passes": 1, "ignore_passes": false},
+                "revision": 7
+            }],
+            "sessions": [],
+            "revision": 12
+        }});
+
+        let db = serde_json::from_value::<EDatabase>(v3).unwrap().to_latest_version();
+        assert!(matches!(db, EDatabase::V4(_)));
+        assert_eq!(db.revision, 12);
+
+        let game = &db.games[0];
+        assert_eq!((game.internal_id, game.revision, game.choose_state.passes), (4, 7, 1));
+        match &game.game_info {
+            EGameInfo::IGDB(igdb) => {
+                assert!(igdb.last_refreshed.is_some());
+                assert!(igdb.metadata.is_none());
+            },
+            EGameInfo::Custom(_) => panic!("expected IGDB info"),
+        }
+    }
+
+    #[test]
+    fn released_on_wanted_platforms() {
+        let today = chrono::offset::Local::now().naive_local().date();
+        let release = |platform: &str, region: &str, date: EReleaseDate| SPlatformReleaseDate {
+            platform: platform.to_string(),
+            region: region.to_string(),
+            date,
+        };
+
+        let dates = vec![
+            release("Nintendo Switch", "Japan", EReleaseDate::Known(today - chrono::Duration::days(30))),
+            release("Nintendo Switch", "North America", EReleaseDate::Known(today + chrono::Duration::days(30))),
+            release("PC (Microsoft Windows)", WORLDWIDE_REGION, EReleaseDate::UnknownUnreleased),
+        ];
+        let game_info = EGameInfo::new_igdb(1, "slug", None, "Test Game", EReleaseDate::UnknownUnreleased)
+            .with_platform_release_dates(dates);
+        let mut game = collection_game(game_info, 1);
+
+        let mut config = SConfig::default();
+        assert!(game.released(&config));
+
+        config.release_preferences.regions = vec![String::from("North America")];
+        assert_eq!(game.release_date(&config), EReleaseDate::Known(today + chrono::Duration::days(30)));
+        assert!(!game.released(&config));
+
+        // -- only the PC release counts, and that hasn't happened
+        config.release_preferences.regions.clear();
+        config.release_preferences.platforms = vec![String::from("PC (Microsoft Windows)")];
+        assert!(!game.released(&config));
+
+        // -- unless there's a copy on PC, which means it's out there
+        game.custom_info.own.steam = true;
+        assert!(game.released(&config));
+    }
+
+    #[test]
+    fn release_date_without_matching_platforms() {
+        let release = SPlatformReleaseDate {
+            platform: String::from("PlayStation 4"),
+            region: String::from(WORLDWIDE_REGION),
+            date: EReleaseDate::UnknownUnreleased,
+        };
+        let overall = EReleaseDate::Known(chrono::NaiveDate::from_ymd(2016, 4, 12));
+        let game_info = EGameInfo::new_igdb(1, "slug", None, "Test Game", overall.clone())
+            .with_platform_release_dates(vec![release]);
+        let mut game = collection_game(game_info, 1);
+
+        // -- nothing on a wanted platform falls back to the overall date
+        let mut config = SConfig::default();
+        config.release_preferences.platforms = vec![String::from("Nintendo Switch")];
+        assert_eq!(game.release_date(&config), overall);
+
+        // -- owned on a platform IGDB doesn't list, so it is out there
+        config.release_preferences.platforms.clear();
+        game.custom_info.own.ps5 = true;
+        assert!(matches!(game.release_date(&config), EReleaseDate::UnknownReleased));
+        assert!(game.released(&config));
+    }
 }
@@ -0,0 +1,275 @@
+use std::collections::HashMap;
+
+use crate::{EDatabase, EGameInfo, ESessionState, SAddCollectionGame, SCollectionGame, SSession};
+use crate::filter_expression::SGameSessionState;
+use crate::filter_preset::SFilterPreset;
+
+// -- owns the database and keeps lookup tables in sync with it. All mutation goes through here so
+// -- the indexes never need a full rebuild after load, and so every change bumps the revisions.
+pub struct SCollectionRepository {
+    db: EDatabase,
+
+    game_idx_by_id: HashMap<u32, usize>,
+    game_id_by_igdb_id: HashMap<u32, u32>,
+    session_idx_by_id: HashMap<u32, usize>,
+    session_ids_by_game_id: HashMap<u32, Vec<u32>>,
+
+    next_game_id: u32,
+    next_session_id: u32,
+}
+
+fn igdb_id(game: &SCollectionGame) -> Option<u32> {
+    match &game.game_info {
+        EGameInfo::IGDB(igdb_info) => Some(igdb_info.id),
+        EGameInfo::Custom(_) => None,
+    }
+}
+
+impl SCollectionRepository {
+    pub fn new(db: EDatabase) -> Self {
+        let mut result = Self {
+            db: db.to_latest_version(),
+            game_idx_by_id: HashMap::new(),
+            game_id_by_igdb_id: HashMap::new(),
+            session_idx_by_id: HashMap::new(),
+            session_ids_by_game_id: HashMap::new(),
+            next_game_id: 1,
+            next_session_id: 1,
+        };
+
+        for idx in 0..result.db.games.len() {
+            result.index_game(idx);
+        }
+        for idx in 0..result.db.sessions.len() {
+            result.index_session(idx);
+        }
+
+        result
+    }
+
+    fn index_game(&mut self, idx: usize) {
+        let game = &self.db.games[idx];
+        self.game_idx_by_id.insert(game.internal_id, idx);
+        if let Some(igdb_id) = igdb_id(game) {
+            self.game_id_by_igdb_id.insert(igdb_id, game.internal_id);
+        }
+        self.next_game_id = std::cmp::max(self.next_game_id, game.internal_id + 1);
+    }
+
+    fn index_session(&mut self, idx: usize) {
+        let session = &self.db.sessions[idx];
+        self.session_idx_by_id.insert(session.internal_id, idx);
+        self.session_ids_by_game_id.entry(session.game_internal_id).or_default().push(session.internal_id);
+        self.next_session_id = std::cmp::max(self.next_session_id, session.internal_id + 1);
+    }
+
+    // -- for serialization
+    pub fn database(&self) -> &EDatabase {
+        &self.db
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.db.revision
+    }
+
+    fn bump_revision(&mut self) {
+        self.db.revision += 1;
+    }
+
+    pub fn games(&self) -> &[SCollectionGame] {
+        &self.db.games
+    }
+
+    pub fn sessions(&self) -> &[SSession] {
+        &self.db.sessions
+    }
+
+    pub fn filter_presets(&self) -> &Vec<SFilterPreset> {
+        &self.db.filter_presets
+    }
+
+    pub fn filter_presets_mut(&mut self) -> &mut Vec<SFilterPreset> {
+        self.bump_revision();
+        &mut self.db.filter_presets
+    }
+
+    pub fn game(&self, internal_id: u32) -> Option<&SCollectionGame> {
+        self.game_idx_by_id.get(&internal_id).map(|idx| &self.db.games[*idx])
+    }
+
+    pub fn game_by_igdb_id(&self, igdb_id: u32) -> Option<&SCollectionGame> {
+        self.game_id_by_igdb_id.get(&igdb_id).and_then(|id| self.game(*id))
+    }
+
+    pub fn add_game(&mut self, add: SAddCollectionGame) -> u32 {
+        let internal_id = self.next_game_id;
+        self.db.games.push(SCollectionGame::new(add, internal_id));
+        self.index_game(self.db.games.len() - 1);
+        self.bump_revision();
+
+        internal_id
+    }
+
+    // -- returns None if there is no such game. The IGDB index is kept up to date even if f
+    // -- changes game_info, but f must not change internal_id. Whatever f does to revision is
+    // -- overwritten with the next revision.
+    pub fn update_game<F, R>(&mut self, internal_id: u32, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut SCollectionGame) -> R,
+    {
+        let idx = *self.game_idx_by_id.get(&internal_id)?;
+        let game = &mut self.db.games[idx];
+
+        let old_igdb_id = igdb_id(game);
+        let old_revision = game.revision;
+        let result = f(game);
+        assert_eq!(game.internal_id, internal_id, "update_game must not change internal_id");
+        game.revision = old_revision + 1;
+        let new_igdb_id = igdb_id(game);
+
+        if old_igdb_id != new_igdb_id {
+            if let Some(old) = old_igdb_id {
+                self.game_id_by_igdb_id.remove(&old);
+            }
+            if let Some(new) = new_igdb_id {
+                self.game_id_by_igdb_id.insert(new, internal_id);
+            }
+        }
+
+        self.bump_revision();
+        Some(result)
+    }
+
+    // -- replace a game wholesale, returns false if there is no game with its internal_id
+    pub fn replace_game(&mut self, game: SCollectionGame) -> bool {
+        self.update_game(game.internal_id, |existing| *existing = game).is_some()
+    }
+
+    pub fn session(&self, internal_id: u32) -> Option<&SSession> {
+        self.session_idx_by_id.get(&internal_id).map(|idx| &self.db.sessions[*idx])
+    }
+
+    pub fn sessions_for_game(&self, game_internal_id: u32) -> impl Iterator<Item = &SSession> {
+        self.session_ids_by_game_id
+            .get(&game_internal_id)
+            .into_iter()
+            .flatten()
+            .filter_map(move |id| self.session(*id))
+    }
+
+    pub fn ongoing_session_for_game(&self, game_internal_id: u32) -> Option<&SSession> {
+        self.sessions_for_game(game_internal_id).find(|s| matches!(s.state, ESessionState::Ongoing))
+    }
+
+    pub fn session_state(&self, game_internal_id: u32) -> SGameSessionState {
+        let mut result = SGameSessionState::default();
+        for session in self.sessions_for_game(game_internal_id) {
+            result.session_count += 1;
+            if let ESessionState::Ongoing = session.state {
+                result.has_active_session = true;
+            }
+            if result.last_session_date.is_none_or(|d| session.start_date > d) {
+                result.last_session_date = Some(session.start_date);
+            }
+        }
+
+        result
+    }
+
+    // -- returns None if the game does not exist
+    pub fn add_session(&mut self, game_internal_id: u32) -> Option<u32> {
+        if !self.game_idx_by_id.contains_key(&game_internal_id) {
+            return None;
+        }
+
+        let internal_id = self.next_session_id;
+        self.db.sessions.push(SSession::new(internal_id, game_internal_id));
+        self.index_session(self.db.sessions.len() - 1);
+        self.bump_revision();
+
+        Some(internal_id)
+    }
+
+    // -- f must not change internal_id or game_internal_id, revision is bumped like update_game
+    pub fn update_session<F, R>(&mut self, internal_id: u32, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut SSession) -> R,
+    {
+        let idx = *self.session_idx_by_id.get(&internal_id)?;
+        let session = &mut self.db.sessions[idx];
+
+        let game_internal_id = session.game_internal_id;
+        let old_revision = session.revision;
+        let result = f(session);
+        assert!(session.internal_id == internal_id && session.game_internal_id == game_internal_id,
+            "update_session must not change ids");
+        session.revision = old_revision + 1;
+
+        self.bump_revision();
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EReleaseDate;
+
+    fn igdb_game(igdb_id: u32) -> SAddCollectionGame {
+        SAddCollectionGame::new(EGameInfo::new_igdb(igdb_id, "slug", None, "Title", EReleaseDate::UnknownReleased))
+    }
+
+    #[test]
+    fn ids_continue_after_load() {
+        let mut repo = SCollectionRepository::new(EDatabase::new());
+        let first = repo.add_game(igdb_game(100));
+        let second = repo.add_game(igdb_game(200));
+        assert_eq!((first, second), (1, 2));
+
+        let session = repo.add_session(second).unwrap();
+        assert!(repo.add_session(99).is_none());
+
+        let json = serde_json::to_string(repo.database()).unwrap();
+        let mut reloaded = SCollectionRepository::new(serde_json::from_str(json.as_str()).unwrap());
+        assert_eq!(reloaded.add_game(igdb_game(300)), 3);
+        assert_eq!(reloaded.add_session(first), Some(session + 1));
+        assert_eq!(reloaded.game_by_igdb_id(200).unwrap().internal_id, 2);
+    }
+
+    #[test]
+    fn indexes_follow_updates() {
+        let mut repo = SCollectionRepository::new(EDatabase::new());
+        let id = repo.add_game(igdb_game(100));
+
+        repo.update_game(id, |g| g.game_info = igdb_game(101).game_info).unwrap();
+        assert!(repo.game_by_igdb_id(100).is_none());
+        assert_eq!(repo.game_by_igdb_id(101).unwrap().internal_id, id);
+
+        let session_id = repo.add_session(id).unwrap();
+        assert!(repo.ongoing_session_for_game(id).is_some());
+        assert!(repo.session_state(id).has_active_session);
+
+        repo.update_session(session_id, |s| s.finish(true)).unwrap();
+        assert!(repo.ongoing_session_for_game(id).is_none());
+        assert_eq!(repo.session_state(id).session_count, 1);
+    }
+
+    #[test]
+    fn revisions_bump_on_change() {
+        let mut repo = SCollectionRepository::new(EDatabase::new());
+        let id = repo.add_game(igdb_game(100));
+        assert_eq!((repo.game(id).unwrap().revision, repo.revision()), (0, 1));
+
+        // -- a stale copy can't roll the revision back
+        let mut stale = repo.game(id).unwrap().clone();
+        repo.update_game(id, |g| g.custom_info.via = String::from("sale")).unwrap();
+        stale.revision = 0;
+        repo.replace_game(stale);
+        assert_eq!(repo.game(id).unwrap().revision, 2);
+
+        let session_id = repo.add_session(id).unwrap();
+        repo.update_session(session_id, |s| s.finish(false)).unwrap();
+        assert_eq!(repo.session(session_id).unwrap().revision, 1);
+        assert_eq!(repo.revision(), 5);
+    }
+}
@@ -0,0 +1,160 @@
+use chrono::{Datelike, Timelike};
+use chrono::naive::NaiveDateTime;
+
+// -- a cron-like "minute hour day-of-month month day-of-week" schedule. Each field takes *, a number,
+// -- a range a-b, a step */n or a-b/n, or a comma separated list of those. @hourly, @daily, @weekly
+// -- and @monthly are accepted as shorthands. Day of week is 0-7, both 0 and 7 are Sunday.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SCronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+
+    // -- like cron, if both day fields are restricted a time matches when either one does
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+fn parse_number(text: &str, min: u32, max: u32, field: &str) -> Result<u32, String> {
+    let value : u32 = text.parse().map_err(|_| format!("\"{}\" is not a number in the {} field", text, field))?;
+    if value < min || value > max {
+        return Err(format!("{} is outside {}-{} in the {} field", value, min, max, field));
+    }
+    Ok(value)
+}
+
+// -- returns the bitmask of allowed values and whether the field was anything other than *
+fn parse_field(text: &str, min: u32, max: u32, field: &str) -> Result<(u64, bool), String> {
+    let mut mask = 0u64;
+    let mut restricted = false;
+
+    for part in text.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, parse_number(step, 1, max, field)?),
+            None => (part, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        }
+        else if let Some((start, end)) = range.split_once('-') {
+            restricted = true;
+            (parse_number(start, min, max, field)?, parse_number(end, min, max, field)?)
+        }
+        else {
+            restricted = true;
+            let value = parse_number(range, min, max, field)?;
+            // -- "5/15" means from 5 to the end in steps of 15
+            (value, if part.contains('/') { max } else { value })
+        };
+
+        if start > end {
+            return Err(format!("{} is backwards in the {} field", range, field));
+        }
+        if step > 1 {
+            restricted = true;
+        }
+
+        let mut value = start;
+        while value <= end {
+            mask |= 1 << value;
+            value += step;
+        }
+    }
+
+    Ok((mask, restricted))
+}
+
+impl SCronSchedule {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let expanded = match text.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+
+        let fields : Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(format!("\"{}\" should have 5 fields: minute hour day-of-month month day-of-week", text));
+        }
+
+        let (minutes, _) = parse_field(fields[0], 0, 59, "minute")?;
+        let (hours, _) = parse_field(fields[1], 0, 23, "hour")?;
+        let (days_of_month, days_of_month_restricted) = parse_field(fields[2], 1, 31, "day of month")?;
+        let (months, _) = parse_field(fields[3], 1, 12, "month")?;
+        let (mut days_of_week, days_of_week_restricted) = parse_field(fields[4], 0, 7, "day of week")?;
+
+        // -- fold 7 into 0 so Sunday only needs checking once
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week |= 1;
+        }
+
+        Ok(Self {
+            minutes,
+            hours,
+            days_of_month,
+            months,
+            days_of_week,
+            days_of_month_restricted,
+            days_of_week_restricted,
+        })
+    }
+
+    // -- whether the schedule fires during the minute containing time
+    pub fn matches(&self, time: NaiveDateTime) -> bool {
+        let has = |mask: u64, value: u32| mask & (1 << value) != 0;
+
+        let day_of_month = has(self.days_of_month, time.day());
+        let day_of_week = has(self.days_of_week, time.weekday().num_days_from_sunday());
+        let day = match (self.days_of_month_restricted, self.days_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            _ => day_of_month && day_of_week,
+        };
+
+        day
+            && has(self.minutes, time.minute())
+            && has(self.hours, time.hour())
+            && has(self.months, time.month())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        chrono::NaiveDate::from_ymd(y, m, d).and_hms(h, min, 0)
+    }
+
+    #[test]
+    fn parses_fields() {
+        let schedule = SCronSchedule::parse("*/15 3-5 * * 1,3").unwrap();
+
+        // -- 2022-06-01 is a Wednesday
+        assert!(schedule.matches(at(2022, 6, 1, 3, 30)));
+        assert!(schedule.matches(at(2022, 6, 1, 5, 45)));
+        assert!(!schedule.matches(at(2022, 6, 1, 6, 0)));
+        assert!(!schedule.matches(at(2022, 6, 1, 3, 10)));
+        assert!(!schedule.matches(at(2022, 6, 2, 3, 0)));
+
+        let daily = SCronSchedule::parse("@daily").unwrap();
+        assert!(daily.matches(at(2022, 6, 2, 0, 0)));
+        assert!(!daily.matches(at(2022, 6, 2, 0, 1)));
+
+        let sunday = SCronSchedule::parse("0 12 * * 7").unwrap();
+        assert!(sunday.matches(at(2022, 6, 5, 12, 0)));
+    }
+
+    #[test]
+    fn rejects_bad_expressions() {
+        assert!(SCronSchedule::parse("* * * *").is_err());
+        assert!(SCronSchedule::parse("60 * * * *").is_err());
+        assert!(SCronSchedule::parse("5-1 * * * *").is_err());
+        assert!(SCronSchedule::parse("* * 0 * *").is_err());
+        assert!(SCronSchedule::parse("a * * * *").is_err());
+    }
+}
@@ -0,0 +1,818 @@
+use std::collections::HashMap;
+
+use crate::bulk_edit::{EBulkEditTarget, SBulkEditRequest};
+use crate::{EDatabase, EReleaseDate, SAddCollectionGame, SCollectionGame, SConfig, SSession, SSessionAndCollectionGame, SSimpleStats};
+use crate::collection_game_filter::{SCollectionGameFilter, SFilterExplanation};
+use crate::filter_expression::{EGameFilterExpr, EGameFilterRequest, SGameSessionState};
+use crate::filter_preset::SFilterPreset;
+use crate::game_list::{SGetGamesRequest, SGetSessionsRequest, SPage};
+use crate::game_patch::{SGamePatch, SGamePatchRequest};
+use crate::igdb_facets::SIGDBFacetOptions;
+use crate::igdb_refresh::SIGDBRefreshRequest;
+use crate::error::EError;
+use crate::events::EChangeEvent;
+use crate::repository::SCollectionRepository;
+use crate::integrity;
+use crate::validation;
+
+// -- where the service persists after every change. The server writes JSON files, tests keep
+// -- everything in memory.
+pub trait TCollectionStorage {
+    fn save(&mut self, config: &SConfig, db: &EDatabase) -> Result<(), String>;
+}
+
+type ChangeListener = Box<dyn Fn(&EChangeEvent) + Send + Sync>;
+
+// -- the collection rules shared by the server and any other frontend
+pub struct SCollectionService<S: TCollectionStorage> {
+    config: SConfig,
+    repository: SCollectionRepository,
+    storage: S,
+    change_listener: Option<ChangeListener>,
+}
+
+fn game_edited(game: SCollectionGame) -> EChangeEvent {
+    EChangeEvent::GameEdited { game }
+}
+
+fn choose_state_changed(game: SCollectionGame) -> EChangeEvent {
+    EChangeEvent::ChooseStateChanged { game }
+}
+
+fn patch_event(patch: &SGamePatch) -> fn(SCollectionGame) -> EChangeEvent {
+    let choose_state_only = SGamePatch{ choose_state: None, ..patch.clone() }.is_empty();
+    if choose_state_only { choose_state_changed } else { game_edited }
+}
+
+impl<S: TCollectionStorage> SCollectionService<S> {
+    pub fn new(config: SConfig, db: EDatabase, storage: S) -> Self {
+        Self {
+            config,
+            repository: SCollectionRepository::new(db),
+            storage,
+            change_listener: None,
+        }
+    }
+
+    // -- called with every change after it has been saved
+    pub fn set_change_listener(&mut self, listener: ChangeListener) {
+        self.change_listener = Some(listener);
+    }
+
+    pub fn publish(&self, event: EChangeEvent) {
+        if let Some(listener) = &self.change_listener {
+            listener(&event);
+        }
+    }
+
+    fn publish_game(&self, internal_id: u32, event: fn(SCollectionGame) -> EChangeEvent) {
+        if let Some(game) = self.repository.game(internal_id) {
+            self.publish(event(game.clone()));
+        }
+    }
+
+    pub fn config(&self) -> &SConfig {
+        &self.config
+    }
+
+    pub fn repository(&self) -> &SCollectionRepository {
+        &self.repository
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+
+    pub fn save(&mut self) -> Result<(), EError> {
+        self.storage.save(&self.config, self.repository.database()).map_err(EError::Storage)
+    }
+
+    fn game(&self, internal_id: u32) -> Result<&SCollectionGame, EError> {
+        self.repository.game(internal_id).ok_or_else(|| EError::game_not_found(internal_id))
+    }
+
+    fn check_game_revision(&self, internal_id: u32, expected_revision: Option<u32>) -> Result<&SCollectionGame, EError> {
+        let game = self.game(internal_id)?;
+        match expected_revision {
+            Some(expected) if expected != game.revision => Err(EError::Conflict(format!(
+                "\"{}\" was changed since it was loaded (revision {}, expected {}), reload it and try again",
+                game.game_info.title(), game.revision, expected))),
+            _ => Ok(game),
+        }
+    }
+
+    fn update_game_and_save<F>(&mut self, internal_id: u32, event: fn(SCollectionGame) -> EChangeEvent, f: F) -> Result<(), EError>
+    where
+        F: FnOnce(&mut SCollectionGame),
+    {
+        self.repository.update_game(internal_id, f).ok_or_else(|| EError::game_not_found(internal_id))?;
+        self.save()?;
+        self.publish_game(internal_id, event);
+        Ok(())
+    }
+
+    // -- games
+
+    pub fn add_game(&mut self, mut game: SAddCollectionGame) -> Result<u32, EError> {
+        validation::validate_add_game(&game)?;
+
+        // -- IGDB info comes straight from a search, so it counts as refreshed
+        game.game_info.set_last_refreshed(chrono::offset::Local::now().naive_local());
+
+        let internal_id = self.repository.add_game(game);
+        self.save()?;
+        self.publish_game(internal_id, |game| EChangeEvent::GameAdded { game });
+        Ok(internal_id)
+    }
+
+    // -- game.revision must match the stored game
+    pub fn edit_game(&mut self, game: SCollectionGame) -> Result<(), EError> {
+        // -- check the game exists first, unknown ids used to be silently ignored
+        self.check_game_revision(game.internal_id, Some(game.revision))?;
+
+        let today = chrono::offset::Local::now().naive_local().date();
+        validation::validate_game(&game, today)?;
+
+        let internal_id = game.internal_id;
+        self.repository.replace_game(game);
+        self.save()?;
+        self.publish_game(internal_id, game_edited);
+        Ok(())
+    }
+
+    // -- refresh cached IGDB info for a batch of games with a single save, without touching anything the
+    // -- user has customized. Games changed since their expected revision are skipped and returned with
+    // -- the conflict, the rest are still saved.
+    pub fn refresh_games_info(&mut self, requests: Vec<SIGDBRefreshRequest>) -> Result<Vec<(u32, EError)>, EError> {
+        let now = chrono::offset::Local::now().naive_local();
+
+        let mut skipped = Vec::new();
+        let mut refreshed = Vec::with_capacity(requests.len());
+        for request in requests {
+            if let Err(e) = self.check_game_revision(request.internal_id, request.expected_revision) {
+                skipped.push((request.internal_id, e));
+                continue;
+            }
+
+            let mut game_info = request.game_info;
+            game_info.set_last_refreshed(now);
+            self.repository.update_game(request.internal_id, |game| game.game_info = game_info);
+            refreshed.push(request.internal_id);
+        }
+
+        if !refreshed.is_empty() {
+            self.save()?;
+        }
+        for internal_id in refreshed {
+            self.publish_game(internal_id, game_edited);
+        }
+        Ok(skipped)
+    }
+
+    // -- games the IGDB update job should refresh, per the configured policy
+    pub fn games_to_refresh(&self, now: chrono::naive::NaiveDateTime) -> Vec<&SCollectionGame> {
+        let policy = &self.config.igdb_refresh;
+        self.repository.games().iter()
+            .filter(|game| {
+                let has_sessions = self.repository.sessions_for_game(game.internal_id).next().is_some();
+                policy.should_refresh(game, has_sessions, now)
+            })
+            .collect()
+    }
+
+    // -- saves a single game refresh the user confirmed, returning the game as stored
+    pub fn refresh_game_info(&mut self, request: &SIGDBRefreshRequest) -> Result<SCollectionGame, EError> {
+        let game = self.check_game_revision(request.internal_id, request.expected_revision)?;
+        if game.game_info.igdb_id().is_none() || game.game_info.igdb_id() != request.game_info.igdb_id() {
+            return Err(EError::BadRequest(format!("\"{}\" can only be refreshed from its own IGDB entry", game.game_info.title())));
+        }
+
+        let mut game_info = request.game_info.clone();
+        game_info.set_last_refreshed(chrono::offset::Local::now().naive_local());
+        self.update_game_and_save(request.internal_id, game_edited, |game| game.game_info = game_info)?;
+        Ok(self.game(request.internal_id)?.clone())
+    }
+
+    // -- unarchived games with a known release date of date
+    pub fn games_released_on(&self, date: chrono::naive::NaiveDate) -> Vec<&SCollectionGame> {
+        self.repository.games().iter()
+            .filter(|game| !game.custom_info.archived)
+            .filter(|game| matches!(game.release_date(&self.config), EReleaseDate::Known(d) if d == date))
+            .collect()
+    }
+
+    pub fn check_integrity(&self, today: chrono::naive::NaiveDate) -> Vec<String> {
+        integrity::check_integrity(self.repository.database(), today)
+    }
+
+    pub fn recent_games(&self, count: usize) -> Vec<SCollectionGame> {
+        self.repository.games().iter().rev().take(count).cloned().collect()
+    }
+
+    fn filter_expression(filter: EGameFilterRequest) -> Result<EGameFilterExpr, EError> {
+        filter.into_expression().map_err(|e| EError::invalid_field("query", e.to_string().as_str()))
+    }
+
+    fn filtered_games(&self, filter_expr: &EGameFilterExpr, session_states: &HashMap<u32, SGameSessionState>) -> Vec<&SCollectionGame> {
+        let no_sessions = SGameSessionState::default();
+
+        let mut games = Vec::with_capacity(self.repository.games().len());
+        for game in self.repository.games() {
+            let session_state = session_states.get(&game.internal_id).unwrap_or(&no_sessions);
+            if filter_expr.game_passes(&self.config, game, session_state) {
+                games.push(game);
+            }
+        }
+        games
+    }
+
+    pub fn get_games(&self, request: SGetGamesRequest) -> Result<SPage<SCollectionGame>, EError> {
+        let filter_expr = Self::filter_expression(request.filter)?;
+
+        let session_states = SGameSessionState::build_map(self.repository.sessions());
+        let mut games = self.filtered_games(&filter_expr, &session_states);
+
+        // -- sort and page before cloning, so only the returned games get copied
+        request.sort.sort_games(&self.config, &mut games, &session_states);
+
+        Ok(SPage::from_refs(&request.page, &games).revision(self.repository.revision()))
+    }
+
+    pub fn explain_filter(&self, internal_id: u32, filter: EGameFilterRequest) -> Result<SFilterExplanation, EError> {
+        let filter_expr = Self::filter_expression(filter)?;
+        let game = self.game(internal_id)?;
+        let session_state = self.repository.session_state(internal_id);
+
+        Ok(filter_expr.explain(&self.config, game, &session_state))
+    }
+
+    // -- genres, themes and game modes of the games that aren't archived
+    pub fn igdb_facet_options(&self) -> Vec<SIGDBFacetOptions> {
+        SIGDBFacetOptions::collect(self.repository.games().iter().filter(|g| !g.custom_info.archived))
+    }
+
+    // -- choose state
+
+    pub fn pass_game(&mut self, internal_id: u32) -> Result<(), EError> {
+        self.update_game_and_save(internal_id, choose_state_changed, |game| game.choose_state.pass())
+    }
+
+    pub fn push_game(&mut self, internal_id: u32) -> Result<(), EError> {
+        self.update_game_and_save(internal_id, choose_state_changed, |game| game.choose_state.push())
+    }
+
+    pub fn retire_game(&mut self, internal_id: u32) -> Result<(), EError> {
+        self.update_game_and_save(internal_id, choose_state_changed, |game| game.choose_state.retire())
+    }
+
+    pub fn reset_choose_state(&mut self, internal_id: u32) -> Result<(), EError> {
+        self.update_game_and_save(internal_id, choose_state_changed, |game| game.choose_state.reset())
+    }
+
+    // -- patches
+
+    // -- None if the patch wouldn't change anything, so no-op patches neither bump the revision nor publish
+    fn patched_game(&self, internal_id: u32, expected_revision: Option<u32>, patch: &SGamePatch) -> Result<Option<SCollectionGame>, EError> {
+        let existing = self.check_game_revision(internal_id, expected_revision)?;
+        let mut game = existing.clone();
+        patch.apply(&mut game);
+
+        if SGamePatch::diff(existing, &game).is_empty() {
+            return Ok(None);
+        }
+
+        let today = chrono::offset::Local::now().naive_local().date();
+        validation::validate_game(&game, today)?;
+
+        Ok(Some(game))
+    }
+
+    // -- returns the game as stored after the patch
+    pub fn patch_game(&mut self, request: &SGamePatchRequest) -> Result<SCollectionGame, EError> {
+        if let Some(game) = self.patched_game(request.internal_id, request.expected_revision, &request.patch)? {
+            self.repository.replace_game(game);
+            self.save()?;
+            self.publish_game(request.internal_id, patch_event(&request.patch));
+        }
+
+        Ok(self.game(request.internal_id)?.clone())
+    }
+
+    // -- nothing is changed if any patch is for a missing game, has a stale revision or fails validation
+    pub fn patch_games(&mut self, requests: &[SGamePatchRequest]) -> Result<(), EError> {
+        let mut changed = Vec::with_capacity(requests.len());
+        for request in requests {
+            if let Some(game) = self.patched_game(request.internal_id, request.expected_revision, &request.patch)? {
+                changed.push((request, game));
+            }
+        }
+
+        if changed.is_empty() {
+            return Ok(());
+        }
+
+        for (_, game) in &changed {
+            self.repository.replace_game(game.clone());
+        }
+
+        self.save()?;
+        for (request, _) in changed {
+            self.publish_game(request.internal_id, patch_event(&request.patch));
+        }
+        Ok(())
+    }
+
+    // -- applies the same edit to every targeted game, returning their IDs. Nothing is changed if any ID is
+    // -- missing or any edited game fails validation.
+    pub fn bulk_edit(&mut self, request: SBulkEditRequest) -> Result<Vec<u32>, EError> {
+        if request.edit.is_empty() {
+            return Err(EError::BadRequest(String::from("Bulk edit has no changes")));
+        }
+        request.edit.validate()?;
+
+        let targets : Vec<&SCollectionGame> = match request.target {
+            EBulkEditTarget::Games(ids) => {
+                let mut games = Vec::with_capacity(ids.len());
+                for internal_id in ids {
+                    games.push(self.game(internal_id)?);
+                }
+                games
+            },
+            EBulkEditTarget::Filter(filter) => {
+                let filter_expr = Self::filter_expression(filter)?;
+                let session_states = SGameSessionState::build_map(self.repository.sessions());
+                self.filtered_games(&filter_expr, &session_states)
+            },
+        };
+
+        let today = chrono::offset::Local::now().naive_local().date();
+        let mut games = Vec::with_capacity(targets.len());
+        for target in targets {
+            let mut game = target.clone();
+            request.edit.apply(&mut game);
+            validation::validate_game(&game, today)?;
+            games.push(game);
+        }
+
+        let internal_ids : Vec<u32> = games.iter().map(|g| g.internal_id).collect();
+        if internal_ids.is_empty() {
+            return Ok(internal_ids);
+        }
+
+        for game in games {
+            self.repository.replace_game(game);
+        }
+
+        self.save()?;
+        let event = if request.edit.choose_state_only() { choose_state_changed } else { game_edited };
+        for internal_id in &internal_ids {
+            self.publish_game(*internal_id, event);
+        }
+        Ok(internal_ids)
+    }
+
+    // -- sessions
+
+    // -- a game can only have one ongoing session at a time
+    pub fn start_session(&mut self, game_internal_id: u32) -> Result<u32, EError> {
+        if self.repository.ongoing_session_for_game(game_internal_id).is_some() {
+            return Err(EError::Conflict(format!("There is already a session started for the game with ID {}", game_internal_id)));
+        }
+
+        let session_id = self.repository.add_session(game_internal_id).ok_or_else(|| EError::game_not_found(game_internal_id))?;
+        self.save()?;
+        self.publish_session(session_id, |session| EChangeEvent::SessionStarted { session });
+
+        Ok(session_id)
+    }
+
+    // -- finishing always pushes the game back, since it was just played
+    pub fn finish_session(&mut self, session_internal_id: u32, expected_revision: Option<u32>, memorable: bool, retire: bool, set_ignore_passes: bool) -> Result<(), EError> {
+        let session = self.repository.session(session_internal_id)
+            .ok_or_else(|| EError::session_not_found(session_internal_id))?;
+        if let Some(expected) = expected_revision {
+            if expected != session.revision {
+                return Err(EError::Conflict(format!(
+                    "Session {} was changed since it was loaded (revision {}, expected {}), reload it and try again",
+                    session_internal_id, session.revision, expected)));
+            }
+        }
+        let game_internal_id = session.game_internal_id;
+        self.game(game_internal_id)?;
+
+        self.repository.update_session(session_internal_id, |s| s.finish(memorable));
+        self.repository.update_game(game_internal_id, |game| {
+            if retire {
+                game.choose_state.retire();
+            }
+            if set_ignore_passes {
+                game.choose_state.set_ignore_passes();
+            }
+            game.choose_state.push();
+        });
+
+        self.save()?;
+        self.publish_session(session_internal_id, |session| EChangeEvent::SessionFinished { session });
+        self.publish_game(game_internal_id, choose_state_changed);
+        Ok(())
+    }
+
+    fn publish_session(&self, internal_id: u32, event: fn(SSession) -> EChangeEvent) {
+        if let Some(session) = self.repository.session(internal_id) {
+            self.publish(event(*session));
+        }
+    }
+
+    pub fn get_sessions(&self, request: SGetSessionsRequest) -> Result<SPage<SSessionAndCollectionGame>, EError> {
+        let mut result = Vec::with_capacity(10);
+
+        for session in self.repository.sessions() {
+            if request.filter.session_passes(session) {
+                let game = self.repository.game(session.game_internal_id)
+                    .ok_or_else(|| EError::Internal(format!("Database has bad data: session {} has no game in the collection", session.internal_id)))?;
+
+                result.push(SSessionAndCollectionGame{
+                    session: *session,
+                    collection_game: game.clone(),
+                });
+            }
+        }
+
+        let session_states = SGameSessionState::build_map(self.repository.sessions());
+        request.sort.sort_sessions(&self.config, &mut result, &session_states);
+
+        let total = result.len();
+        let range = request.page.range(total);
+        let offset = range.start;
+        let items = result.drain(range).collect();
+
+        Ok(SPage{
+            items,
+            offset,
+            total,
+            revision: self.repository.revision(),
+        })
+    }
+
+    // -- filter presets
+
+    pub fn add_filter_preset(&mut self, preset: SFilterPreset) -> Result<(), EError> {
+        if preset.name.trim().is_empty() {
+            return Err(EError::invalid_field("name", "Filter preset name cannot be empty"));
+        }
+        if self.repository.filter_presets().iter().any(|p| p.name == preset.name) {
+            return Err(EError::Conflict(format!("Filter preset {} already exists", preset.name)));
+        }
+
+        self.repository.filter_presets_mut().push(preset);
+        self.save_presets()
+    }
+
+    pub fn update_filter_preset(&mut self, preset: SFilterPreset) -> Result<(), EError> {
+        match self.repository.filter_presets_mut().iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => return Err(EError::filter_preset_not_found(preset.name.as_str())),
+        }
+        self.save_presets()
+    }
+
+    pub fn delete_filter_preset(&mut self, name: &str) -> Result<(), EError> {
+        let presets = self.repository.filter_presets_mut();
+        let count_before = presets.len();
+        presets.retain(|p| p.name != name);
+        if presets.len() == count_before {
+            return Err(EError::filter_preset_not_found(name));
+        }
+        self.save_presets()
+    }
+
+    fn save_presets(&mut self) -> Result<(), EError> {
+        self.save()?;
+        self.publish(EChangeEvent::FilterPresetsChanged);
+        Ok(())
+    }
+
+    // -- stats
+
+    pub fn simple_stats(&self) -> SSimpleStats {
+        let mut stats = SSimpleStats::default();
+
+        let today = chrono::offset::Local::now().naive_local().date();
+
+        fn inc(stat: &mut u32) {
+            *stat += 1;
+        }
+
+        let selectable_filter = SCollectionGameFilter::new()
+            .require_released(true)
+            .require_alive(true)
+            .require_is_after_valid_date();
+
+        for game in self.repository.games() {
+            inc(&mut stats.total_collection_size);
+
+            let selectable = selectable_filter.game_passes(&self.config, game);
+
+            if selectable {
+                inc(&mut stats.collection_selectable);
+            }
+
+            if game.released(&self.config) {
+                inc(&mut stats.collection_released);
+            }
+
+            if game.choose_state.retired {
+                inc(&mut stats.collection_retired);
+            }
+            else if game.choose_state.passes > self.config.live_max_passes {
+                inc(&mut stats.collection_passed_many_times);
+            }
+            else if game.choose_state.next_valid_proposal_date > today {
+                inc(&mut stats.collection_cooldown);
+            }
+
+            if game.custom_info.own.owned() {
+                inc(&mut stats.collection_owned);
+
+                if selectable {
+                    inc(&mut stats.selectable_owned);
+                }
+            }
+
+            if self.repository.sessions_for_game(game.internal_id).next().is_some() {
+                inc(&mut stats.collection_played_before);
+
+                if selectable {
+                    inc(&mut stats.selectable_played_before);
+                }
+            }
+
+            if game.custom_info.tags.couch_playable {
+                inc(&mut stats.collection_couch_playable_tag);
+
+                if selectable {
+                    inc(&mut stats.selectable_couch_playable_tag);
+                }
+            }
+
+            if game.custom_info.tags.japanese_practice {
+                inc(&mut stats.collection_japanese_practice_tag);
+
+                if selectable {
+                    inc(&mut stats.selectable_japanese_practice_tag);
+                }
+            }
+
+            if game.custom_info.tags.portable_playable {
+                inc(&mut stats.collection_portable_playable_tag);
+
+                if selectable {
+                    inc(&mut stats.selectable_portable_playable_tag);
+                }
+            }
+
+            if game.custom_info.tags.retro {
+                inc(&mut stats.collection_retro_tag);
+
+                if selectable {
+                    inc(&mut stats.selectable_retro_tag);
+                }
+            }
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EGameInfo, EReleaseDate, ESessionState, SSessionFilter};
+    use crate::game_list::SPageRequest;
+    use crate::test_games::custom_add_game;
+
+    #[derive(Default)]
+    struct STestStorage {
+        saves: u32,
+        fail: bool,
+    }
+
+    impl TCollectionStorage for STestStorage {
+        fn save(&mut self, _config: &SConfig, _db: &EDatabase) -> Result<(), String> {
+            if self.fail {
+                return Err(String::from("disk full"));
+            }
+            self.saves += 1;
+            Ok(())
+        }
+    }
+
+    fn test_service() -> SCollectionService<STestStorage> {
+        SCollectionService::new(SConfig::default(), EDatabase::new(), STestStorage::default())
+    }
+
+    #[test]
+    fn one_ongoing_session_per_game() {
+        let mut service = test_service();
+        let game_id = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+
+        let session_id = service.start_session(game_id).unwrap();
+        assert!(matches!(service.start_session(game_id), Err(EError::Conflict(_))));
+
+        service.finish_session(session_id, None, false, false, false).unwrap();
+        assert!(service.start_session(game_id).is_ok());
+
+        assert_eq!(service.start_session(1234), Err(EError::game_not_found(1234)));
+    }
+
+    #[test]
+    fn finishing_pushes_and_retires() {
+        let mut service = test_service();
+        let game_id = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+        let session_id = service.start_session(game_id).unwrap();
+
+        service.finish_session(session_id, None, true, true, false).unwrap();
+
+        let game = service.repository().game(game_id).unwrap();
+        assert_eq!(game.choose_state.pushes, 1);
+        assert!(game.choose_state.retired);
+        assert!(matches!(service.repository().session(session_id).unwrap().state, ESessionState::Finished{memorable: true, ..}));
+
+        assert_eq!(service.finish_session(99, None, false, false, false), Err(EError::session_not_found(99)));
+    }
+
+    #[test]
+    fn every_change_is_saved() {
+        let mut service = test_service();
+        let game_id = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+        service.pass_game(game_id).unwrap();
+        service.start_session(game_id).unwrap();
+        assert_eq!(service.storage().saves, 3);
+
+        // -- lookups that fail don't save
+        assert!(service.pass_game(99).is_err());
+        assert_eq!(service.storage().saves, 3);
+
+        service.storage.fail = true;
+        assert!(matches!(service.retire_game(game_id), Err(EError::Storage(_))));
+    }
+
+    #[test]
+    fn patch_games_is_all_or_nothing() {
+        let mut service = test_service();
+        let game_id = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+
+        let mut choose_state = service.repository().game(game_id).unwrap().choose_state;
+        choose_state.pass();
+        let patch = SGamePatch::new().choose_state(choose_state);
+
+        let requests = [
+            SGamePatchRequest{ internal_id: game_id, expected_revision: None, patch: patch.clone() },
+            SGamePatchRequest{ internal_id: 99, expected_revision: None, patch: patch.clone() },
+        ];
+        assert_eq!(service.patch_games(&requests), Err(EError::game_not_found(99)));
+        assert_eq!(service.repository().game(game_id).unwrap().choose_state.passes, 0);
+
+        let invalid = [SGamePatchRequest{ internal_id: game_id, expected_revision: None, patch: patch.clone().title("") }];
+        assert!(matches!(service.patch_games(&invalid), Err(EError::Validation(_))));
+
+        service.patch_games(&requests[..1]).unwrap();
+        assert_eq!(service.repository().game(game_id).unwrap().choose_state.passes, 1);
+    }
+
+    #[test]
+    fn no_op_patches_change_nothing() {
+        let mut service = test_service();
+        let game_id = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+        let game = service.repository().game(game_id).unwrap().clone();
+        let saves = service.storage().saves;
+
+        let no_op = SGamePatchRequest{ internal_id: game_id, expected_revision: Some(game.revision), patch: SGamePatch::new().choose_state(game.choose_state) };
+        service.patch_games(std::slice::from_ref(&no_op)).unwrap();
+        assert_eq!(service.patch_game(&no_op).unwrap().revision, game.revision);
+        assert_eq!(service.repository().game(game_id).unwrap().revision, game.revision);
+        assert_eq!(service.storage().saves, saves);
+
+        let mut choose_state = game.choose_state;
+        choose_state.pass();
+        let pass = SGamePatchRequest{ internal_id: game_id, expected_revision: Some(game.revision), patch: SGamePatch::new().choose_state(choose_state) };
+        service.patch_games(&[no_op, pass]).unwrap();
+        assert_eq!(service.repository().game(game_id).unwrap().revision, game.revision + 1);
+        assert_eq!(service.storage().saves, saves + 1);
+    }
+
+    #[test]
+    fn bulk_edit_is_all_or_nothing() {
+        use crate::bulk_edit::{EBulkEditTarget, SBulkEdit, SBulkEditRequest};
+        use crate::EGameTag;
+
+        let mut service = test_service();
+        let a = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+        let b = service.add_game(custom_add_game("Hades", EReleaseDate::UnknownReleased)).unwrap();
+
+        let edit = SBulkEdit::new().tag(EGameTag::PortablePlayable, true).own("humble", true);
+        let bad = SBulkEditRequest{ target: EBulkEditTarget::Games(vec![a, 99]), edit: edit.clone() };
+        assert_eq!(service.bulk_edit(bad), Err(EError::game_not_found(99)));
+        assert!(!service.repository().game(a).unwrap().custom_info.own.humble);
+
+        let by_filter = SBulkEditRequest{ target: EBulkEditTarget::Filter(EGameFilterRequest::Query(String::from("hades"))), edit };
+        assert_eq!(service.bulk_edit(by_filter).unwrap(), vec![b]);
+        let game = service.repository().game(b).unwrap();
+        assert!(game.custom_info.own.humble && game.custom_info.tags.portable_playable);
+        assert!(!service.repository().game(a).unwrap().custom_info.own.humble);
+    }
+
+    #[test]
+    fn igdb_refresh_batches_save_once() {
+        let mut service = test_service();
+        let celeste = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+        let hades = service.add_game(custom_add_game("Hades", EReleaseDate::UnknownReleased)).unwrap();
+        let saves = service.storage().saves;
+
+        let request = |internal_id: u32, revision: u32, title: &str| SIGDBRefreshRequest {
+            internal_id,
+            expected_revision: Some(revision),
+            game_info: EGameInfo::new_igdb(internal_id, "slug", None, title, EReleaseDate::UnknownReleased),
+        };
+
+        // -- hades was edited after the refresh read it, so only celeste is refreshed
+        service.patch_game(&SGamePatchRequest{ internal_id: hades, expected_revision: None, patch: SGamePatch::new().archived(true) }).unwrap();
+        let skipped = service.refresh_games_info(vec![request(celeste, 0, "Celeste (IGDB)"), request(hades, 0, "Hades (IGDB)")]).unwrap();
+
+        assert_eq!(skipped.len(), 1);
+        assert!(matches!(skipped[0], (id, EError::Conflict(_)) if id == hades));
+        assert_eq!(service.storage().saves, saves + 2);
+        assert_eq!(service.repository().game(celeste).unwrap().game_info.title(), "Celeste (IGDB)");
+        assert_eq!(service.repository().game(hades).unwrap().game_info.title(), "Hades");
+    }
+
+    #[test]
+    fn stale_revisions_conflict() {
+        let mut service = test_service();
+        let game_id = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+        let phone_copy = service.repository().game(game_id).unwrap().clone();
+
+        let mut pc_copy = phone_copy.clone();
+        pc_copy.custom_info.via = String::from("bundle");
+        service.edit_game(pc_copy).unwrap();
+
+        // -- the phone's copy is now stale, for both full edits and patches
+        assert!(matches!(service.edit_game(phone_copy.clone()), Err(EError::Conflict(_))));
+        let request = SGamePatchRequest{
+            internal_id: game_id,
+            expected_revision: Some(phone_copy.revision),
+            patch: SGamePatch::new().archived(true),
+        };
+        assert!(matches!(service.patch_game(&request), Err(EError::Conflict(_))));
+
+        let request = SGamePatchRequest{ expected_revision: Some(phone_copy.revision + 1), ..request };
+        assert_eq!(service.patch_game(&request).unwrap().revision, phone_copy.revision + 2);
+
+        let session_id = service.start_session(game_id).unwrap();
+        assert!(matches!(service.finish_session(session_id, Some(3), false, false, false), Err(EError::Conflict(_))));
+        service.finish_session(session_id, Some(0), false, false, false).unwrap();
+    }
+
+    #[test]
+    fn changes_are_published_after_save() {
+        use std::sync::{Arc, Mutex};
+
+        let mut service = test_service();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let listener_events = events.clone();
+        service.set_change_listener(Box::new(move |e: &EChangeEvent| {
+            let kind = serde_json::to_value(e).unwrap()["kind"].as_str().unwrap().to_string();
+            listener_events.lock().unwrap().push(kind);
+        }));
+
+        let game_id = service.add_game(custom_add_game("Celeste", EReleaseDate::UnknownReleased)).unwrap();
+        let session_id = service.start_session(game_id).unwrap();
+        service.finish_session(session_id, None, false, false, false).unwrap();
+
+        service.storage.fail = true;
+        assert!(service.pass_game(game_id).is_err());
+
+        assert_eq!(*events.lock().unwrap(), vec!["game_added", "session_started", "session_finished", "choose_state_changed"]);
+    }
+
+    #[test]
+    fn sessions_and_stats() {
+        let mut service = test_service();
+        let played = service.add_game(custom_add_game("Played", EReleaseDate::UnknownReleased)).unwrap();
+        service.add_game(custom_add_game("Unplayed", EReleaseDate::UnknownReleased)).unwrap();
+        service.start_session(played).unwrap();
+
+        let request = SGetSessionsRequest {
+            filter: SSessionFilter{ game_id: None, active_only: true, memorable_only: false, year: None },
+            sort: Default::default(),
+            page: SPageRequest::default(),
+        };
+        let page = service.get_sessions(request).unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.items[0].collection_game.internal_id, played);
+
+        let stats = service.simple_stats();
+        assert_eq!(stats.total_collection_size, 2);
+        assert_eq!(stats.collection_played_before, 1);
+    }
+}
@@ -0,0 +1,16 @@
+// -- game factories shared by the unit tests
+
+use crate::{EGameInfo, EReleaseDate, SAddCollectionGame, SCollectionGame};
+
+pub fn collection_game(game_info: EGameInfo, internal_id: u32) -> SCollectionGame {
+    SCollectionGame::new(SAddCollectionGame::new(game_info), internal_id)
+}
+
+pub fn custom_add_game(title: &str, release_date: EReleaseDate) -> SAddCollectionGame {
+    SAddCollectionGame::new(EGameInfo::new_custom(String::from(title), release_date))
+}
+
+// -- released, but we don't know when
+pub fn custom_game(title: &str, internal_id: u32) -> SCollectionGame {
+    SCollectionGame::new(custom_add_game(title, EReleaseDate::UnknownReleased), internal_id)
+}
@@ -0,0 +1,102 @@
+use chrono::naive::NaiveDate;
+
+use crate::{EHowLongToBeat, SAddCollectionGame, SCollectionGame};
+use crate::error::{EError, SFieldError};
+
+// -- matches the max on the edit screen's hours input
+pub const MAX_HOW_LONG_TO_BEAT_HOURS: u16 = 9999;
+
+// -- field names used in SFieldError, so clients can map errors back to inputs
+pub const FIELD_TITLE: &str = "title";
+pub const FIELD_HOW_LONG_TO_BEAT: &str = "how_long_to_beat";
+pub const FIELD_NEXT_VALID_PROPOSAL_DATE: &str = "next_valid_proposal_date";
+
+fn validate_title(title: &str, errors: &mut Vec<SFieldError>) {
+    if title.trim().is_empty() {
+        errors.push(SFieldError::new(FIELD_TITLE, "Title cannot be empty"));
+    }
+}
+
+fn validate_how_long_to_beat(how_long_to_beat: &EHowLongToBeat, errors: &mut Vec<SFieldError>) {
+    if let EHowLongToBeat::Manual(hours) = how_long_to_beat {
+        if *hours == 0 {
+            errors.push(SFieldError::new(FIELD_HOW_LONG_TO_BEAT, "Manual hours must be at least 1, use Unknown if there is no data"));
+        }
+        else if *hours > MAX_HOW_LONG_TO_BEAT_HOURS {
+            let msg = format!("Manual hours cannot be more than {}", MAX_HOW_LONG_TO_BEAT_HOURS);
+            errors.push(SFieldError::new(FIELD_HOW_LONG_TO_BEAT, msg.as_str()));
+        }
+    }
+}
+
+fn into_result(errors: Vec<SFieldError>) -> Result<(), EError> {
+    if errors.is_empty() {
+        Ok(())
+    }
+    else {
+        Err(EError::Validation(errors))
+    }
+}
+
+pub fn validate_add_game(game: &SAddCollectionGame) -> Result<(), EError> {
+    let mut errors = Vec::new();
+    validate_title(game.game_info.title(), &mut errors);
+    validate_how_long_to_beat(&game.how_long_to_beat, &mut errors);
+    into_result(errors)
+}
+
+// -- does not check that internal_id exists, that is up to whoever stores the game
+pub fn validate_game(game: &SCollectionGame, today: NaiveDate) -> Result<(), EError> {
+    let mut errors = Vec::new();
+    validate_title(game.game_info.title(), &mut errors);
+    validate_how_long_to_beat(&game.how_long_to_beat, &mut errors);
+
+    let latest = game.choose_state.latest_valid_proposal_date(today);
+    if game.choose_state.next_valid_proposal_date > latest {
+        let msg = format!("Next valid choose date cannot be after {} with {} passes", latest, game.choose_state.passes);
+        errors.push(SFieldError::new(FIELD_NEXT_VALID_PROPOSAL_DATE, msg.as_str()));
+    }
+
+    into_result(errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::EReleaseDate;
+    use crate::test_games::custom_add_game;
+
+    fn field_names(result: Result<(), EError>) -> Vec<String> {
+        match result {
+            Err(EError::Validation(fields)) => fields.into_iter().map(|f| f.field).collect(),
+            other => panic!("expected validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn add_game_rules() {
+        let mut game = custom_add_game("Hades", EReleaseDate::UnknownReleased);
+        assert!(validate_add_game(&game).is_ok());
+
+        game.game_info.set_title("  ");
+        game.how_long_to_beat = EHowLongToBeat::Manual(0);
+        assert_eq!(field_names(validate_add_game(&game)), vec![FIELD_TITLE, FIELD_HOW_LONG_TO_BEAT]);
+    }
+
+    #[test]
+    fn far_future_proposal_date() {
+        let today = NaiveDate::from_ymd(2022, 1, 1);
+        let add = custom_add_game("Hades", EReleaseDate::UnknownReleased);
+        let mut game = SCollectionGame::new(add, 1);
+
+        game.choose_state.next_valid_proposal_date = NaiveDate::from_ymd(2022, 6, 1);
+        assert!(validate_game(&game, today).is_ok());
+
+        game.choose_state.next_valid_proposal_date = NaiveDate::from_ymd(2030, 1, 1);
+        assert_eq!(field_names(validate_game(&game, today)), vec![FIELD_NEXT_VALID_PROPOSAL_DATE]);
+
+        // -- lots of passes legitimately push the date out further
+        game.choose_state.passes = 100;
+        assert!(validate_game(&game, today).is_ok());
+    }
+}
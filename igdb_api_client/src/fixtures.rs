@@ -0,0 +1,94 @@
+use async_trait::async_trait;
+
+use gamechooser_core as core;
+
+use crate::{SGamesInfo, SIGDBGameResult, TMetadataProvider, unique_ids};
+
+// -- IGDB's default page size, which is all a search gets
+const SEARCH_LIMIT: usize = 10;
+
+// -- serves games from recorded IGDB JSON instead of the network. The file is a JSON array of game
+// -- objects as returned by the games endpoint for GAME_FIELDS.
+pub struct SFixtureMetadataProvider {
+    games: Vec<SIGDBGameResult>,
+}
+
+impl SFixtureMetadataProvider {
+    pub fn from_json(json: &str) -> Result<Self, core::EError> {
+        let games = serde_json::from_str(json)
+            .map_err(|e| core::EError::Internal(format!("Could not parse IGDB fixtures: {}", e)))?;
+        Ok(Self { games })
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, core::EError> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| core::EError::Internal(format!("Could not read IGDB fixtures from {}: {}", path, e)))?;
+        Self::from_json(json.as_str())
+    }
+
+    // -- a case insensitive substring match standing in for IGDB's search
+    pub(crate) fn search_results(&self, name: &str, games_only: bool) -> Vec<SIGDBGameResult> {
+        let name = name.to_lowercase();
+        self.games.iter()
+            .filter(|g| g.version_parent.is_none())
+            .filter(|g| !games_only || g.category.unwrap_or(0) == 0)
+            .filter(|g| g.name.to_lowercase().contains(name.as_str()))
+            .take(SEARCH_LIMIT)
+            .cloned()
+            .collect()
+    }
+
+    pub(crate) fn games_with_ids(&self, igdb_ids: &[u32]) -> Vec<SIGDBGameResult> {
+        self.games.iter()
+            .filter(|g| igdb_ids.contains(&g.id))
+            .cloned()
+            .collect()
+    }
+}
+
+#[async_trait]
+impl TMetadataProvider for SFixtureMetadataProvider {
+    async fn search(&self, name: &str, games_only: bool) -> Result<Vec<core::EGameInfo>, core::EError> {
+        Ok(self.search_results(name, games_only).into_iter().map(SIGDBGameResult::into_game_info).collect())
+    }
+
+    async fn get_games_info(&self, igdb_ids: &[u32]) -> Result<SGamesInfo, core::EError> {
+        let ids = unique_ids(igdb_ids);
+        let results = self.games_with_ids(&ids);
+        Ok(SGamesInfo::new(ids, results))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_follows_igdb_filters() {
+        let fixtures = SFixtureMetadataProvider::from_json(include_str!("../fixtures/games.json")).unwrap();
+
+        let all : Vec<u32> = fixtures.search_results("hollow knight", false).iter().map(|g| g.id).collect();
+        let games : Vec<u32> = fixtures.search_results("hollow knight", true).iter().map(|g| g.id).collect();
+        assert_eq!(all.len(), games.len() + 1);
+        assert!(games.contains(&14593));
+        assert!(fixtures.search_results("not a game", false).is_empty());
+
+        let info = fixtures.games_with_ids(&[26226]).pop().unwrap().into_game_info();
+        assert_eq!(info.title(), "Celeste");
+        let metadata = info.igdb_metadata().unwrap();
+        assert_eq!(metadata.genres, vec!["Platform", "Indie"]);
+        assert_eq!((metadata.developers.len(), metadata.publishers.len()), (1, 1));
+        assert_eq!(metadata.aggregated_rating, Some(92));
+        assert_eq!(metadata.websites[1].kind, "steam");
+        assert!(matches!(info.release_date(), core::EReleaseDate::Known(d) if d == chrono::NaiveDate::from_ymd(2018, 1, 25)));
+        let platforms : Vec<&str> = info.platform_release_dates().iter().map(|r| r.platform.as_str()).collect();
+        assert_eq!(platforms, vec!["Nintendo Switch", "PC (Microsoft Windows)"]);
+
+        // -- the early access date without a status loses to the full release on the same platform
+        let info = fixtures.games_with_ids(&[113112]).pop().unwrap().into_game_info();
+        let releases = info.platform_release_dates();
+        assert_eq!(releases.len(), 1);
+        assert_eq!(releases[0].region, core::WORLDWIDE_REGION);
+        assert_eq!(releases[0].date, core::EReleaseDate::Known(chrono::NaiveDate::from_ymd(2020, 9, 17)));
+    }
+}
@@ -1,33 +1,94 @@
-use std::error::Error;
+use std::collections::{BTreeMap, HashMap};
 use std::println;
 use std::result::Result;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use gamechooser_core as core;
 
-#[derive(Default, Serialize, Deserialize)]
+mod rate_limit;
+use rate_limit::SRateLimiter;
+
+mod fixtures;
+mod provider;
+mod response_cache;
+#[cfg(feature = "stand-in")]
+mod stand_in;
+pub use fixtures::SFixtureMetadataProvider;
+pub use provider::TMetadataProvider;
+pub use response_cache::{EQueryKind, EResponseCacheMode, SResponseCache, SResponseCacheConfig};
+#[cfg(feature = "stand-in")]
+pub use stand_in::SStandInServer;
+
+const DEFAULT_IGDB_BASE_URL: &str = "https://api.igdb.com/v4";
+const DEFAULT_TWITCH_BASE_URL: &str = "https://id.twitch.tv";
+
+#[derive(Serialize, Deserialize)]
 pub struct SConfigFile {
     twitch_client_id: String,
     twitch_client_secret: String,
+
+    // -- only changed to point at a stand-in server
+    #[serde(default = "default_igdb_base_url")]
+    igdb_base_url: String,
+    #[serde(default = "default_twitch_base_url")]
+    twitch_base_url: String,
+
+    #[serde(default)]
+    cache: SResponseCacheConfig,
+}
+
+fn default_igdb_base_url() -> String {
+    String::from(DEFAULT_IGDB_BASE_URL)
+}
+
+fn default_twitch_base_url() -> String {
+    String::from(DEFAULT_TWITCH_BASE_URL)
+}
+
+impl Default for SConfigFile {
+    fn default() -> Self {
+        Self {
+            twitch_client_id: String::new(),
+            twitch_client_secret: String::new(),
+            igdb_base_url: default_igdb_base_url(),
+            twitch_base_url: default_twitch_base_url(),
+            cache: SResponseCacheConfig::default(),
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Clone)]
+// -- no Debug on anything holding the secret or a token, so they can't end up in logs
+#[derive(Serialize, Clone)]
 struct STwitchOauthTokenRequest {
     pub client_id: String,
     pub client_secret: String,
     pub grant_type: &'static str,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Deserialize)]
 #[allow(dead_code)]
 struct STwitchOauthTokenResponse {
     access_token: String,
-    expires_in: u32,
+    expires_in: u64,
     token_type: String,
 }
 
+#[derive(Clone)]
+struct SCachedToken {
+    access_token: String,
+    expires_at: Instant,
+}
+
+// -- tokens are refreshed this long before Twitch says they expire
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+// -- responses recorded within this long of each other go into the cache file in one write
+const CACHE_SAVE_DELAY: Duration = Duration::from_secs(10);
+
 #[derive(Clone)]
 pub struct STwitchAPIRequestBuilder {
     pub url: String,
@@ -35,20 +96,53 @@ pub struct STwitchAPIRequestBuilder {
     pub body: Option<String>,
 }
 
+// -- meant to be kept for the life of the program. Clones share the cached token, which is fetched
+// -- when first needed and again shortly before it expires or when IGDB rejects it. They also share
+// -- the rate limiter, so IGDB's limits hold across everything using the session.
 #[derive(Clone)]
 pub struct SReqwestTwitchAPISession {
     client: reqwest::Client,
-    twitch_client_id: String,
-    token_info: Option<STwitchOauthTokenResponse>,
+    token_request: STwitchOauthTokenRequest,
+    token: Arc<Mutex<Option<SCachedToken>>>,
+    limiter: Arc<SRateLimiter>,
+    cache: Option<Arc<Mutex<SResponseCache>>>,
+    // -- held while writing the cache file, so saves land in the order they were taken
+    cache_writer: Arc<tokio::sync::Mutex<()>>,
+    igdb_base_url: String,
+    twitch_base_url: String,
 }
 
 pub struct SReqwestTwitchAPIClient {}
 
 impl SConfigFile {
+    pub fn new(twitch_client_id: &str, twitch_client_secret: &str) -> Self {
+        let mut result = Self::default();
+        result.set_twitch_client(twitch_client_id, twitch_client_secret);
+        result
+    }
+
+    // -- base URLs without a trailing slash, e.g. "https://api.igdb.com/v4" and "https://id.twitch.tv"
+    pub fn base_urls(mut self, igdb_base_url: &str, twitch_base_url: &str) -> Self {
+        self.igdb_base_url = igdb_base_url.trim_end_matches('/').to_string();
+        self.twitch_base_url = twitch_base_url.trim_end_matches('/').to_string();
+        self
+    }
+
     pub fn set_twitch_client(&mut self, id: &str, secret: &str) {
         self.twitch_client_id = id.to_string();
         self.twitch_client_secret = secret.to_string();
     }
+
+    pub fn cache_config(&self) -> &SResponseCacheConfig {
+        &self.cache
+    }
+
+    pub fn set_cache(&mut self, mode: EResponseCacheMode, path: Option<&str>) {
+        self.cache.mode = mode;
+        if let Some(path) = path {
+            self.cache.path = path.to_string();
+        }
+    }
 }
 
 impl STwitchAPIRequestBuilder {
@@ -76,31 +170,40 @@ impl STwitchAPIRequestBuilder {
     }
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 struct SIGDBInfoResultReleaseDateStatus {
     name: String,
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Clone, Serialize, Deserialize, Debug)]
 struct SIGDBInfoResultReleaseDate {
     date: Option<i64>, // unix timestamp
     status: Option<SIGDBInfoResultReleaseDateStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    platform: Option<SIGDBNamed>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    region: Option<u8>,
+}
+
+// -- None for timestamps chrono can't represent, which are treated like a missing date
+fn timestamp_date(timestamp: i64) -> Option<chrono::naive::NaiveDate> {
+    chrono::naive::NaiveDateTime::from_timestamp_opt(timestamp, 0).map(|dt| dt.date())
+}
+
+fn dated_timestamp(date: &SIGDBInfoResultReleaseDate) -> Option<(i64, chrono::naive::NaiveDate)> {
+    let timestamp = date.date?;
+    Some((timestamp, timestamp_date(timestamp)?))
 }
 
-fn best_release_date(game_status: Option<u8>, dates: Option<Vec<SIGDBInfoResultReleaseDate>>) -> core::EReleaseDate {
+fn best_release_date(game_status: Option<u8>, dates: &[SIGDBInfoResultReleaseDate]) -> core::EReleaseDate {
     let mut best_date = core::EReleaseDate::UnknownUnreleased;
     let mut earliest = i64::MAX;
 
-    let dates_inner = match dates {
-        Some(a) => a,
-        None => return best_date,
-    };
-
-    for date in &dates_inner {
-        match (date.date, &date.status) {
-            (Some(date_inner), Some(status_inner)) => {
+    for date in dates {
+        match (dated_timestamp(date), &date.status) {
+            (Some((date_inner, day)), Some(status_inner)) => {
                 if status_inner.name == "Full Release" && date_inner < earliest {
-                    best_date = core::EReleaseDate::Known(chrono::naive::NaiveDateTime::from_timestamp(date_inner, 0).date());
+                    best_date = core::EReleaseDate::Known(day);
                     earliest = date_inner;
                 }
             },
@@ -110,11 +213,11 @@ fn best_release_date(game_status: Option<u8>, dates: Option<Vec<SIGDBInfoResultR
 
     // -- fall back to supporting dates with unknown status if no Full Release was found, and status is 'released'
     if earliest == i64::MAX  && game_status.unwrap_or(0) == 0 /* 0 means 'released' */ {
-        for date in &dates_inner {
-            match (date.date, &date.status) {
-                (Some(date_inner), None) => {
+        for date in dates {
+            match (dated_timestamp(date), &date.status) {
+                (Some((date_inner, day)), None) => {
                     if date_inner < earliest {
-                        best_date = core::EReleaseDate::Known(chrono::naive::NaiveDateTime::from_timestamp(date_inner, 0).date());
+                        best_date = core::EReleaseDate::Known(day);
                         earliest = date_inner;
                     }
                 },
@@ -126,382 +229,619 @@ fn best_release_date(game_status: Option<u8>, dates: Option<Vec<SIGDBInfoResultR
     best_date
 }
 
-impl SReqwestTwitchAPIClient {
-    pub async fn new_session() -> Result<SReqwestTwitchAPISession, String> {
-        let cfg: SConfigFile = confy::load("gamechooser2_igdb_api_client").unwrap();
+fn region_name(region: Option<u8>) -> &'static str {
+    match region {
+        Some(1) => "Europe",
+        Some(2) => "North America",
+        Some(3) => "Australia",
+        Some(4) => "New Zealand",
+        Some(5) => "Japan",
+        Some(6) => "China",
+        Some(7) => "Asia",
+        Some(9) => "Korea",
+        Some(10) => "Brazil",
+        _ => core::WORLDWIDE_REGION,
+    }
+}
 
-        let params = STwitchOauthTokenRequest {
-            client_id: cfg.twitch_client_id,
-            client_secret: cfg.twitch_client_secret,
-            grant_type: "client_credentials",
+// -- the earliest date for each platform and region, picked with the same status rules as best_release_date
+fn platform_release_dates(game_status: Option<u8>, dates: &[SIGDBInfoResultReleaseDate]) -> Vec<core::SPlatformReleaseDate> {
+    let mut full_releases : BTreeMap<(String, &str), core::EReleaseDate> = BTreeMap::new();
+    let mut unknown_status : BTreeMap<(String, &str), core::EReleaseDate> = BTreeMap::new();
+
+    for date in dates {
+        let platform = match &date.platform {
+            Some(p) => p.name.clone(),
+            None => continue,
         };
 
-        Self::init(params).await
-    }
+        let releases = match &date.status {
+            Some(status) if status.name == "Full Release" => &mut full_releases,
+            Some(_) => continue,
+            None => &mut unknown_status,
+        };
 
-    fn prepare_request(
-        session: &SReqwestTwitchAPISession,
-        rb: STwitchAPIRequestBuilder,
-    ) -> reqwest::RequestBuilder {
-        let mut request = session.client.post(rb.url);
+        let release_date = match date.date.and_then(timestamp_date) {
+            Some(day) => core::EReleaseDate::Known(day),
+            None => core::EReleaseDate::UnknownUnreleased,
+        };
 
-        if let Some(b) = rb.body {
-            request = request.body(b);
+        let earliest = releases.entry((platform, region_name(date.region))).or_insert(core::EReleaseDate::UnknownUnreleased);
+        if release_date < *earliest {
+            *earliest = release_date;
         }
+    }
 
-        for (hname, hvalue) in rb.headers {
-            request = request.header(hname.as_str(), hvalue.as_str());
+    if game_status.unwrap_or(0) == 0 {
+        for (key, release_date) in unknown_status {
+            full_releases.entry(key).or_insert(release_date);
         }
+    }
 
-        request
+    full_releases.into_iter()
+        .map(|((platform, region), date)| core::SPlatformReleaseDate{ platform, region: region.to_string(), date })
+        .collect()
+}
+
+// -- IGDB caps results per query at 500, and the default limit is only 10
+pub const MAX_IDS_PER_QUERY: usize = 500;
+// -- and a multiquery can hold at most 10 queries
+const MAX_MULTIQUERY_QUERIES: usize = 10;
+
+const GAME_FIELDS: &str = "fields name,slug,release_dates.date,release_dates.status.name,release_dates.platform.name,\
+    release_dates.region,status,cover.image_id,\
+    summary,genres.name,themes.name,game_modes.name,platforms.name,franchises.name,collection.name,\
+    involved_companies.company.name,involved_companies.developer,involved_companies.publisher,\
+    aggregated_rating,screenshots.image_id,websites.url,websites.category;";
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct SIGDBGameResultCover {
+    id: u32,
+    image_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct SIGDBNamed {
+    name: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct SIGDBImage {
+    image_id: String,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct SIGDBInvolvedCompany {
+    company: SIGDBNamed,
+    #[serde(default)]
+    developer: bool,
+    #[serde(default)]
+    publisher: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct SIGDBWebsiteResult {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<u32>,
+    url: String,
+}
+
+// -- IGDB's website category enum
+fn website_kind(category: Option<u32>) -> &'static str {
+    match category {
+        Some(1) => "official",
+        Some(2) => "wikia",
+        Some(3) => "wikipedia",
+        Some(4) => "facebook",
+        Some(5) => "twitter",
+        Some(6) => "twitch",
+        Some(8) => "instagram",
+        Some(9) => "youtube",
+        Some(10) | Some(11) => "app store",
+        Some(12) => "google play",
+        Some(13) => "steam",
+        Some(14) => "reddit",
+        Some(15) => "itch.io",
+        Some(16) => "epic",
+        Some(17) => "gog",
+        Some(18) => "discord",
+        _ => "other",
     }
+}
 
-    async fn init(params: STwitchOauthTokenRequest) -> Result<SReqwestTwitchAPISession, String> {
-        let client = reqwest::Client::new();
+fn names(named: Option<Vec<SIGDBNamed>>) -> Vec<String> {
+    named.unwrap_or_default().into_iter().map(|n| n.name).collect()
+}
 
-        let res = client
-            .post("https://id.twitch.tv/oauth2/token")
-            .form(&params)
-            .send()
-            .await;
-
-        match res {
-            Ok(res_) => {
-                let resp: STwitchOauthTokenResponse = res_.json().await.unwrap();
-                println!("{:?}", resp);
-                Ok(SReqwestTwitchAPISession {
-                    client,
-                    twitch_client_id: params.client_id,
-                    token_info: Some(resp),
-                })
+// -- also the format of fixture files, which can add category and version_parent so search filtering
+// -- can be faked. IGDB itself only sends them when asked for.
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct SIGDBGameResult {
+    id: u32,
+    name: String,
+    slug: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    release_dates: Option<Vec<SIGDBInfoResultReleaseDate>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cover: Option<SIGDBGameResultCover>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    category: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version_parent: Option<u32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    summary: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    genres: Option<Vec<SIGDBNamed>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    themes: Option<Vec<SIGDBNamed>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    game_modes: Option<Vec<SIGDBNamed>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    platforms: Option<Vec<SIGDBNamed>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    franchises: Option<Vec<SIGDBNamed>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    collection: Option<SIGDBNamed>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    involved_companies: Option<Vec<SIGDBInvolvedCompany>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    aggregated_rating: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    screenshots: Option<Vec<SIGDBImage>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    websites: Option<Vec<SIGDBWebsiteResult>>,
+}
+
+impl SIGDBGameResult {
+    fn into_game_info(self) -> core::EGameInfo {
+        let companies = self.involved_companies.unwrap_or_default();
+        let company_names = |f: fn(&SIGDBInvolvedCompany) -> bool| -> Vec<String> {
+            companies.iter().filter(|c| f(c)).map(|c| c.company.name.clone()).collect()
+        };
+
+        let mut franchises = names(self.franchises);
+        if let Some(collection) = self.collection {
+            if !franchises.contains(&collection.name) {
+                franchises.push(collection.name);
             }
-            Err(e_) => Err(e_.to_string()),
         }
-    }
 
-    async fn post_interp_json<T: DeserializeOwned>(
-        session: SReqwestTwitchAPISession,
-        rb: STwitchAPIRequestBuilder,
-    ) -> Result<T, Box<dyn Error>> {
-        let req = Self::prepare_request(&session, rb);
-        let resp = req.send().await?;
-        Ok(resp.json().await?)
+        let metadata = core::SIGDBMetadata {
+            summary: self.summary,
+            genres: names(self.genres),
+            themes: names(self.themes),
+            game_modes: names(self.game_modes),
+            platforms: names(self.platforms),
+            franchises,
+            developers: company_names(|c| c.developer),
+            publishers: company_names(|c| c.publisher),
+            aggregated_rating: self.aggregated_rating.map(|r| r.round().clamp(0.0, 100.0) as u8),
+            screenshot_ids: self.screenshots.unwrap_or_default().into_iter().map(|s| s.image_id).collect(),
+            websites: self.websites.unwrap_or_default().into_iter()
+                .map(|w| core::SIGDBWebsite{ kind: website_kind(w.category).to_string(), url: w.url })
+                .collect(),
+        };
+
+        let release_dates = self.release_dates.unwrap_or_default();
+
+        core::EGameInfo::new_igdb(
+            self.id,
+            self.slug.as_str(),
+            self.cover.map(|c| c.image_id),
+            self.name.as_str(),
+            best_release_date(self.status, &release_dates),
+        )
+            .with_igdb_metadata(metadata)
+            .with_platform_release_dates(platform_release_dates(self.status, &release_dates))
     }
+}
 
-    #[allow(dead_code)]
-    async fn post_text(
-        session: SReqwestTwitchAPISession,
-        rb: STwitchAPIRequestBuilder,
-    ) -> Result<String, String> {
-        let req = Self::prepare_request(&session, rb);
-        let resp = req.send().await.unwrap();
-        Ok(resp.text().await.unwrap())
+// -- multiquery answers with one of these per query, in no guaranteed order
+#[derive(Serialize, Deserialize)]
+struct SIGDBMultiQueryResult {
+    name: String,
+    result: Vec<SIGDBGameResult>,
+}
+
+// -- result of get_games_info, IDs IGDB had nothing for end up in missing
+#[derive(Debug, Default)]
+pub struct SGamesInfo {
+    pub found: HashMap<u32, core::EGameInfo>,
+    pub missing: Vec<u32>,
+}
+
+impl SGamesInfo {
+    // -- ids must already be deduplicated
+    fn new(ids: Vec<u32>, results: Vec<SIGDBGameResult>) -> Self {
+        let mut result = Self::default();
+        for game in results {
+            result.found.insert(game.id, game.into_game_info());
+        }
+        result.missing = ids.into_iter().filter(|id| !result.found.contains_key(id)).collect();
+        result
     }
+}
+
+fn unique_ids(igdb_ids: &[u32]) -> Vec<u32> {
+    let mut ids = igdb_ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    ids
+}
 
-    fn access_token(session: &SReqwestTwitchAPISession) -> &str {
-        session.token_info.as_ref().unwrap().access_token.as_str()
+fn quote_query_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn search_where_clause(games_only: bool) -> &'static str {
+    if games_only {
+        "where category = 0 & version_parent = null;"
+    } else {
+        "where version_parent = null;"
     }
+}
 
-    pub async fn get_game_info(
-        session: &SReqwestTwitchAPISession,
-        igdb_id: u32,
-    ) -> Result<core::EGameInfo, String> {
-        #[derive(Deserialize, Debug)]
-        #[allow(dead_code)]
-        struct SIGDBInfoResultCover {
-            id: u32,
-            image_id: String,
-        }
-        #[derive(Deserialize, Debug)]
-        struct SIGDBInfoResult {
-            id: u32,
-            name: String,
-            slug: String,
-            release_dates: Option<Vec<SIGDBInfoResultReleaseDate>>,
-            status: Option<u8>,
-            cover: Option<SIGDBInfoResultCover>,
+impl SReqwestTwitchAPISession {
+    fn cached_token(&self) -> Option<String> {
+        let token = self.token.lock().expect("token lock poisoned");
+        match token.as_ref() {
+            Some(t) if Instant::now() + TOKEN_EXPIRY_MARGIN < t.expires_at => Some(t.access_token.clone()),
+            _ => None,
         }
+    }
 
-        let mut query_results: Vec<SIGDBInfoResult> = {
-            let where_clause = format!("where id = {};", igdb_id);
-            let body = format!(
-                "{}fields name,slug,release_dates.date,release_dates.status.name,status,cover.image_id;",
-                where_clause
-            );
+    fn invalidate_token(&self) {
+        *self.token.lock().expect("token lock poisoned") = None;
+    }
 
-            let request = STwitchAPIRequestBuilder::new()
-                .url("https://api.igdb.com/v4/games/")
-                .header("Client-ID", session.twitch_client_id.as_str())
-                .header(
-                    "Authorization",
-                    format!("Bearer {}", SReqwestTwitchAPIClient::access_token(&session)).as_str(),
-                )
-                .header("Accept", "application/json")
-                .body(body.as_str());
+    async fn access_token(&self) -> Result<String, core::EError> {
+        if let Some(token) = self.cached_token() {
+            return Ok(token);
+        }
 
-            match SReqwestTwitchAPIClient::post_interp_json::<Vec<SIGDBInfoResult>>(
-                session.clone(),
-                request,
-            )
+        let resp = self.client
+            .post(format!("{}/oauth2/token", self.twitch_base_url))
+            .form(&self.token_request)
+            .send()
             .await
-            {
-                Ok(res) => Ok(res),
-                Err(e) => Err(format!("Failed with error {:?}", e)),
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| core::EError::UpstreamIGDB(format!("Failed to get a Twitch token: {}", e)))?;
+
+        let token_info: STwitchOauthTokenResponse = resp.json().await
+            .map_err(|e| core::EError::UpstreamIGDB(format!("Failed to read the Twitch token response: {}", e)))?;
+
+        println!("Got a new Twitch token, expires in {}s", token_info.expires_in);
+        let cached = SCachedToken {
+            access_token: token_info.access_token,
+            expires_at: Instant::now() + Duration::from_secs(token_info.expires_in),
+        };
+        *self.token.lock().expect("token lock poisoned") = Some(cached.clone());
+
+        Ok(cached.access_token)
+    }
+
+    // -- writes out anything recorded since the last save, off the async threads. Saves also run on
+    // -- their own shortly after responses are recorded.
+    pub async fn save_cache(&self) -> Result<(), core::EError> {
+        if let Some(cache) = &self.cache {
+            let _writing = self.cache_writer.lock().await;
+            let snapshot = cache.lock().expect("cache lock poisoned").snapshot()?;
+            if let Some(snapshot) = snapshot {
+                tokio::task::spawn_blocking(move || snapshot.write()).await
+                    .map_err(|e| core::EError::Internal(format!("IGDB cache save failed: {}", e)))??;
             }
-        }?;
+        }
+
+        Ok(())
+    }
 
-        if query_results.len() < 1 {
-            return Err(format!("Got no results for IGDB game with ID {}", igdb_id));
+    fn record_in_cache(&self, record: impl FnOnce(&mut SResponseCache) -> Result<(), core::EError>) {
+        if let Some(cache) = &self.cache {
+            let schedule_save = {
+                let mut cache = cache.lock().expect("cache lock poisoned");
+                // -- a cache we can't write to shouldn't fail the request
+                if let Err(e) = record(&mut cache) {
+                    println!("Could not save IGDB response to the cache: {}", e);
+                }
+                cache.schedule_save()
+            };
+
+            if schedule_save {
+                let session = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(CACHE_SAVE_DELAY).await;
+                    if let Err(e) = session.save_cache().await {
+                        println!("Could not save the IGDB cache: {}", e);
+                    }
+                });
+            }
         }
+    }
+}
+
+impl SReqwestTwitchAPIClient {
+    pub async fn new_session() -> Result<SReqwestTwitchAPISession, core::EError> {
+        let cfg: SConfigFile = confy::load("gamechooser2_igdb_api_client")
+            .map_err(|e| core::EError::Internal(format!("Could not load the IGDB client config: {}", e)))?;
+
+        Self::new_session_with_config(cfg).await
+    }
 
-        if query_results.len() > 1 {
-            return Err(format!(
-                "Got more than one result for IGDB game with ID {}\n {:?}",
-                igdb_id, query_results
-            ));
+    pub async fn new_session_with_config(cfg: SConfigFile) -> Result<SReqwestTwitchAPISession, core::EError> {
+        let replay_only = cfg.cache.mode == EResponseCacheMode::ReplayOnly;
+        let cache = match cfg.cache.mode {
+            EResponseCacheMode::Off => None,
+            _ => Some(Arc::new(Mutex::new(SResponseCache::load(cfg.cache)?))),
+        };
+
+        if !replay_only && (cfg.twitch_client_id.is_empty() || cfg.twitch_client_secret.is_empty()) {
+            return Err(core::EError::Internal(String::from("No Twitch client ID/secret configured, set them with cli_client set_twitch_client")));
         }
 
-        assert!(query_results.len() == 1);
-        let query_result = query_results.pop().expect("assert");
+        let session = SReqwestTwitchAPISession {
+            client: reqwest::Client::new(),
+            token_request: STwitchOauthTokenRequest {
+                client_id: cfg.twitch_client_id,
+                client_secret: cfg.twitch_client_secret,
+                grant_type: "client_credentials",
+            },
+            token: Arc::new(Mutex::new(None)),
+            limiter: Arc::new(SRateLimiter::new(rate_limit::IGDB_REQUESTS_PER_SECOND, rate_limit::IGDB_MAX_OPEN_REQUESTS)),
+            cache,
+            cache_writer: Arc::new(tokio::sync::Mutex::new(())),
+            igdb_base_url: cfg.igdb_base_url,
+            twitch_base_url: cfg.twitch_base_url,
+        };
 
-        let result = core::EGameInfo::new_igdb(
-            query_result.id,
-            query_result.slug.as_str(),
-            query_result.cover.map(|c| c.image_id),
-            query_result.name.as_str(),
-            best_release_date(query_result.status, query_result.release_dates),
-        );
+        // -- fetch a token now so bad credentials show up straight away
+        if !replay_only {
+            session.access_token().await?;
+        }
 
-        Ok(result)
+        Ok(session)
     }
 
-    pub async fn search(
+    fn prepare_request(
         session: &SReqwestTwitchAPISession,
-        name: &str,
-        games_only: bool,
-    ) -> Result<Vec<core::EGameInfo>, String> {
-        #[derive(Deserialize, Debug)]
-        #[allow(dead_code)]
-        struct SIGDBSearchResultCover {
-            id: u32,
-            image_id: String,
+        rb: STwitchAPIRequestBuilder,
+        access_token: &str,
+    ) -> reqwest::RequestBuilder {
+        let mut request = session.client.post(rb.url)
+            .header("Client-ID", session.token_request.client_id.as_str())
+            .header("Authorization", format!("Bearer {}", access_token).as_str());
+
+        if let Some(b) = rb.body {
+            request = request.body(b);
         }
-        #[derive(Deserialize, Debug)]
-        struct SIGDBSearchResult {
-            id: u32,
-            name: String,
-            slug: String,
-            release_dates: Option<Vec<SIGDBInfoResultReleaseDate>>,
-            status: Option<u8>,
-            cover: Option<SIGDBSearchResultCover>,
+
+        for (hname, hvalue) in rb.headers {
+            request = request.header(hname.as_str(), hvalue.as_str());
         }
 
-        let search_results: Vec<SIGDBSearchResult> = {
-            let where_clause = if games_only {
-                "where category = 0 & version_parent = null;"
-            } else {
-                "where version_parent = null;"
-            };
-            let body = format!(
-                "search \"{}\"; {}fields name,slug,release_dates.date,release_dates.status.name,status,cover.image_id;",
-                name, where_clause
-            );
+        request
+    }
 
-            /*
-            Should be equivalent to:
-            curl -d "search \"halo\"; fields name,release_dates,cover.url;" -H 'Client-ID: <ID>' -H 'Authorization: Bearer <BEARER>' -H 'Accept: application/json' https://api.igdb.com/v4/games
-            */
+    // -- waits on the rate limiter, retries once with a new token if IGDB says the cached one is no
+    // -- good, and retries 429s, 5xxs and connection failures with backoff. The permit keeps the
+    // -- request counted as open until the caller has read the body.
+    async fn send(
+        session: &SReqwestTwitchAPISession,
+        rb: STwitchAPIRequestBuilder,
+    ) -> Result<(reqwest::Response, tokio::sync::SemaphorePermit<'_>), core::EError> {
+        let mut refreshed_token = false;
+        let mut attempt = 0;
+        loop {
+            let access_token = session.access_token().await?;
+            let permit = session.limiter.acquire().await;
+            let req = Self::prepare_request(session, rb.clone(), access_token.as_str());
+
+            let (retry_reason, rate_limited, retry_after) = match req.send().await {
+                Ok(resp) if resp.status() == reqwest::StatusCode::UNAUTHORIZED && !refreshed_token => {
+                    session.invalidate_token();
+                    refreshed_token = true;
+                    continue;
+                },
+                Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                    let retry_after = resp.headers().get(reqwest::header::RETRY_AFTER)
+                        .and_then(|v| v.to_str().ok())
+                        .and_then(|v| v.parse::<u64>().ok())
+                        .map(Duration::from_secs);
+                    (String::from("rate limited by IGDB"), true, retry_after)
+                },
+                Ok(resp) if resp.status().is_server_error() => (format!("IGDB returned {}", resp.status()), false, None),
+                Ok(resp) => {
+                    let resp = resp.error_for_status().map_err(|e| core::EError::UpstreamIGDB(format!("Failed with error {}", e)))?;
+                    return Ok((resp, permit));
+                },
+                Err(e) if e.is_connect() || e.is_timeout() => (format!("request failed: {}", e), false, None),
+                Err(e) => return Err(core::EError::UpstreamIGDB(format!("Failed with error {}", e))),
+            };
+            drop(permit);
 
-            let request = STwitchAPIRequestBuilder::new()
-                .url("https://api.igdb.com/v4/games/")
-                .header("Client-ID", session.twitch_client_id.as_str())
-                .header(
-                    "Authorization",
-                    format!("Bearer {}", SReqwestTwitchAPIClient::access_token(&session)).as_str(),
-                )
-                .header("Accept", "application/json")
-                .body(body.as_str());
+            if attempt >= rate_limit::MAX_RETRIES {
+                println!("IGDB {}, giving up after {} retries", retry_reason, attempt);
+                return Err(core::EError::UpstreamIGDB(format!("IGDB request failed after {} retries: {}", attempt, retry_reason)));
+            }
 
-            match SReqwestTwitchAPIClient::post_interp_json::<Vec<SIGDBSearchResult>>(
-                session.clone(),
-                request,
-            )
-            .await
-            {
-                Ok(res) => Ok(res),
-                Err(e) => Err(format!("Failed with error {:?}", e)),
+            let delay = rate_limit::retry_delay(attempt, retry_after);
+            attempt += 1;
+            println!("IGDB {}, retry {}/{} in {}ms", retry_reason, attempt, rate_limit::MAX_RETRIES, delay.as_millis());
+            if rate_limited {
+                session.limiter.pause(delay).await;
+            }
+            else {
+                tokio::time::sleep(delay).await;
             }
-        }?;
-
-        let mut results = Vec::with_capacity(search_results.len());
-        for search_res in search_results {
-            results.push(core::EGameInfo::new_igdb(
-                search_res.id,
-                search_res.slug.as_str(),
-                search_res.cover.map(|c| c.image_id),
-                search_res.name.as_str(),
-                best_release_date(search_res.status, search_res.release_dates),
-            ));
         }
-
-        Ok(results)
     }
 
-    // -- not pub because the API doesn't seem to work when you have 'search' in the queries
-    #[allow(dead_code)]
-    async fn multi_search(
+    // -- goes through the response cache when there is one
+    async fn post_interp_json<T: DeserializeOwned>(
         session: &SReqwestTwitchAPISession,
-        names: &[&str],
-    ) -> Result<Vec<Vec<core::EGameInfo>>, String> {
-        if names.len() > 10 {
-            return Err(String::from("Cannot multi-search for more than 10 games"));
+        rb: STwitchAPIRequestBuilder,
+        kind: EQueryKind,
+    ) -> Result<T, core::EError> {
+        let endpoint = rb.url.trim_start_matches(session.igdb_base_url.as_str()).trim_matches('/').to_string();
+        let query = rb.body.clone().unwrap_or_default();
+        let parse = |text: &str| serde_json::from_str::<T>(text).map_err(|e| core::EError::UpstreamIGDB(format!("Failed with error {}", e)));
+
+        if let Some(cache) = &session.cache {
+            let cache = cache.lock().expect("cache lock poisoned");
+            if let Some(cached) = cache.get(endpoint.as_str(), query.as_str(), kind) {
+                return parse(cached.response.as_str());
+            }
+            if cache.mode() == EResponseCacheMode::ReplayOnly {
+                return Err(core::EError::UpstreamIGDB(format!("Nothing cached for this {} query, and the IGDB cache is replay only", endpoint)));
+            }
         }
 
-        #[derive(Deserialize)]
-        #[allow(dead_code)]
-        struct SIGDBSearchResultCover {
-            id: u32,
-            image_id: String,
-        }
-        #[derive(Deserialize)]
-        struct SIGDBSearchResult {
-            id: u32,
-            slug: String,
-            name: String,
-            release_dates: Option<Vec<SIGDBInfoResultReleaseDate>>,
-            status: Option<u8>,
-            cover: Option<SIGDBSearchResultCover>,
-        }
+        let (resp, _permit) = Self::send(session, rb).await?;
+        let text = resp.text().await.map_err(|e| core::EError::UpstreamIGDB(format!("Failed with error {}", e)))?;
+        let result = parse(text.as_str())?;
 
-        #[derive(Deserialize)]
-        struct SIGDBMultiSearchResults {
-            r0: Option<Vec<SIGDBSearchResult>>,
-            r1: Option<Vec<SIGDBSearchResult>>,
-            r2: Option<Vec<SIGDBSearchResult>>,
-            r3: Option<Vec<SIGDBSearchResult>>,
-            r4: Option<Vec<SIGDBSearchResult>>,
-            r5: Option<Vec<SIGDBSearchResult>>,
-            r6: Option<Vec<SIGDBSearchResult>>,
-            r7: Option<Vec<SIGDBSearchResult>>,
-            r8: Option<Vec<SIGDBSearchResult>>,
-            r9: Option<Vec<SIGDBSearchResult>>,
-        }
+        session.record_in_cache(|cache| {
+            cache.insert(endpoint.as_str(), query.as_str(), text);
+            Ok(())
+        });
 
-        impl SIGDBMultiSearchResults {
-            fn to_vec(self) -> Vec<Vec<SIGDBSearchResult>> {
-                let mut result = Vec::with_capacity(10);
+        Ok(result)
+    }
 
-                if let Some(r) = self.r0 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r1 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r2 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r3 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r4 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r5 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r6 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r7 {
-                    result.push(r);
-                }
-                if let Some(r) = self.r8 {
-                    result.push(r);
+    pub async fn get_game_info(
+        session: &SReqwestTwitchAPISession,
+        igdb_id: u32,
+    ) -> Result<core::EGameInfo, core::EError> {
+        let mut games_info = Self::get_games_info(session, &[igdb_id]).await?;
+        games_info.found.remove(&igdb_id)
+            .ok_or_else(|| core::EError::UpstreamIGDB(format!("Got no results for IGDB game with ID {}", igdb_id)))
+    }
+
+    // -- one request per MAX_IDS_PER_QUERY IDs, duplicates are only asked for once. The response cache
+    // -- is checked per game, so only IDs it can't answer are requested.
+    pub async fn get_games_info(
+        session: &SReqwestTwitchAPISession,
+        igdb_ids: &[u32],
+    ) -> Result<SGamesInfo, core::EError> {
+        let ids = unique_ids(igdb_ids);
+        let parse = |text: &str| serde_json::from_str::<Vec<SIGDBGameResult>>(text).map_err(|e| core::EError::UpstreamIGDB(format!("Failed with error {}", e)));
+
+        let mut results = Vec::with_capacity(ids.len());
+        let mut uncached_ids = Vec::with_capacity(ids.len());
+        match &session.cache {
+            Some(cache) => {
+                let cache = cache.lock().expect("cache lock poisoned");
+                for id in &ids {
+                    match cache.get_game(*id) {
+                        Some(cached) => results.append(&mut parse(cached.response.as_str())?),
+                        None => uncached_ids.push(*id),
+                    }
                 }
-                if let Some(r) = self.r9 {
-                    result.push(r);
+                if !uncached_ids.is_empty() && cache.mode() == EResponseCacheMode::ReplayOnly {
+                    return Err(core::EError::UpstreamIGDB(format!("Nothing cached for IGDB games {:?}, and the IGDB cache is replay only", uncached_ids)));
                 }
-
-                result
-            }
+            },
+            None => uncached_ids.extend_from_slice(&ids),
         }
 
-        let mq_results: SIGDBMultiSearchResults = {
-            let mut body = String::new();
-
-            for (idx, name) in names.iter().enumerate() {
-                let name_query = format!(
-                    "
-query games \"r{}\" {{
-    search \"{}\";
-    fields name,slug,release_dates.date,release_dates.status.name,status,cover.image_id;
-}};\n",
-                    idx, name
-                );
-
-                body.push_str(name_query.as_str());
-            }
-
-            println!("{}", body);
+        for chunk in uncached_ids.chunks(MAX_IDS_PER_QUERY) {
+            let id_list : Vec<String> = chunk.iter().map(|id| id.to_string()).collect();
+            let body = format!("where id = ({}); {} limit {};", id_list.join(","), GAME_FIELDS, MAX_IDS_PER_QUERY);
 
             let request = STwitchAPIRequestBuilder::new()
-                .url("https://api.igdb.com/v4/multiquery/")
-                .header("Client-ID", session.twitch_client_id.as_str())
-                .header(
-                    "Authorization",
-                    format!("Bearer {}", SReqwestTwitchAPIClient::access_token(&session)).as_str(),
-                )
+                .url(format!("{}/games/", session.igdb_base_url).as_str())
                 .header("Accept", "application/json")
                 .body(body.as_str());
 
-            println!("\n\n\nTEXT\n\n\n");
+            let (resp, _permit) = Self::send(session, request).await?;
+            let text = resp.text().await.map_err(|e| core::EError::UpstreamIGDB(format!("Failed with error {}", e)))?;
+            results.append(&mut parse(text.as_str())?);
 
-            match SReqwestTwitchAPIClient::post_text(session.clone(), request.clone()).await {
-                Ok(res) => {
-                    println!("{:?}", res);
-                }
-                Err(e) => {
-                    return Err(format!("Failed with error {:?}", e));
-                }
-            }
+            session.record_in_cache(|cache| cache.insert_games(chunk, text.as_str()));
+        }
 
-            println!("\n\n\nJSON\n\n\n");
+        Ok(SGamesInfo::new(ids, results))
+    }
 
-            match SReqwestTwitchAPIClient::post_interp_json::<SIGDBMultiSearchResults>(
-                session.clone(),
-                request,
-            )
-            .await
-            {
-                Ok(res) => Ok(res),
-                Err(e) => Err(format!("Failed with error {:?}", e)),
+    pub async fn search(
+        session: &SReqwestTwitchAPISession,
+        name: &str,
+        games_only: bool,
+    ) -> Result<Vec<core::EGameInfo>, core::EError> {
+        let body = format!("search {}; {}{}", quote_query_string(name), search_where_clause(games_only), GAME_FIELDS);
+
+        /*
+        Should be equivalent to:
+        curl -d "search \"halo\"; fields name,release_dates,cover.url;" -H 'Client-ID: <ID>' -H 'Authorization: Bearer <BEARER>' -H 'Accept: application/json' https://api.igdb.com/v4/games
+        */
+
+        let request = STwitchAPIRequestBuilder::new()
+            .url(format!("{}/games/", session.igdb_base_url).as_str())
+            .header("Accept", "application/json")
+            .body(body.as_str());
+
+        let search_results = SReqwestTwitchAPIClient::post_interp_json::<Vec<SIGDBGameResult>>(session, request, EQueryKind::Search).await?;
+        Ok(search_results.into_iter().map(SIGDBGameResult::into_game_info).collect())
+    }
+
+    // -- searches for several names at once through multiquery, ten names per request. Results line
+    // -- up with names.
+    pub async fn multi_search(
+        session: &SReqwestTwitchAPISession,
+        names: &[&str],
+        games_only: bool,
+    ) -> Result<Vec<Vec<core::EGameInfo>>, core::EError> {
+        let mut results = Vec::with_capacity(names.len());
+
+        for chunk in names.chunks(MAX_MULTIQUERY_QUERIES) {
+            let mut body = String::new();
+            for (idx, name) in chunk.iter().enumerate() {
+                body.push_str(format!(
+                    "query games \"r{}\" {{ search {}; {}{} }};\n",
+                    idx, quote_query_string(name), search_where_clause(games_only), GAME_FIELDS
+                ).as_str());
             }
-        }?;
 
-        fn extract_cover_url(cover: SIGDBSearchResultCover) -> String {
-            cover.image_id
-        }
+            let request = STwitchAPIRequestBuilder::new()
+                .url(format!("{}/multiquery/", session.igdb_base_url).as_str())
+                .header("Accept", "application/json")
+                .body(body.as_str());
 
-        let mq_results_vec = mq_results.to_vec();
-
-        let mut results = Vec::with_capacity(mq_results_vec.len());
-        for query_result in mq_results_vec {
-            let mut name_result = Vec::with_capacity(query_result.len());
-            for igdb_game in query_result {
-                name_result.push(core::EGameInfo::new_igdb(
-                    igdb_game.id,
-                    igdb_game.slug.as_str(),
-                    igdb_game.cover.map(extract_cover_url),
-                    igdb_game.name.as_str(),
-                    best_release_date(igdb_game.status, igdb_game.release_dates),
-                ));
+            let mq_results = SReqwestTwitchAPIClient::post_interp_json::<Vec<SIGDBMultiQueryResult>>(session, request, EQueryKind::Search).await?;
+
+            let mut chunk_results : Vec<Vec<core::EGameInfo>> = chunk.iter().map(|_| Vec::new()).collect();
+            for mq_result in mq_results {
+                let idx = mq_result.name.strip_prefix('r')
+                    .and_then(|idx| idx.parse::<usize>().ok())
+                    .filter(|idx| *idx < chunk_results.len())
+                    .ok_or_else(|| core::EError::UpstreamIGDB(format!("Unexpected multiquery result \"{}\"", mq_result.name)))?;
+                chunk_results[idx] = mq_result.result.into_iter().map(SIGDBGameResult::into_game_info).collect();
             }
-            results.push(name_result);
+            results.append(&mut chunk_results);
         }
 
-        assert!(results.len() == names.len());
-
         Ok(results)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn out_of_range_release_dates_are_skipped() {
+        let release = |date: i64| SIGDBInfoResultReleaseDate {
+            date: Some(date),
+            status: Some(SIGDBInfoResultReleaseDateStatus{ name: String::from("Full Release") }),
+            platform: None,
+            region: None,
+        };
+        let known = chrono::NaiveDate::from_ymd(2018, 1, 25);
+
+        let dates = [release(i64::MIN), release(1516838400)];
+        assert_eq!(best_release_date(None, &dates), core::EReleaseDate::Known(known));
+        assert_eq!(best_release_date(None, &dates[..1]), core::EReleaseDate::UnknownUnreleased);
+    }
+}
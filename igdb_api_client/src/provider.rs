@@ -0,0 +1,31 @@
+use async_trait::async_trait;
+
+use gamechooser_core as core;
+
+use crate::{SGamesInfo, SReqwestTwitchAPIClient, SReqwestTwitchAPISession};
+
+// -- where game metadata comes from. The server only talks to IGDB through this, so it can run
+// -- against fixtures or a stand-in server instead of the real thing.
+#[async_trait]
+pub trait TMetadataProvider: Send + Sync {
+    async fn search(&self, name: &str, games_only: bool) -> Result<Vec<core::EGameInfo>, core::EError>;
+
+    async fn get_games_info(&self, igdb_ids: &[u32]) -> Result<SGamesInfo, core::EError>;
+
+    async fn get_game_info(&self, igdb_id: u32) -> Result<core::EGameInfo, core::EError> {
+        let mut games_info = self.get_games_info(&[igdb_id]).await?;
+        games_info.found.remove(&igdb_id)
+            .ok_or_else(|| core::EError::UpstreamIGDB(format!("Got no results for IGDB game with ID {}", igdb_id)))
+    }
+}
+
+#[async_trait]
+impl TMetadataProvider for SReqwestTwitchAPISession {
+    async fn search(&self, name: &str, games_only: bool) -> Result<Vec<core::EGameInfo>, core::EError> {
+        SReqwestTwitchAPIClient::search(self, name, games_only).await
+    }
+
+    async fn get_games_info(&self, igdb_ids: &[u32]) -> Result<SGamesInfo, core::EError> {
+        SReqwestTwitchAPIClient::get_games_info(self, igdb_ids).await
+    }
+}
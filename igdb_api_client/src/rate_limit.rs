@@ -0,0 +1,122 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use tokio::sync::{Mutex, Semaphore, SemaphorePermit};
+use tokio::time::Instant;
+
+// -- IGDB allows 4 requests per second and 8 open requests at a time per client id
+pub const IGDB_REQUESTS_PER_SECOND: u32 = 4;
+pub const IGDB_MAX_OPEN_REQUESTS: usize = 8;
+
+pub const MAX_RETRIES: u32 = 4;
+const BACKOFF_BASE: Duration = Duration::from_millis(500);
+const BACKOFF_MAX: Duration = Duration::from_secs(8);
+// -- a 429's Retry-After is followed up to this, longer would hold up a user's request for too long
+const RETRY_AFTER_MAX: Duration = Duration::from_secs(30);
+
+// -- spaces requests out evenly rather than allowing bursts, so a full second's budget is never
+// -- spent in one go and then rejected by IGDB
+pub struct SRateLimiter {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+    open_requests: Semaphore,
+}
+
+impl SRateLimiter {
+    pub fn new(requests_per_second: u32, max_open_requests: usize) -> Self {
+        Self {
+            interval: Duration::from_secs(1) / requests_per_second,
+            next_slot: Mutex::new(Instant::now()),
+            open_requests: Semaphore::new(max_open_requests),
+        }
+    }
+
+    // -- waits for a free slot. The request counts as open until the permit is dropped.
+    pub async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self.open_requests.acquire().await.expect("rate limiter semaphore closed");
+
+        let wait_until = {
+            let mut next_slot = self.next_slot.lock().await;
+            let slot = std::cmp::max(*next_slot, Instant::now());
+            *next_slot = slot + self.interval;
+            slot
+        };
+        tokio::time::sleep_until(wait_until).await;
+
+        permit
+    }
+
+    // -- pushes every later request back, for when IGDB says we're going too fast anyway
+    pub async fn pause(&self, duration: Duration) {
+        let mut next_slot = self.next_slot.lock().await;
+        *next_slot = std::cmp::max(*next_slot, Instant::now() + duration);
+    }
+}
+
+// -- how long to wait before retry attempt, starting at 0: IGDB's Retry-After if it sent one, otherwise
+// -- exponential backoff with full jitter
+pub fn retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    match retry_after {
+        Some(retry_after) => std::cmp::min(retry_after, RETRY_AFTER_MAX),
+        None => {
+            let max = std::cmp::min(BACKOFF_BASE * 2u32.pow(attempt), BACKOFF_MAX);
+            max.mul_f64(random_unit(attempt))
+        },
+    }
+}
+
+// -- good enough randomness for jitter without pulling in a crate for it. RandomState is keyed randomly
+// -- per process, the clock and attempt keep retries within a process from repeating.
+fn random_unit(attempt: u32) -> f64 {
+    let since_epoch = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(since_epoch.as_nanos());
+    hasher.write_u32(attempt);
+    (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn spaces_requests_evenly() {
+        let limiter = SRateLimiter::new(IGDB_REQUESTS_PER_SECOND, IGDB_MAX_OPEN_REQUESTS);
+        let start = Instant::now();
+
+        let mut started = Vec::new();
+        for _ in 0..6 {
+            let _permit = limiter.acquire().await;
+            started.push(start.elapsed().as_millis());
+        }
+        assert_eq!(started, vec![0, 250, 500, 750, 1000, 1250]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_open_requests() {
+        let limiter = SRateLimiter::new(IGDB_REQUESTS_PER_SECOND, IGDB_MAX_OPEN_REQUESTS);
+
+        let mut permits = Vec::new();
+        for _ in 0..IGDB_MAX_OPEN_REQUESTS {
+            permits.push(limiter.acquire().await);
+        }
+
+        // -- nothing else starts while all of them are open, however long that takes
+        assert!(tokio::time::timeout(Duration::from_secs(60), limiter.acquire()).await.is_err());
+
+        permits.pop();
+        assert!(tokio::time::timeout(Duration::from_millis(1), limiter.acquire()).await.is_ok());
+    }
+
+    #[test]
+    fn retry_delays_are_capped() {
+        assert_eq!(retry_delay(0, Some(Duration::from_secs(2))), Duration::from_secs(2));
+        assert_eq!(retry_delay(0, Some(Duration::from_secs(3600))), RETRY_AFTER_MAX);
+
+        for attempt in 0..10 {
+            assert!(retry_delay(attempt, None) <= BACKOFF_MAX);
+        }
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+
+use chrono::naive::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use gamechooser_core as core;
+
+use crate::{GAME_FIELDS, SIGDBGameResult, SIGDBMultiQueryResult};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EResponseCacheMode {
+    #[default]
+    Off,
+    // -- answers from the cache while entries are fresh, records everything else
+    ReadWrite,
+    // -- never touches the network, stale entries are still used and misses are errors
+    ReplayOnly,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SResponseCacheConfig {
+    pub mode: EResponseCacheMode,
+    // -- relative to the working directory, so give the server and cli_client an absolute path if
+    // -- they should share a cache
+    pub path: String,
+    // -- searches are what we repeat most, and new games show up in them slowly
+    pub search_ttl_hours: i64,
+    // -- everything else, mostly game info lookups by ID
+    pub lookup_ttl_hours: i64,
+}
+
+impl Default for SResponseCacheConfig {
+    fn default() -> Self {
+        Self {
+            mode: EResponseCacheMode::Off,
+            path: String::from("igdb_cache.json"),
+            search_ttl_hours: 24 * 7,
+            lookup_ttl_hours: 24,
+        }
+    }
+}
+
+// -- picks the TTL a cached response is fresh for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EQueryKind {
+    Search,
+    Lookup,
+}
+
+// -- the raw response text, exactly as IGDB sent it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SCachedResponse {
+    pub endpoint: String,
+    pub query: String,
+    pub response: String,
+    pub recorded: NaiveDateTime,
+}
+
+// -- inserts only mark the cache as changed. The session schedules one save for each batch of
+// -- inserts, and anything still unsaved is written when the cache is dropped.
+pub struct SResponseCache {
+    config: SResponseCacheConfig,
+    entries: HashMap<(String, String), SCachedResponse>,
+    unsaved: bool,
+    save_scheduled: bool,
+}
+
+// -- the cache file contents, taken under the cache lock so they can be written outside it
+pub(crate) struct SCacheSnapshot {
+    path: String,
+    json: String,
+}
+
+fn now() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+// -- lookups by ID are cached one game per entry under this query rather than per batch, so a
+// -- batch is answered from games recorded in any other batch
+fn game_lookup_query(igdb_id: u32) -> String {
+    format!("where id = {}; {}", igdb_id, GAME_FIELDS)
+}
+
+impl SResponseCache {
+    // -- a missing file is an empty cache
+    pub fn load(config: SResponseCacheConfig) -> Result<Self, core::EError> {
+        let entries : Vec<SCachedResponse> = match std::fs::read_to_string(config.path.as_str()) {
+            Ok(json) => serde_json::from_str(json.as_str())
+                .map_err(|e| core::EError::Storage(format!("Could not parse IGDB cache {}: {}", config.path, e)))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(core::EError::Storage(format!("Could not read IGDB cache {}: {}", config.path, e))),
+        };
+
+        Ok(Self {
+            config,
+            entries: entries.into_iter().map(|e| ((e.endpoint.clone(), e.query.clone()), e)).collect(),
+            unsaved: false,
+            save_scheduled: false,
+        })
+    }
+
+    pub fn mode(&self) -> EResponseCacheMode {
+        self.config.mode
+    }
+
+    fn ttl(&self, kind: EQueryKind) -> chrono::Duration {
+        match kind {
+            EQueryKind::Search => chrono::Duration::hours(self.config.search_ttl_hours),
+            EQueryKind::Lookup => chrono::Duration::hours(self.config.lookup_ttl_hours),
+        }
+    }
+
+    // -- fresh entries only, unless replaying
+    pub fn get(&self, endpoint: &str, query: &str, kind: EQueryKind) -> Option<&SCachedResponse> {
+        let entry = self.entries.get(&(endpoint.to_string(), query.to_string()))?;
+        let fresh = entry.recorded + self.ttl(kind) > now();
+        (fresh || self.config.mode == EResponseCacheMode::ReplayOnly).then_some(entry)
+    }
+
+    pub fn get_game(&self, igdb_id: u32) -> Option<&SCachedResponse> {
+        self.get("games", game_lookup_query(igdb_id).as_str(), EQueryKind::Lookup)
+    }
+
+    pub fn insert(&mut self, endpoint: &str, query: &str, response: String) {
+        let entry = SCachedResponse {
+            endpoint: endpoint.to_string(),
+            query: query.to_string(),
+            response,
+            recorded: now(),
+        };
+        self.entries.insert((entry.endpoint.clone(), entry.query.clone()), entry);
+        self.unsaved = true;
+    }
+
+    // -- splits a games response for igdb_ids into one entry per game. IDs IGDB didn't return get an
+    // -- empty list, so they replay as missing rather than as a cache miss.
+    pub fn insert_games(&mut self, igdb_ids: &[u32], response: &str) -> Result<(), core::EError> {
+        let games : Vec<serde_json::Value> = serde_json::from_str(response)
+            .map_err(|e| core::EError::Internal(format!("Could not parse games response for the IGDB cache: {}", e)))?;
+
+        let mut by_id : HashMap<u32, Vec<serde_json::Value>> = igdb_ids.iter().map(|id| (*id, Vec::new())).collect();
+        for game in games {
+            let id = game.get("id").and_then(|id| id.as_u64())
+                .ok_or_else(|| core::EError::Internal(String::from("Games response for the IGDB cache has a game without an ID")))?;
+            by_id.entry(id as u32).or_default().push(game);
+        }
+
+        for (id, games) in by_id {
+            let response = serde_json::to_string(&games)
+                .map_err(|e| core::EError::Internal(format!("Could not serialize IGDB game {} for the cache: {}", id, e)))?;
+            self.insert("games", game_lookup_query(id).as_str(), response);
+        }
+        Ok(())
+    }
+
+    // -- true once per batch of unsaved inserts, for whoever should schedule the save
+    pub(crate) fn schedule_save(&mut self) -> bool {
+        let schedule = self.unsaved && !self.save_scheduled;
+        self.save_scheduled |= schedule;
+        schedule
+    }
+
+    // -- None when there is nothing to save
+    pub(crate) fn snapshot(&mut self) -> Result<Option<SCacheSnapshot>, core::EError> {
+        if !self.unsaved {
+            return Ok(None);
+        }
+
+        let mut entries : Vec<&SCachedResponse> = self.entries.values().collect();
+        entries.sort_by(|a, b| (&a.endpoint, &a.query).cmp(&(&b.endpoint, &b.query)));
+
+        let json = serde_json::to_string_pretty(&entries)
+            .map_err(|e| core::EError::Internal(format!("Could not serialize IGDB cache: {}", e)))?;
+        self.unsaved = false;
+        self.save_scheduled = false;
+
+        Ok(Some(SCacheSnapshot {
+            path: self.config.path.clone(),
+            json,
+        }))
+    }
+
+    // -- every game in the cached responses as a fixture file for SFixtureMetadataProvider, newest
+    // -- response wins when a game shows up more than once
+    pub fn export_fixtures(&self) -> Result<String, core::EError> {
+        let mut entries : Vec<&SCachedResponse> = self.entries.values().collect();
+        entries.sort_by_key(|e| e.recorded);
+
+        let mut games : HashMap<u32, SIGDBGameResult> = HashMap::new();
+        for entry in entries {
+            let results : Vec<SIGDBGameResult> = if entry.endpoint == "multiquery" {
+                serde_json::from_str::<Vec<SIGDBMultiQueryResult>>(entry.response.as_str())
+                    .map(|results| results.into_iter().flat_map(|r| r.result).collect())
+            }
+            else {
+                serde_json::from_str(entry.response.as_str())
+            }.map_err(|e| core::EError::Internal(format!("Could not parse cached {} response: {}", entry.endpoint, e)))?;
+
+            for game in results {
+                games.insert(game.id, game);
+            }
+        }
+
+        let mut games : Vec<SIGDBGameResult> = games.into_values().collect();
+        games.sort_by_key(|g| g.id);
+        serde_json::to_string_pretty(&games)
+            .map_err(|e| core::EError::Internal(format!("Could not serialize fixtures: {}", e)))
+    }
+}
+
+impl Drop for SResponseCache {
+    fn drop(&mut self) {
+        let saved = self.snapshot().and_then(|snapshot| match snapshot {
+            Some(snapshot) => snapshot.write(),
+            None => Ok(()),
+        });
+        if let Err(e) = saved {
+            println!("Could not save the IGDB cache: {}", e);
+        }
+    }
+}
+
+impl SCacheSnapshot {
+    // -- written beside the cache file and renamed over it, so a failed write leaves the old cache
+    pub(crate) fn write(&self) -> Result<(), core::EError> {
+        let temp_path = format!("{}.tmp", self.path);
+        std::fs::write(temp_path.as_str(), self.json.as_str())
+            .map_err(|e| core::EError::Storage(format!("Could not write IGDB cache {}: {}", temp_path, e)))?;
+        std::fs::rename(temp_path.as_str(), self.path.as_str())
+            .map_err(|e| core::EError::Storage(format!("Could not replace IGDB cache {}: {}", self.path, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_are_saved_in_batches() {
+        let path = std::env::temp_dir().join(format!("igdb_cache_unit_test_{}.json", std::process::id()));
+        let config = SResponseCacheConfig {
+            mode: EResponseCacheMode::ReadWrite,
+            path: path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        let mut cache = SResponseCache::load(config.clone()).unwrap();
+        assert!(!cache.schedule_save());
+
+        cache.insert("games", "search \"celeste\";", String::from("[]"));
+        assert!(cache.schedule_save());
+        cache.insert("games", "search \"hades\";", String::from("[]"));
+        assert!(!cache.schedule_save());
+
+        cache.snapshot().unwrap().unwrap().write().unwrap();
+        assert!(cache.snapshot().unwrap().is_none());
+
+        // -- the TTL comes from the kind of query, not its text
+        let reloaded = SResponseCache::load(config).unwrap();
+        assert!(reloaded.get("games", "search \"hades\";", EQueryKind::Search).is_some());
+        drop(reloaded);
+        std::fs::remove_file(path).unwrap();
+    }
+}
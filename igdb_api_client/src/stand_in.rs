@@ -0,0 +1,250 @@
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use crate::{SConfigFile, SFixtureMetadataProvider, SIGDBMultiQueryResult};
+
+// -- a local HTTP server answering the Twitch token and IGDB games/multiquery endpoints from fixtures,
+// -- so the real client can be tested end to end. Point a session at it with config(). It only
+// -- understands the queries this crate sends.
+pub struct SStandInServer {
+    address: SocketAddr,
+    state: Arc<SStandInState>,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct SStandInState {
+    fixtures: SFixtureMetadataProvider,
+    failures: Mutex<VecDeque<u16>>,
+    requests: Mutex<Vec<String>>,
+    tokens_issued: Mutex<u32>,
+}
+
+struct SHTTPRequest {
+    path: String,
+    authorized: bool,
+    body: String,
+}
+
+impl SStandInServer {
+    pub fn start(fixtures: SFixtureMetadataProvider) -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = listener.local_addr()?;
+
+        let state = Arc::new(SStandInState {
+            fixtures,
+            failures: Mutex::new(VecDeque::new()),
+            requests: Mutex::new(Vec::new()),
+            tokens_issued: Mutex::new(0),
+        });
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread_state = state.clone();
+        let thread_shutdown = shutdown.clone();
+        let thread = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                if let Ok(stream) = stream {
+                    thread_state.handle(stream);
+                }
+            }
+        });
+
+        Ok(Self {
+            address,
+            state,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn config(&self) -> SConfigFile {
+        SConfigFile::new("stand-in-client", "stand-in-secret")
+            .base_urls(format!("http://{}/v4", self.address).as_str(), format!("http://{}", self.address).as_str())
+    }
+
+    // -- the next IGDB request gets this status instead of an answer, queued failures go in order
+    pub fn fail_next(&self, status: u16) {
+        self.state.failures.lock().expect("stand-in lock poisoned").push_back(status);
+    }
+
+    // -- paths of every request served so far, including failed ones
+    pub fn requests(&self) -> Vec<String> {
+        self.state.requests.lock().expect("stand-in lock poisoned").clone()
+    }
+}
+
+impl Drop for SStandInServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // -- wake the accept loop so it sees the flag
+        let _ = TcpStream::connect(self.address);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl SStandInState {
+    fn handle(&self, mut stream: TcpStream) {
+        let (status, body) = match read_request(&mut stream) {
+            Some(request) => {
+                self.requests.lock().expect("stand-in lock poisoned").push(request.path.clone());
+                self.respond(&request)
+            },
+            None => (400, String::from("bad request")),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {} Stand-in\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status, body.len(), body
+        );
+        let _ = stream.write_all(response.as_bytes());
+    }
+
+    fn respond(&self, request: &SHTTPRequest) -> (u16, String) {
+        if request.path == "/oauth2/token" {
+            let mut tokens_issued = self.tokens_issued.lock().expect("stand-in lock poisoned");
+            *tokens_issued += 1;
+            return (200, format!("{{\"access_token\":\"stand-in-{}\",\"expires_in\":3600,\"token_type\":\"bearer\"}}", tokens_issued));
+        }
+
+        if !request.path.starts_with("/v4/") {
+            return (404, String::from("not found"));
+        }
+        if let Some(status) = self.failures.lock().expect("stand-in lock poisoned").pop_front() {
+            return (status, String::from("stand-in failure"));
+        }
+        if !request.authorized {
+            return (401, String::from("no bearer token"));
+        }
+
+        let json = match request.path.as_str() {
+            "/v4/games/" => self.games_query(request.body.as_str()).and_then(|games| serde_json::to_string(&games).ok()),
+            "/v4/multiquery/" => self.multiquery(request.body.as_str()).and_then(|results| serde_json::to_string(&results).ok()),
+            _ => return (404, String::from("not found")),
+        };
+
+        match json {
+            Some(json) => (200, json),
+            None => (400, String::from("could not understand query")),
+        }
+    }
+
+    fn games_query(&self, query: &str) -> Option<Vec<crate::SIGDBGameResult>> {
+        if let Some(idx) = query.find("search ") {
+            let (name, _) = read_quoted(&query[idx + "search ".len()..])?;
+            return Some(self.fixtures.search_results(name.as_str(), query.contains("category = 0")));
+        }
+
+        let idx = query.find("where id = (")?;
+        let rest = &query[idx + "where id = (".len()..];
+        let ids : Option<Vec<u32>> = rest[..rest.find(')')?].split(',').map(|id| id.trim().parse().ok()).collect();
+        Some(self.fixtures.games_with_ids(&ids?))
+    }
+
+    fn multiquery(&self, body: &str) -> Option<Vec<SIGDBMultiQueryResult>> {
+        let mut results = Vec::new();
+        let mut rest = body;
+        while let Some(idx) = rest.find("query games ") {
+            let (name, after_name) = read_quoted(&rest[idx + "query games ".len()..])?;
+            let start = after_name.find('{')? + 1;
+            let len = query_len(&after_name[start..])?;
+            results.push(SIGDBMultiQueryResult {
+                name,
+                result: self.games_query(&after_name[start..start + len])?,
+            });
+            rest = &after_name[start + len..];
+        }
+        Some(results)
+    }
+}
+
+// -- reads one quoted, backslash escaped string from the start of text, returns it and what follows
+fn read_quoted(text: &str) -> Option<(String, &str)> {
+    let mut chars = text.trim_start().char_indices();
+    let offset = text.len() - text.trim_start().len();
+    if chars.next()?.1 != '"' {
+        return None;
+    }
+
+    let mut result = String::new();
+    while let Some((idx, c)) = chars.next() {
+        match c {
+            '\\' => result.push(chars.next()?.1),
+            '"' => return Some((result, &text[offset + idx + 1..])),
+            _ => result.push(c),
+        }
+    }
+    None
+}
+
+// -- length of a multiquery body up to its closing brace, skipping braces inside strings
+fn query_len(text: &str) -> Option<usize> {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (idx, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            '}' if !in_string => return Some(idx),
+            _ => {},
+        }
+    }
+    None
+}
+
+fn read_request(stream: &mut TcpStream) -> Option<SHTTPRequest> {
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4096];
+
+    let header_end = loop {
+        let read = stream.read(&mut buffer).ok()?;
+        if read == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buffer[..read]);
+        if let Some(idx) = data.windows(4).position(|w| w == b"\r\n\r\n") {
+            break idx + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&data[..header_end]).to_string();
+    let mut lines = head.lines();
+    let path = lines.next()?.split_whitespace().nth(1)?.to_string();
+
+    let mut content_length = 0;
+    let mut authorized = false;
+    for line in lines {
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim().to_lowercase();
+            if name == "content-length" {
+                content_length = value.trim().parse().ok()?;
+            }
+            else if name == "authorization" && value.trim().starts_with("Bearer stand-in-") {
+                authorized = true;
+            }
+        }
+    }
+
+    while data.len() < header_end + content_length {
+        let read = stream.read(&mut buffer).ok()?;
+        if read == 0 {
+            return None;
+        }
+        data.extend_from_slice(&buffer[..read]);
+    }
+
+    Some(SHTTPRequest {
+        path,
+        authorized,
+        body: String::from_utf8_lossy(&data[header_end..header_end + content_length]).to_string(),
+    })
+}
@@ -0,0 +1,92 @@
+use igdb_api_client::{EResponseCacheMode, SConfigFile, SFixtureMetadataProvider, SReqwestTwitchAPIClient, SResponseCache, SStandInServer, TMetadataProvider};
+
+fn start_server() -> SStandInServer {
+    let fixtures = SFixtureMetadataProvider::from_json(include_str!("../fixtures/games.json")).unwrap();
+    SStandInServer::start(fixtures).unwrap()
+}
+
+#[tokio::test]
+async fn client_against_stand_in() {
+    let server = start_server();
+    let session = SReqwestTwitchAPIClient::new_session_with_config(server.config()).await.unwrap();
+
+    let games_info = session.get_games_info(&[113112, 26226, 26226, 999]).await.unwrap();
+    assert_eq!(games_info.found.len(), 2);
+    assert_eq!(games_info.found[&26226].title(), "Celeste");
+    assert_eq!(games_info.missing, vec![999]);
+
+    let found = session.search("hollow \"knight", true).await.unwrap();
+    assert!(found.is_empty());
+    let found = session.search("hades", false).await.unwrap();
+    assert_eq!(found.len(), 1);
+
+    let names = ["hades", "nothing", "hollow knight"];
+    let results = SReqwestTwitchAPIClient::multi_search(&session, &names, true).await.unwrap();
+    let counts : Vec<usize> = results.iter().map(|r| r.len()).collect();
+    assert_eq!(counts, vec![1, 0, 2]);
+
+    // -- only the session start fetched a token
+    let requests = server.requests();
+    assert_eq!(requests.iter().filter(|p| p.as_str() == "/oauth2/token").count(), 1);
+}
+
+#[tokio::test]
+async fn retries_through_failures() {
+    let server = start_server();
+    let session = SReqwestTwitchAPIClient::new_session_with_config(server.config()).await.unwrap();
+
+    server.fail_next(401);
+    server.fail_next(503);
+    let game = session.get_game_info(14593).await.unwrap();
+    assert_eq!(game.title(), "Hollow Knight");
+
+    // -- the 401 got a new token, the 503 was retried with the same one
+    let requests = server.requests();
+    assert_eq!(requests.iter().filter(|p| p.as_str() == "/oauth2/token").count(), 2);
+    assert_eq!(requests.iter().filter(|p| p.as_str() == "/v4/games/").count(), 3);
+
+    assert!(session.get_game_info(999).await.is_err());
+}
+
+#[tokio::test]
+async fn records_and_replays() {
+    let cache_path = std::env::temp_dir().join(format!("igdb_cache_test_{}.json", std::process::id()));
+    let cache_path = cache_path.to_str().unwrap();
+    let _ = std::fs::remove_file(cache_path);
+
+    let server = start_server();
+    let mut config = server.config();
+    config.set_cache(EResponseCacheMode::ReadWrite, Some(cache_path));
+    let session = SReqwestTwitchAPIClient::new_session_with_config(config).await.unwrap();
+
+    session.search("celeste", false).await.unwrap();
+    session.search("celeste", false).await.unwrap();
+    session.get_games_info(&[113112, 999]).await.unwrap();
+    session.get_game_info(26226).await.unwrap();
+    session.get_games_info(&[26226, 113112]).await.unwrap();
+    assert_eq!(server.requests().iter().filter(|p| p.as_str() == "/v4/games/").count(), 3);
+    drop(server);
+    session.save_cache().await.unwrap();
+    assert!(!std::path::Path::new(format!("{}.tmp", cache_path).as_str()).exists());
+
+    // -- replaying needs no credentials and no server
+    let mut config = SConfigFile::default();
+    config.set_cache(EResponseCacheMode::ReplayOnly, Some(cache_path));
+    let cache = SResponseCache::load(config.cache_config().clone()).unwrap();
+    let session = SReqwestTwitchAPIClient::new_session_with_config(config).await.unwrap();
+
+    assert_eq!(session.search("celeste", false).await.unwrap()[0].title(), "Celeste");
+    assert_eq!(session.get_game_info(113112).await.unwrap().title(), "Hades");
+
+    // -- lookups replay per game, whatever batches they were recorded in
+    let games_info = session.get_games_info(&[26226, 113112, 999]).await.unwrap();
+    assert_eq!(games_info.found.len(), 2);
+    assert_eq!(games_info.missing, vec![999]);
+    assert!(session.get_games_info(&[26226, 14593]).await.is_err());
+    assert!(session.search("hades", false).await.is_err());
+
+    let fixtures = SFixtureMetadataProvider::from_json(cache.export_fixtures().unwrap().as_str()).unwrap();
+    assert_eq!(fixtures.get_games_info(&[26226, 113112, 14593]).await.unwrap().missing, vec![14593]);
+
+    std::fs::remove_file(cache_path).unwrap();
+}
@@ -1,6 +1,7 @@
 #[macro_use] extern crate rocket;
 
-use std::collections::{HashMap};
+mod scheduler;
+
 use std::ops::{Deref, DerefMut};
 use std::result::{Result};
 
@@ -10,27 +11,58 @@ use serde::de::{DeserializeOwned};
 use serde_json;
 use sublime_fuzzy;
 use rocket::response::{Responder, Response};
+use rocket::response::stream::{Event, EventStream};
 use rocket::serde::json::Json as RocketJson;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use once_cell::sync::Lazy;
 
 use gamechooser_core as core;
-use igdb_api_client::SReqwestTwitchAPIClient;
+use igdb_api_client::{SFixtureMetadataProvider, SReqwestTwitchAPIClient, TMetadataProvider};
+
+type SData = core::SCollectionService<SJsonFileStorage>;
+
+static MEMORY_DB : Lazy<RwLock<Result<SData, core::EError>>> = Lazy::new(|| RwLock::new(load_db()));
+
+static JOBS : Lazy<RwLock<core::SJobRegistry>> = Lazy::new(|| RwLock::new(core::SJobRegistry::new()));
+
+// -- one provider for the life of the server so the Twitch token is reused until it expires
+static METADATA_PROVIDER : Lazy<tokio::sync::OnceCell<Box<dyn TMetadataProvider>>> = Lazy::new(tokio::sync::OnceCell::new);
+
+// -- every saved change goes out here, each /events stream holds a receiver
+static CHANGE_EVENTS : Lazy<broadcast::Sender<core::EChangeEvent>> = Lazy::new(|| broadcast::channel(64).0);
+
+// -- IGDB itself, unless igdb_fixtures in the config points at recorded JSON to work offline with
+async fn metadata_provider() -> Result<&'static dyn TMetadataProvider, core::EError> {
+    let provider = METADATA_PROVIDER.get_or_try_init(|| async {
+        let cfg : SConfigFile = confy::load("gamechooser2_server", None)
+            .map_err(|e| core::EError::Storage(format!("Could not load server config file: {}", e)))?;
+
+        let provider : Box<dyn TMetadataProvider> = match cfg.igdb_fixtures {
+            Some(path) => Box::new(SFixtureMetadataProvider::from_file(path.as_str())?),
+            None => Box::new(SReqwestTwitchAPIClient::new_session().await?),
+        };
+        Ok::<_, core::EError>(provider)
+    }).await?;
 
-struct SData {
-    app_config: core::SConfig,
-    serialized_db: core::EDatabase,
-    game_igdb_id_to_internal_id: HashMap<u32, u32>,
-    game_sessions_reverse_lookup: HashMap<u32, Vec<u32>>,
+    Ok(provider.as_ref())
 }
 
-static MEMORY_DB : Lazy<RwLock<Result<SData, ()>>> = Lazy::new(|| RwLock::new(load_db()));
+// -- saves the database and app config as JSON files under SConfigFile::db_path
+struct SJsonFileStorage {
+    cfg: SConfigFile,
+}
 
-#[derive(Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct SConfigFile {
     db_path: String,
     auth_secret: String,
     auth_pw: String,
+
+    #[serde(default)]
+    schedule: scheduler::SScheduleConfig,
+
+    #[serde(default)]
+    igdb_fixtures: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -40,62 +72,41 @@ struct SOwnRecord {
     storefront: String,
 }
 
-/*
-#[derive(Reponder)
-enum MyEnum {
-   #[response(status = 500]
-   InternalServerError
-   #[response(status = 400, content_type = "json")]
-   BadRequest(&'static str)
-   ...
-}
-*/
+// -- core::EError sent back as JSON, with a status code matching the error code
+struct SErrorResponse(core::EError);
 
-#[allow(dead_code)]
-enum EErrorResponse {
-    DBError,
-    ExternalAPIError(String),
-    BadRequest(String),
-    NotAuthenticated,
+struct AuthenticatedUser {
 }
 
-struct AuthenticatedUser {
+impl From<core::EError> for SErrorResponse {
+    fn from(e: core::EError) -> Self {
+        Self(e)
+    }
 }
 
-impl<'r> Responder<'r, 'static> for EErrorResponse {
+impl<'r> Responder<'r, 'static> for SErrorResponse {
     fn respond_to(self, _: &'r rocket::Request<'_>) -> rocket::response::Result<'static> {
-        match self {
-            Self::DBError => {
-                let body = "Server was unable to access database file.";
-                Response::build()
-                    .status(rocket::http::Status::InternalServerError)
-                    .header(rocket::http::ContentType::Plain)
-                    .sized_body(body.len(), std::io::Cursor::new(body))
-                    .ok()
-            },
-            Self::ExternalAPIError(msg) => {
-                Response::build()
-                    .status(rocket::http::Status::InternalServerError)
-                    .header(rocket::http::ContentType::Plain)
-                    .sized_body(msg.len(), std::io::Cursor::new(msg))
-                    .ok()
-            },
-            Self::BadRequest(msg) => {
-                Response::build()
-                    .status(rocket::http::Status::BadRequest)
-                    .header(rocket::http::ContentType::Plain)
-                    .sized_body(msg.len(), std::io::Cursor::new(msg))
-                    .ok()
-            },
-            Self::NotAuthenticated => {
-                let body = "You have not authenticated, please log in.";
-                Response::build()
-                    .status(rocket::http::Status::Unauthorized)
-                    .header(rocket::http::ContentType::Plain)
-                    .sized_body(body.len(), std::io::Cursor::new(body))
-                    .ok()
-            }
+        let status = match self.0.code() {
+            core::EErrorCode::NotFound => rocket::http::Status::NotFound,
+            core::EErrorCode::Conflict => rocket::http::Status::Conflict,
+            core::EErrorCode::Validation => rocket::http::Status::UnprocessableEntity,
+            core::EErrorCode::UpstreamIGDB => rocket::http::Status::BadGateway,
+            core::EErrorCode::Storage => rocket::http::Status::InternalServerError,
+            core::EErrorCode::BadRequest => rocket::http::Status::BadRequest,
+            core::EErrorCode::NotAuthenticated => rocket::http::Status::Unauthorized,
+            core::EErrorCode::Internal => rocket::http::Status::InternalServerError,
+        };
+
+        if status.class() == rocket::http::StatusClass::ServerError {
+            eprintln!("{}", self.0);
         }
+
+        let body = serde_json::to_string(&self.0).map_err(|_| rocket::http::Status::InternalServerError)?;
+        Response::build()
+            .status(status)
+            .header(rocket::http::ContentType::JSON)
+            .sized_body(body.len(), std::io::Cursor::new(body))
+            .ok()
     }
 }
 
@@ -129,177 +140,134 @@ impl<'r> rocket::request::FromRequest<'r> for AuthenticatedUser {
     }
 }
 
-fn refresh_db_acceleration(data: &mut SData) -> Result<(), ()> {
-    data.game_igdb_id_to_internal_id.clear();
-    data.game_sessions_reverse_lookup.clear();
+fn load_file<T: DeserializeOwned>(cfg: &SConfigFile, file_name: &str, default_value: T) -> Result<T, core::EError> {
+    let mut path = std::path::PathBuf::new();
+    path.push(&cfg.db_path);
+    path.push(file_name);
+    path.set_extension("json");
 
-    // -- generate additional data for SData
-    for session in &data.serialized_db.sessions {
-        if !data.game_sessions_reverse_lookup.contains_key(&session.game_internal_id) {
-            data.game_sessions_reverse_lookup.insert(session.game_internal_id, Vec::new());
-        }
+    // -- read existing file
+    if path.exists() {
+        let file = match std::fs::File::open(path.clone()) {
+            Ok(f) => f,
+            Err(e) => {
+                return Err(core::EError::Storage(format!("Failed to open {} with: {:?}", path.display(), e)));
+            }
+        };
+        let reader = std::io::BufReader::new(file);
 
-        match data.game_sessions_reverse_lookup.get_mut(&session.game_internal_id) {
-            Some(session_list) => session_list.push(session.internal_id),
-            None => {
-                eprintln!("Created entry in game_sessions_reverse_lookup but couldn't find it immediately after.");
-                return Err(())
+        // Read the JSON contents of the file as an instance of `User`.
+        let value : T = match serde_json::from_reader(reader) {
+            Ok(g) => g,
+            Err(e) => {
+                return Err(core::EError::Storage(format!("Failed to deserialize {} with: {:?}", path.display(), e)));
             }
         };
-    }
 
-    for game in &data.serialized_db.games {
-        if let core::EGameInfo::IGDB(igdb_info) = &game.game_info {
-            data.game_igdb_id_to_internal_id.insert(igdb_info.id, game.internal_id);
-        }
+        Ok(value)
+    }
+    else {
+        println!("No DB file at {}, loading default/empty", path.display());
+        Ok(default_value)
     }
-
-    Ok(())
 }
 
-fn load_db() -> Result<SData, ()> {
+fn load_db() -> Result<SData, core::EError> {
     println!("Loading confy config from {}", confy::get_configuration_file_path("gamechooser2_server", None).unwrap().as_path().display());
     let cfg : SConfigFile = confy::load("gamechooser2_server", None).unwrap();
 
-    fn load_file<T: DeserializeOwned>(cfg: &SConfigFile, file_name: &str, default_value: T) -> Result<T, ()> {
-        let mut path = std::path::PathBuf::new();
-        path.push(&cfg.db_path);
-        path.push(file_name);
-        path.set_extension("json");
-
-        // -- read existing file
-        if path.exists() {
-            let file = match std::fs::File::open(path.clone()) {
-                Ok(f) => f,
-                Err(e) => {
-                    eprintln!("Failed to open {} with: {:?}", path.display(), e);
-                    return Err(());
-                }
-            };
-            let reader = std::io::BufReader::new(file);
-
-            // Read the JSON contents of the file as an instance of `User`.
-            let value : T = match serde_json::from_reader(reader) {
-                Ok(g) => g,
-                Err(e) => {
-                    eprintln!("Failed to deserialize {} with: {:?}", path.display(), e);
-                    return Err(());
-                }
-            };
-
-            Ok(value)
-        }
-        else {
-            println!("No DB file at {}, loading default/empty", path.display());
-            Ok(default_value)
-        }
-    }
-
     let app_config : core::SConfig = load_file(&cfg, "app_config", core::SConfig::default())?;
     let db : core::EDatabase = load_file(&cfg, "database", core::EDatabase::new())?;
 
-    let updated_db = db.to_latest_version();
-
-    let mut data = SData {
-        app_config,
-        serialized_db: updated_db,
-        game_igdb_id_to_internal_id: HashMap::new(),
-        game_sessions_reverse_lookup: HashMap::new(),
-    };
+    let mut service = core::SCollectionService::new(app_config, db, SJsonFileStorage{ cfg });
+    service.set_change_listener(Box::new(|event| {
+        // -- fails when nobody is listening, which is fine
+        let _ = CHANGE_EVENTS.send(event.clone());
+    }));
 
-    refresh_db_acceleration(&mut data)?;
-
-    Ok(data)
+    Ok(service)
 }
 
-fn save_db(data: &mut SData) -> Result<(), ()> {
-    let cfg : SConfigFile = confy::load("gamechooser2_server", None).unwrap();
+impl core::TCollectionStorage for SJsonFileStorage {
+    fn save(&mut self, config: &core::SConfig, db: &core::EDatabase) -> Result<(), String> {
+        save_file(&self.cfg, "database", db, true)?;
+        save_file(&self.cfg, "app_config", config, false)?;
 
-    refresh_db_acceleration(data)?;
-
-    fn save_file<T: serde::Serialize>(cfg: &SConfigFile, file_name: &str, data: &T, backup: bool) -> Result<(), ()> {
-        let mut path = std::path::PathBuf::new();
-        path.push(&cfg.db_path);
-        path.push(file_name);
-        path.set_extension("json");
-
-        if path.exists() {
-            if backup {
-                let mut backup_path = std::path::PathBuf::new();
-                backup_path.push(&cfg.db_path);
-                backup_path.push("bak");
+        Ok(())
+    }
+}
 
-                if !backup_path.exists() {
-                    if let Err(_) = std::fs::create_dir(backup_path.clone()) {
-                        eprintln!("Failed to back up DB before overwriting, aborted.");
-                        return Err(());
-                    }
-                }
+fn save_file<T: serde::Serialize>(cfg: &SConfigFile, file_name: &str, data: &T, backup: bool) -> Result<(), String> {
+    let mut path = std::path::PathBuf::new();
+    path.push(&cfg.db_path);
+    path.push(file_name);
+    path.set_extension("json");
 
-                let bak_file_name = format!("{}_{}.json", file_name, chrono::offset::Utc::now().timestamp());
-                backup_path.push(bak_file_name);
+    if path.exists() {
+        if backup {
+            let mut backup_path = std::path::PathBuf::new();
+            backup_path.push(&cfg.db_path);
+            backup_path.push("bak");
 
-                if let Err(e) = std::fs::rename(path.clone(), backup_path) {
-                    eprintln!("Failed to delete {}.json with: {:?}", file_name, e);
-                    return Err(());
+            if !backup_path.exists() {
+                if let Err(_) = std::fs::create_dir(backup_path.clone()) {
+                    return Err(String::from("Failed to back up DB before overwriting, aborted."));
                 }
             }
-            else {
-                if let Err(e) = std::fs::remove_file(path.clone()){
-                    eprintln!("Failed to delete {}.json with: {:?}", file_name, e);
-                    return Err(());
-                }
-            }
-        }
 
-        let open_options = std::fs::OpenOptions::new()
-            .create_new(true)
-            .write(true)
-            .append(true)
-            .open(&path);
+            let bak_file_name = format!("{}_{}.json", file_name, chrono::offset::Utc::now().timestamp());
+            backup_path.push(bak_file_name);
 
-        let file = match open_options {
-            Ok(f) => f,
-            Err(e) => {
-                eprintln!("Failed to open {} with: {:?}", path.display(), e);
-                return Err(());
+            if let Err(e) = std::fs::rename(path.clone(), backup_path) {
+                return Err(format!("Failed to delete {}.json with: {:?}", file_name, e));
             }
-        };
-        let writer = std::io::BufWriter::new(file);
-
-        match serde_json::to_writer_pretty(writer, data) {
-            Ok(_) => {},
-            Err(e) => {
-                eprintln!("Failed to serialize {}.json with: {:?}", file_name, e);
-                return Err(());
+        }
+        else {
+            if let Err(e) = std::fs::remove_file(path.clone()){
+                return Err(format!("Failed to delete {}.json with: {:?}", file_name, e));
             }
-        };
-
-        Ok(())
+        }
     }
 
-    save_file(&cfg, "database", &data.serialized_db, true)?;
-    save_file(&cfg, "app_config", &data.app_config, false)?;
+    let open_options = std::fs::OpenOptions::new()
+        .create_new(true)
+        .write(true)
+        .append(true)
+        .open(&path);
 
+    let file = match open_options {
+        Ok(f) => f,
+        Err(e) => {
+            return Err(format!("Failed to open {} with: {:?}", path.display(), e));
+        }
+    };
+    let writer = std::io::BufWriter::new(file);
+
+    match serde_json::to_writer_pretty(writer, data) {
+        Ok(_) => {},
+        Err(e) => {
+            return Err(format!("Failed to serialize {}.json with: {:?}", file_name, e));
+        }
+    };
 
     Ok(())
 }
 
 #[post("/get_config")]
-async fn get_config() -> Result<RocketJson<core::SConfig>, EErrorResponse> {
+async fn get_config() -> Result<RocketJson<core::SConfig>, SErrorResponse> {
     let db_guard = MEMORY_DB.read().await;
-    let config = &db_guard.deref().as_ref().map_err(|_| EErrorResponse::DBError)?.app_config;
+    let config = db_guard.deref().as_ref().map_err(|e| e.clone())?.config();
 
     Ok(RocketJson(config.clone()))
 }
 
 #[post("/search_igdb/<name>/<games_only>")]
-async fn search_igdb(name: &str, games_only: bool) -> Result<RocketJson<Vec<core::SSearchIGDBResult>>, EErrorResponse> {
-    let db_guard = MEMORY_DB.read().await;
-    let db = db_guard.deref().as_ref().map_err(|_| EErrorResponse::DBError)?;
+async fn search_igdb(name: &str, games_only: bool) -> Result<RocketJson<Vec<core::SSearchIGDBResult>>, SErrorResponse> {
+    // -- searching can wait on IGDB's rate limit, so don't hold the DB lock until it's done
+    let igdb_games = metadata_provider().await?.search(name, games_only).await?;
 
-    let session = SReqwestTwitchAPIClient::new_session().await.map_err(|e| EErrorResponse::ExternalAPIError(e))?;
-    let igdb_games = SReqwestTwitchAPIClient::search(&session, name, games_only).await.map_err(|e| EErrorResponse::ExternalAPIError(e))?;
+    let db_guard = MEMORY_DB.read().await;
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
     let mut results = Vec::with_capacity(igdb_games.len());
     for game in igdb_games {
@@ -308,7 +276,7 @@ async fn search_igdb(name: &str, games_only: bool) -> Result<RocketJson<Vec<core
             core::EGameInfo::IGDB(igdb_info) => igdb_info.id,
         };
 
-        let in_collection = db.game_igdb_id_to_internal_id.contains_key(&igdb_id);
+        let in_collection = db.repository().game_by_igdb_id(igdb_id).is_some();
 
         results.push(core::SSearchIGDBResult{
             game_info: game,
@@ -320,153 +288,337 @@ async fn search_igdb(name: &str, games_only: bool) -> Result<RocketJson<Vec<core
 }
 
 #[post("/add_game", data = "<game>")]
-async fn add_game(game: RocketJson<core::SAddCollectionGame>, _user: AuthenticatedUser) -> Result<(), EErrorResponse> {
+async fn add_game(game: RocketJson<core::SAddCollectionGame>, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
     let mut db_guard = MEMORY_DB.write().await;
-    let mut db = db_guard.deref_mut().as_mut().map_err(|_| EErrorResponse::DBError)?;
-
-    let mut max_id = 0;
-    for collection_game in &db.serialized_db.games {
-        max_id = std::cmp::max(max_id, collection_game.internal_id);
-    }
-
-    db.serialized_db.games.push(core::SCollectionGame::new(game.into_inner(), max_id + 1));
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    save_db(&mut db).map_err(|_| EErrorResponse::DBError)?;
+    db.add_game(game.into_inner())?;
 
     Ok(())
 }
 
 #[post("/add_game", data = "<game>", rank = 2)]
 #[allow(unused_variables)]
-async fn add_game_no_auth(game: RocketJson<core::SAddCollectionGame>) -> Result<(), EErrorResponse> {
-    return Err(EErrorResponse::NotAuthenticated);
+async fn add_game_no_auth(game: RocketJson<core::SAddCollectionGame>) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
 }
 
 #[post("/edit_game", data = "<game>")]
-async fn edit_game(game: RocketJson<core::SCollectionGame>, _user: AuthenticatedUser) -> Result<(), EErrorResponse> {
+async fn edit_game(game: RocketJson<core::SCollectionGame>, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
     let mut db_guard = MEMORY_DB.write().await;
-    let mut db = db_guard.deref_mut().as_mut().map_err(|_| EErrorResponse::DBError)?;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    let edit_internal_id = game.internal_id;
+    db.edit_game(game.into_inner())?;
 
-    for collection_game in &mut db.serialized_db.games {
-        let internal_id = collection_game.internal_id;
-        if internal_id == edit_internal_id {
-            *collection_game = game.into_inner();
-            break;
-        }
-    }
+    Ok(())
+}
 
-    save_db(&mut db).map_err(|_| EErrorResponse::DBError)?;
+#[post("/edit_game", data = "<game>", rank = 2)]
+#[allow(unused_variables)]
+async fn edit_game_no_auth(game: RocketJson<core::SAddCollectionGame>, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
+}
+
+#[post("/get_game/<game_internal_id>")]
+async fn get_game(game_internal_id: u32) -> Result<RocketJson<core::SCollectionGame>, SErrorResponse> {
+    let db_guard = MEMORY_DB.read().await;
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
+
+    let game = db.repository().game(game_internal_id).ok_or_else(|| core::EError::game_not_found(game_internal_id))?;
+    Ok(RocketJson(game.clone()))
+}
+
+#[post("/patch_game", data = "<request>")]
+async fn patch_game(request: RocketJson<core::SGamePatchRequest>, _user: AuthenticatedUser) -> Result<RocketJson<core::SCollectionGame>, SErrorResponse> {
+    let mut db_guard = MEMORY_DB.write().await;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
+
+    Ok(RocketJson(db.patch_game(&request)?))
+}
+
+#[post("/patch_game", data = "<request>", rank = 2)]
+#[allow(unused_variables)]
+async fn patch_game_no_auth(request: RocketJson<core::SGamePatchRequest>) -> Result<RocketJson<core::SCollectionGame>, SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
+}
+
+#[post("/patch_games", data = "<patches>")]
+async fn patch_games(patches: RocketJson<Vec<core::SGamePatchRequest>>, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
+    let mut db_guard = MEMORY_DB.write().await;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
+
+    db.patch_games(&patches)?;
 
     Ok(())
 }
 
-#[post("/edit_game", data = "<game>", rank = 2)]
+#[post("/patch_games", data = "<patches>", rank = 2)]
 #[allow(unused_variables)]
-async fn edit_game_no_auth(game: RocketJson<core::SAddCollectionGame>, _user: AuthenticatedUser) -> Result<(), EErrorResponse> {
-    return Err(EErrorResponse::NotAuthenticated);
+async fn patch_games_no_auth(patches: RocketJson<Vec<core::SGamePatchRequest>>) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
+}
+
+// -- returns the IDs of every game that was changed
+#[post("/bulk_edit_games", data = "<request>")]
+async fn bulk_edit_games(request: RocketJson<core::SBulkEditRequest>, _user: AuthenticatedUser) -> Result<RocketJson<Vec<u32>>, SErrorResponse> {
+    let mut db_guard = MEMORY_DB.write().await;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
+
+    Ok(RocketJson(db.bulk_edit(request.into_inner())?))
+}
+
+#[post("/bulk_edit_games", data = "<request>", rank = 2)]
+#[allow(unused_variables)]
+async fn bulk_edit_games_no_auth(request: RocketJson<core::SBulkEditRequest>) -> Result<RocketJson<Vec<u32>>, SErrorResponse> {
+    Err(core::EError::NotAuthenticated.into())
 }
 
 #[post("/get_recent_collection_games")]
-async fn get_recent_collection_games() -> Result<RocketJson<Vec<core::SCollectionGame>>, EErrorResponse> {
+async fn get_recent_collection_games() -> Result<RocketJson<Vec<core::SCollectionGame>>, SErrorResponse> {
     let db_guard = MEMORY_DB.read().await;
-    let db = &db_guard.deref().as_ref().map_err(|_| EErrorResponse::DBError)?.serialized_db;
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
-    let mut result = Vec::with_capacity(10);
+    Ok(RocketJson(db.recent_games(10)))
+}
 
-    let mut count = 0;
-    while count < 10 && db.games.len() > 0 {
-        result.push(db.games[db.games.len() - 1 - count].clone());
-        count += 1;
-    }
+// -- fetches one game from IGDB without saving anything, the DB lock is not held during the fetch
+#[post("/preview_igdb_refresh/<game_internal_id>")]
+async fn preview_igdb_refresh(game_internal_id: u32, _user: AuthenticatedUser) -> Result<RocketJson<core::SIGDBRefreshPreview>, SErrorResponse> {
+    let game = {
+        let db_guard = MEMORY_DB.read().await;
+        let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
+        db.repository().game(game_internal_id).cloned().ok_or_else(|| core::EError::game_not_found(game_internal_id))?
+    };
 
-    Ok(RocketJson(result))
+    let igdb_id = game.game_info.igdb_id()
+        .ok_or_else(|| core::EError::BadRequest(format!("\"{}\" was added manually and has no IGDB entry", game.game_info.title())))?;
+
+    let igdb_game_info = metadata_provider().await?.get_game_info(igdb_id).await?;
+
+    Ok(RocketJson(core::SIGDBRefreshPreview::new(&game, igdb_game_info)))
+}
+
+#[post("/preview_igdb_refresh/<game_internal_id>", rank = 2)]
+#[allow(unused_variables)]
+async fn preview_igdb_refresh_no_auth(game_internal_id: u32) -> Result<RocketJson<core::SIGDBRefreshPreview>, SErrorResponse> {
+    Err(core::EError::NotAuthenticated.into())
 }
 
-#[post("/update_igdb_games")]
-async fn update_igdb_games() -> Result<(), EErrorResponse> {
+#[post("/apply_igdb_refresh", data = "<request>")]
+async fn apply_igdb_refresh(request: RocketJson<core::SIGDBRefreshRequest>, _user: AuthenticatedUser) -> Result<RocketJson<core::SCollectionGame>, SErrorResponse> {
     let mut db_guard = MEMORY_DB.write().await;
-    let db = db_guard.deref_mut().as_mut().map_err(|_| EErrorResponse::DBError)?;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    let mut games_with_sessions = std::collections::HashSet::with_capacity(db.serialized_db.games.len());
-    for session in &db.serialized_db.sessions {
-        games_with_sessions.insert(session.game_internal_id);
-    }
+    Ok(RocketJson(db.refresh_game_info(&request)?))
+}
+
+#[post("/apply_igdb_refresh", data = "<request>", rank = 2)]
+#[allow(unused_variables)]
+async fn apply_igdb_refresh_no_auth(request: RocketJson<core::SIGDBRefreshRequest>) -> Result<RocketJson<core::SCollectionGame>, SErrorResponse> {
+    Err(core::EError::NotAuthenticated.into())
+}
 
-    let today = chrono::offset::Local::now().naive_local().date();
+fn publish_job(job: &core::SJob) {
+    let _ = CHANGE_EVENTS.send(core::EChangeEvent::JobUpdated{ job: job.clone() });
+}
 
-    // -- we might falsley believe a game came out if it had a bad date, so
-    // -- we still update for games that "released" in the last 6 months
-    let mut six_months_ago = today;
-    for _ in 0..(6*30) {
-        six_months_ago = six_months_ago.pred();
+async fn update_job<F>(job_id: u32, f: F)
+where
+    F: FnOnce(&mut core::SJob),
+{
+    let mut jobs = JOBS.write().await;
+    if let Ok(job) = jobs.update(job_id, f) {
+        publish_job(job);
     }
+}
 
-    let mut games_to_update = Vec::with_capacity(db.serialized_db.games.len());
-    for (i, game) in db.serialized_db.games.iter().enumerate() {
-        if let core::EGameInfo::IGDB(igdb_game_info) = &game.game_info {
-            if games_with_sessions.contains(&game.internal_id) {
+// -- a game picked for the IGDB update job, with the revision it had then
+struct SIGDBUpdateTarget {
+    internal_id: u32,
+    revision: u32,
+    igdb_id: u32,
+    title: String,
+}
+
+// -- other requests keep working while this runs, the DB lock is only held to save each chunk
+async fn run_igdb_update_job(job_id: u32, games_to_update: Vec<SIGDBUpdateTarget>) {
+    let provider = match metadata_provider().await {
+        Ok(s) => s,
+        Err(e) => {
+            let mut jobs = JOBS.write().await;
+            if let Ok(job) = jobs.finish(job_id, chrono::offset::Local::now().naive_local(), Some(e.to_string())) {
+                publish_job(job);
+            }
+            return;
+        }
+    };
+
+    // -- one IGDB request and one save per chunk
+    for chunk in games_to_update.chunks(igdb_api_client::MAX_IDS_PER_QUERY) {
+        let chunk_message = format!("Fetching {} games from IGDB", chunk.len());
+        update_job(job_id, |job| job.current_title = Some(chunk_message)).await;
+
+        let igdb_ids : Vec<u32> = chunk.iter().map(|target| target.igdb_id).collect();
+        let mut games_info = match provider.get_games_info(&igdb_ids).await {
+            Ok(games_info) => games_info,
+            Err(e) => {
+                let message = e.to_string();
+                update_job(job_id, |job| {
+                    job.done += chunk.len();
+                    for target in chunk {
+                        job.errors.push(core::SJobError{ title: target.title.clone(), message: message.clone() });
+                    }
+                }).await;
                 continue;
             }
+        };
 
-            let update = match igdb_game_info.cached_release_date {
-                core::EReleaseDate::UnknownUnreleased => true,
-                core::EReleaseDate::UnknownReleased => false,
-                core::EReleaseDate::Known(d) => d >= six_months_ago,
-            };
+        let mut errors = Vec::new();
+        let mut requests = Vec::with_capacity(chunk.len());
+        for target in chunk {
+            match games_info.found.remove(&target.igdb_id) {
+                Some(game_info) => {
+                    println!("Updating game \"{}\" as \"{}\"", target.title, game_info.title());
+                    requests.push(core::SIGDBRefreshRequest {
+                        internal_id: target.internal_id,
+                        expected_revision: Some(target.revision),
+                        game_info,
+                    });
+                },
+                None => errors.push(core::SJobError {
+                    title: target.title.clone(),
+                    message: format!("Got no results for IGDB game with ID {}", target.igdb_id),
+                }),
+            }
+        }
 
-            if update {
-                games_to_update.push(i);
+        // -- games edited or refreshed by hand since the job started keep those changes
+        let skipped = {
+            let mut db_guard = MEMORY_DB.write().await;
+            match db_guard.deref_mut().as_mut() {
+                Ok(db) => db.refresh_games_info(requests),
+                Err(e) => Err(e.clone()),
             }
+        };
+        match skipped {
+            Ok(skipped) => {
+                for (internal_id, e) in skipped {
+                    let title = chunk.iter().find(|target| target.internal_id == internal_id).map(|target| target.title.clone()).unwrap_or_default();
+                    errors.push(core::SJobError{ title, message: e.to_string() });
+                }
+            },
+            Err(e) => {
+                let message = e.to_string();
+                errors = chunk.iter().map(|target| core::SJobError{ title: target.title.clone(), message: message.clone() }).collect();
+            },
         }
+
+        update_job(job_id, |job| {
+            job.done += chunk.len();
+            job.errors.append(&mut errors);
+        }).await;
     }
 
-    let session = SReqwestTwitchAPIClient::new_session().await.map_err(|e| EErrorResponse::ExternalAPIError(e))?;
+    let mut jobs = JOBS.write().await;
+    if let Ok(job) = jobs.finish(job_id, chrono::offset::Local::now().naive_local(), None) {
+        publish_job(job);
+    }
+}
 
-    for i in games_to_update {
-        let game = &mut db.serialized_db.games[i];
+// -- starts refreshing IGDB info in the background for the games picked by the config's igdb_refresh
+// -- policy, progress goes out as JobUpdated events
+async fn start_igdb_update_job() -> Result<core::SJob, core::EError> {
+    let db_guard = MEMORY_DB.read().await;
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
+
+    let now = chrono::offset::Local::now().naive_local();
+    let games_to_update : Vec<SIGDBUpdateTarget> = db.games_to_refresh(now).iter()
+        .filter_map(|game| game.game_info.igdb_id().map(|igdb_id| SIGDBUpdateTarget {
+            internal_id: game.internal_id,
+            revision: game.revision,
+            igdb_id,
+            title: game.game_info.title().to_string(),
+        }))
+        .collect();
+    drop(db_guard);
+
+    let job = {
+        let mut jobs = JOBS.write().await;
+        let job = jobs.start(core::EJobKind::IGDBUpdate, now)?.id;
+        jobs.update(job, |job| job.total = games_to_update.len())?.clone()
+    };
+    publish_job(&job);
 
-        println!("Updating game \"{}\"", game.game_info.title());
+    tokio::spawn(run_igdb_update_job(job.id, games_to_update));
 
-        let mut new_game_info = None;
-        if let core::EGameInfo::IGDB(igdb_info) = &mut game.game_info {
-            let igdb_game_info = SReqwestTwitchAPIClient::get_game_info(&session, igdb_info.id).await.map_err(|e| EErrorResponse::ExternalAPIError(e))?;
-            new_game_info = Some(igdb_game_info);
-        }
+    Ok(job)
+}
 
-        if let Some(gi) = new_game_info {
-            println!("Updated as \"{}\"", gi.title());
-            game.game_info = gi;
-        }
+#[post("/start_igdb_update")]
+async fn start_igdb_update(_user: AuthenticatedUser) -> Result<RocketJson<core::SJob>, SErrorResponse> {
+    Ok(RocketJson(start_igdb_update_job().await?))
+}
 
-        // -- just hard sleep here to avoid using up our API request budget
-        tokio::time::sleep(std::time::Duration::from_secs_f32(0.5)).await;
-    }
+#[post("/start_igdb_update", rank = 2)]
+async fn start_igdb_update_no_auth() -> Result<RocketJson<core::SJob>, SErrorResponse> {
+    Err(core::EError::NotAuthenticated.into())
+}
 
-    save_db(db).map_err(|_| EErrorResponse::DBError)?;
+// -- recent and running jobs, so a reloaded page can pick up where it was
+#[post("/get_jobs")]
+async fn get_jobs() -> RocketJson<Vec<core::SJob>> {
+    RocketJson(JOBS.read().await.jobs().to_vec())
+}
 
-    Ok(())
+#[post("/get_job/<id>")]
+async fn get_job(id: u32) -> Result<RocketJson<core::SJob>, SErrorResponse> {
+    Ok(RocketJson(JOBS.read().await.job(id)?.clone()))
+}
+
+// -- the most recent scheduled task runs, oldest first
+#[post("/get_scheduled_runs")]
+async fn get_scheduled_runs() -> RocketJson<Vec<scheduler::SScheduledRun>> {
+    RocketJson(scheduler::RUN_LOG.read().await.clone())
 }
 
 #[post("/search_collection/<query>")]
-async fn search_collection(query: &str) -> Result<RocketJson<Vec<core::SCollectionGame>>, EErrorResponse> {
+async fn search_collection(query: &str) -> Result<RocketJson<Vec<core::SCollectionGame>>, SErrorResponse> {
+    let parsed_query = core::parse_collection_query(query).map_err(|e| core::EError::invalid_field("query", e.to_string().as_str()))?;
+    let title_text = parsed_query.title_text();
+    let skip_archived = !parsed_query.mentions_archived();
+
     let db_guard = MEMORY_DB.read().await;
-    let db = &db_guard.deref().as_ref().map_err(|_| EErrorResponse::DBError)?.serialized_db;
+    let data = db_guard.deref().as_ref().map_err(|e| e.clone())?;
+    let repository = data.repository();
+
+    let session_states = core::SGameSessionState::build_map(repository.sessions());
+    let no_sessions = core::SGameSessionState::default();
 
     #[derive(Debug)]
     struct SScore {
         idx: usize,
         score: isize,
     }
-    let mut scores = Vec::with_capacity(db.games.len());
+    let mut scores = Vec::with_capacity(repository.games().len());
 
-    for (idx, game) in db.games.iter().enumerate() {
-        if game.custom_info.archived {
+    for (idx, game) in repository.games().iter().enumerate() {
+        if skip_archived && game.custom_info.archived {
             continue;
         }
 
-        if let Some(m) = sublime_fuzzy::best_match(query, game.game_info.title()) {
+        let session_state = session_states.get(&game.internal_id).unwrap_or(&no_sessions);
+        if !parsed_query.filter.game_passes(data.config(), game, session_state) {
+            continue;
+        }
+
+        // -- a query with only filter terms matches everything that passes
+        if title_text.is_empty() {
+            scores.push(SScore{
+                idx,
+                score: 0,
+            });
+        }
+        else if let Some(m) = sublime_fuzzy::best_match(title_text.as_str(), game.game_info.title()) {
             scores.push(SScore{
                 idx,
                 score: m.score(),
@@ -478,376 +630,215 @@ async fn search_collection(query: &str) -> Result<RocketJson<Vec<core::SCollecti
 
     let mut result = Vec::with_capacity(20);
     for i in 0..std::cmp::min(20, scores.len()) {
-        result.push(db.games[scores[i].idx].clone());
+        result.push(repository.games()[scores[i].idx].clone());
     }
 
     Ok(RocketJson(result))
 }
 
 #[post("/start_session/<game_internal_id>")]
-async fn start_session(game_internal_id: u32, _user: AuthenticatedUser) -> Result<(), EErrorResponse> {
+async fn start_session(game_internal_id: u32, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
     let mut db_guard = MEMORY_DB.write().await;
-    let mut db = db_guard.deref_mut().as_mut().map_err(|_| EErrorResponse::DBError)?;
-
-    for session in &db.serialized_db.sessions {
-        if matches!(session.state, core::ESessionState::Ongoing) && session.game_internal_id == game_internal_id {
-            return Err(EErrorResponse::BadRequest(format!("There is already a session started for the game with ID {}", game_internal_id)));
-        }
-    }
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    let mut found_game = false;
-    for game in &db.serialized_db.games {
-        if game.internal_id == game_internal_id {
-            found_game = true;
-            break;
-        }
-    }
-
-    if !found_game {
-        return Err(EErrorResponse::BadRequest(format!("Could not find a game with internal_id {} to start session for.", game_internal_id)));
-    }
-
-    let mut max_id = 0;
-    for session in &db.serialized_db.sessions {
-        max_id = std::cmp::max(max_id, session.internal_id);
-    }
-
-    db.serialized_db.sessions.push(core::SSession::new(max_id + 1, game_internal_id));
-
-    save_db(&mut db).map_err(|_| EErrorResponse::DBError)?;
+    db.start_session(game_internal_id)?;
 
     Ok(())
 }
 
 #[post("/start_session/<game_internal_id>", rank = 2)]
 #[allow(unused_variables)]
-async fn start_session_no_auth(game_internal_id: u32) -> Result<(), EErrorResponse> {
-    return Err(EErrorResponse::NotAuthenticated);
+async fn start_session_no_auth(game_internal_id: u32) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
 }
 
-#[post("/finish_session/<session_internal_id>/<memorable>/<retire>/<set_ignore_passes>")]
-async fn finish_session(session_internal_id: u32, memorable: bool, retire: bool, set_ignore_passes: bool, _user: AuthenticatedUser) -> Result<(), EErrorResponse> {
+#[post("/finish_session/<session_internal_id>/<memorable>/<retire>/<set_ignore_passes>?<revision>")]
+async fn finish_session(session_internal_id: u32, memorable: bool, retire: bool, set_ignore_passes: bool, revision: Option<u32>, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
     let mut db_guard = MEMORY_DB.write().await;
-    let mut db = db_guard.deref_mut().as_mut().map_err(|_| EErrorResponse::DBError)?;
-
-    let mut game_id_opt = None;
-    for s in &mut db.serialized_db.sessions {
-        if s.internal_id == session_internal_id {
-            s.finish(memorable);
-            game_id_opt = Some(s.game_internal_id);
-            break;
-        }
-    }
-
-    if game_id_opt.is_none() {
-        return Err(EErrorResponse::BadRequest(String::from("Could not find session with matching internal_id to finish.")));
-    }
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    let game_id = game_id_opt.expect("checked above");
-
-    for game in &mut db.serialized_db.games {
-        if game.internal_id == game_id {
-            if retire {
-                game.choose_state.retire();
-            }
-            if set_ignore_passes {
-                game.choose_state.set_ignore_passes();
-            }
-            game.choose_state.push();
-
-            break;
-        }
-    }
-
-    save_db(&mut db).map_err(|_| EErrorResponse::DBError)?;
+    db.finish_session(session_internal_id, revision, memorable, retire, set_ignore_passes)?;
 
     Ok(())
 }
 
 #[post("/finish_session/<session_internal_id>/<memorable>", rank = 2)]
 #[allow(unused_variables)]
-async fn finish_session_no_auth(session_internal_id: u32, memorable: bool) -> Result<(), EErrorResponse> {
-    return Err(EErrorResponse::NotAuthenticated);
+async fn finish_session_no_auth(session_internal_id: u32, memorable: bool) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
 }
 
-#[post("/get_sessions", data = "<filter>")]
-async fn get_sessions(filter: RocketJson<core::SSessionFilter>, _user: AuthenticatedUser) -> Result<RocketJson<Vec<core::SSessionAndCollectionGame>>, EErrorResponse> {
+#[post("/get_sessions", data = "<request>")]
+async fn get_sessions(request: RocketJson<core::SGetSessionsRequest>, _user: AuthenticatedUser) -> Result<RocketJson<core::SPage<core::SSessionAndCollectionGame>>, SErrorResponse> {
     let db_guard = MEMORY_DB.read().await;
-    let db = &db_guard.deref().as_ref().map_err(|_| EErrorResponse::DBError)?.serialized_db;
-
-    let mut result = Vec::with_capacity(10);
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
-    for session in &db.sessions {
-        if filter.session_passes(&session) {
-
-            // -- find the game
-            let mut game_opt = None;
-            for temp_game in &db.games {
-                if temp_game.internal_id == session.game_internal_id {
-                    game_opt = Some(temp_game.clone());
-                    break;
-                }
-            }
-
-            //println!("Session {:?} had no valid game in collection!", session);
-            let game = game_opt.ok_or(EErrorResponse::BadRequest(String::from("Server has bad data, won't be able to continue until it's fixed.")))?;
-
-            result.push(core::SSessionAndCollectionGame{
-                session: session.clone(),
-                collection_game: game,
-            });
-        }
-    }
-
-    Ok(RocketJson(result))
+    Ok(RocketJson(db.get_sessions(request.into_inner())?))
 }
 
-#[post("/get_sessions", data = "<filter>", rank = 2)]
+#[post("/get_sessions", data = "<request>", rank = 2)]
 #[allow(unused_variables)]
-async fn get_sessions_no_auth(filter: RocketJson<core::SSessionFilter>) -> Result<RocketJson<Vec<core::SSessionAndCollectionGame>>, EErrorResponse> {
-    return Err(EErrorResponse::NotAuthenticated);
+async fn get_sessions_no_auth(request: RocketJson<core::SGetSessionsRequest>) -> Result<RocketJson<core::SPage<core::SSessionAndCollectionGame>>, SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
 }
 
-#[post("/get_games", data = "<filter>")]
+#[post("/get_games", data = "<request>")]
 async fn get_games(
-    filter: RocketJson<core::SCollectionGameAndSessionStateFilter>,
-) -> Result<RocketJson<Vec<core::SCollectionGame>>, EErrorResponse> {
-
-    let filter_inner = filter.into_inner();
-
+    request: RocketJson<core::SGetGamesRequest>,
+) -> Result<RocketJson<core::SPage<core::SCollectionGame>>, SErrorResponse> {
     let db_guard = MEMORY_DB.read().await;
-    let data = &db_guard.deref().as_ref().map_err(|_| EErrorResponse::DBError)?;
-
-
-    let mut games = Vec::with_capacity(data.serialized_db.games.len());
-
-    for game in &data.serialized_db.games {
-        if filter_inner.game_filter.game_passes(&data.app_config, &game) {
-            //println!("Passed game with: {:?}", game.choose_state);
-            games.push(game.clone());
-        }
-    }
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
-    if let Some(session_state_filter) = filter_inner.session_state_filter {
-
-        // build session table
-        let mut active_session_game_ids = std::collections::HashSet::new();
-        let mut session_counts : std::collections::HashMap<u32, u16> = std::collections::HashMap::new();
-        for session in &data.serialized_db.sessions {
-            session_counts.entry(session.game_internal_id).and_modify(|count| *count += 1).or_insert(1);
-
-            if let core::ESessionState::Ongoing = session.state {
-                active_session_game_ids.insert(session.game_internal_id);
-            }
-        }
+    Ok(RocketJson(db.get_games(request.into_inner())?))
+}
 
-        games.retain(|game| {
-            session_state_filter.game_passes(
-                &game,
-                session_counts.get(&game.internal_id)
-                    .unwrap_or(&0).clone(),
-                active_session_game_ids.contains(&game.internal_id),
-            )
-        });
-    }
+#[post("/get_filter_presets")]
+async fn get_filter_presets() -> Result<RocketJson<Vec<core::SFilterPreset>>, SErrorResponse> {
+    let db_guard = MEMORY_DB.read().await;
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
-    Ok(RocketJson(games))
+    Ok(RocketJson(db.repository().filter_presets().clone()))
 }
 
-#[post("/update_choose_state", data = "<games>")]
-async fn update_choose_state(games: RocketJson<Vec<core::SCollectionGame>>, _user: AuthenticatedUser) -> Result<(), EErrorResponse> {
-    let mut games_inner = games.into_inner();
+#[post("/add_filter_preset", data = "<preset>")]
+async fn add_filter_preset(preset: RocketJson<core::SFilterPreset>, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
     let mut db_guard = MEMORY_DB.write().await;
-    let db = db_guard.deref_mut().as_mut().map_err(|_| EErrorResponse::DBError)?;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    let mut input_idx = 0;
-    let mut output_idx = 0;
+    db.add_filter_preset(preset.into_inner())?;
 
-    games_inner.sort_unstable_by(|a, b| a.internal_id.cmp(&b.internal_id));
+    Ok(())
+}
 
-    while input_idx < games_inner.len() && output_idx < db.serialized_db.games.len() {
-        if games_inner[input_idx].internal_id == db.serialized_db.games[output_idx].internal_id {
-            db.serialized_db.games[output_idx].choose_state = games_inner[input_idx].choose_state;
-            input_idx = input_idx + 1;
-            output_idx = output_idx + 1;
-        }
-        else {
-            output_idx = output_idx + 1;
+#[post("/add_filter_preset", data = "<preset>", rank = 2)]
+#[allow(unused_variables)]
+async fn add_filter_preset_no_auth(preset: RocketJson<core::SFilterPreset>) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
+}
 
-            // -- EVERY game in games should be present in collection_games, and both vecs
-            // -- should be strictly in order.
-            if db.serialized_db.games[output_idx].internal_id > games_inner[input_idx].internal_id {
-                return Err(EErrorResponse::BadRequest(String::from("During update_choose_state, either games or collection_games as out of order!")));
-            }
-        }
-    }
+#[post("/update_filter_preset", data = "<preset>")]
+async fn update_filter_preset(preset: RocketJson<core::SFilterPreset>, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
+    let mut db_guard = MEMORY_DB.write().await;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    save_db(db).map_err(|_| EErrorResponse::DBError)?;
+    db.update_filter_preset(preset.into_inner())?;
 
     Ok(())
 }
 
-#[post("/update_choose_state", data = "<games>", rank = 2)]
+#[post("/update_filter_preset", data = "<preset>", rank = 2)]
 #[allow(unused_variables)]
-async fn update_choose_state_no_auth(games: RocketJson<Vec<core::SCollectionGame>>) -> Result<(), EErrorResponse> {
-    return Err(EErrorResponse::NotAuthenticated);
+async fn update_filter_preset_no_auth(preset: RocketJson<core::SFilterPreset>) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
 }
 
-#[post("/reset_choose_state/<game_internal_id>")]
-async fn reset_choose_state(game_internal_id: u32, _user: AuthenticatedUser) -> Result<(), EErrorResponse> {
+#[post("/delete_filter_preset/<name>")]
+async fn delete_filter_preset(name: &str, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
     let mut db_guard = MEMORY_DB.write().await;
-    let db = db_guard.deref_mut().as_mut().map_err(|_| EErrorResponse::DBError)?;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-    for game in &mut db.serialized_db.games {
-        if game.internal_id == game_internal_id {
-            game.choose_state.reset();
-            save_db(db).map_err(|_| EErrorResponse::DBError)?;
-            return Ok(());
-        }
-    }
+    db.delete_filter_preset(name)?;
 
-    Err(EErrorResponse::BadRequest(format!("Did not find game with internal_id {} to reset choose_state on", game_internal_id)))
+    Ok(())
 }
 
-#[post("/reset_choose_state/<game_internal_id>", rank = 2)]
+#[post("/delete_filter_preset/<name>", rank = 2)]
 #[allow(unused_variables)]
-async fn reset_choose_state_no_auth(game_internal_id: u32) -> Result<(), EErrorResponse> {
-    return Err(EErrorResponse::NotAuthenticated);
+async fn delete_filter_preset_no_auth(name: &str) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
 }
 
-#[post("/simple_stats")]
-async fn simple_stats() -> Result<RocketJson<core::SSimpleStats>, EErrorResponse> {
+#[post("/explain_filter/<game_internal_id>", data = "<filter>")]
+async fn explain_filter(
+    game_internal_id: u32,
+    filter: RocketJson<core::EGameFilterRequest>,
+) -> Result<RocketJson<core::SFilterExplanation>, SErrorResponse> {
     let db_guard = MEMORY_DB.read().await;
-    let data = db_guard.deref().as_ref().map_err(|_| EErrorResponse::DBError)?;
-
-    let mut stats = core::SSimpleStats{
-        total_collection_size: 0,
-
-        collection_released: 0,
-        collection_owned: 0,
-        collection_selectable: 0,
-        collection_retired: 0,
-        collection_passed_many_times: 0,
-        collection_cooldown: 0,
-
-        collection_played_before: 0,
-        collection_couch_playable_tag: 0,
-        collection_japanese_practice_tag: 0,
-        collection_portable_playable_tag: 0,
-        collection_retro_tag: 0,
-
-        selectable_owned: 0,
-        selectable_played_before: 0,
-        selectable_couch_playable_tag: 0,
-        selectable_japanese_practice_tag: 0,
-        selectable_portable_playable_tag: 0,
-        selectable_retro_tag: 0,
-    };
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
-    let today = chrono::offset::Local::now().naive_local().date();
-
-    fn inc(stat: &mut u32) {
-        *stat = *stat + 1;
-    }
-
-    let selectable_filter = core::SCollectionGameFilter::new()
-        .require_released(true)
-        .require_alive(true)
-        .require_is_after_valid_date();
-
-    for game in &data.serialized_db.games {
-        inc(&mut stats.total_collection_size);
-
-        let selectable = selectable_filter.game_passes(&data.app_config, &game);
-
-        if selectable {
-            inc(&mut stats.collection_selectable);
-        }
-
-        if game.game_info.released() {
-            inc(&mut stats.collection_released);
-        }
+    Ok(RocketJson(db.explain_filter(game_internal_id, filter.into_inner())?))
+}
 
-        if game.choose_state.retired {
-            inc(&mut stats.collection_retired);
-        }
-        else if game.choose_state.passes > data.app_config.live_max_passes {
-            inc(&mut stats.collection_passed_many_times);
-        }
-        else if game.choose_state.next_valid_proposal_date > today {
-            inc(&mut stats.collection_cooldown);
-        }
+#[post("/reset_choose_state/<game_internal_id>")]
+async fn reset_choose_state(game_internal_id: u32, _user: AuthenticatedUser) -> Result<(), SErrorResponse> {
+    let mut db_guard = MEMORY_DB.write().await;
+    let db = db_guard.deref_mut().as_mut().map_err(|e| e.clone())?;
 
-        if game.custom_info.own.owned() {
-            inc(&mut stats.collection_owned);
+    db.reset_choose_state(game_internal_id)?;
 
-            if selectable {
-                inc(&mut stats.selectable_owned);
-            }
-        }
+    Ok(())
+}
 
-        if let Some(session_list) = data.game_sessions_reverse_lookup.get(&game.internal_id) {
-            if session_list.len() > 0 {
-                inc(&mut stats.collection_played_before);
+#[post("/reset_choose_state/<game_internal_id>", rank = 2)]
+#[allow(unused_variables)]
+async fn reset_choose_state_no_auth(game_internal_id: u32) -> Result<(), SErrorResponse> {
+    return Err(core::EError::NotAuthenticated.into());
+}
 
-                if selectable {
-                    inc(&mut stats.selectable_played_before);
-                }
-            }
-        }
+#[post("/get_igdb_facet_options")]
+async fn get_igdb_facet_options() -> Result<RocketJson<Vec<core::SIGDBFacetOptions>>, SErrorResponse> {
+    let db_guard = MEMORY_DB.read().await;
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
-        if game.custom_info.tags.couch_playable {
-            inc(&mut stats.collection_couch_playable_tag);
+    Ok(RocketJson(db.igdb_facet_options()))
+}
 
-            if selectable {
-                inc(&mut stats.selectable_couch_playable_tag);
-            }
-        }
+#[post("/simple_stats")]
+async fn simple_stats() -> Result<RocketJson<core::SSimpleStats>, SErrorResponse> {
+    let db_guard = MEMORY_DB.read().await;
+    let db = db_guard.deref().as_ref().map_err(|e| e.clone())?;
 
-        if game.custom_info.tags.japanese_practice {
-            inc(&mut stats.collection_japanese_practice_tag);
+    Ok(RocketJson(db.simple_stats()))
+}
 
-            if selectable {
-                inc(&mut stats.selectable_japanese_practice_tag);
-            }
-        }
+#[post("/check_logged_in")]
+async fn check_logged_in(_user: AuthenticatedUser) -> Result<(), SErrorResponse> {
+    Ok(())
+}
 
-        if game.custom_info.tags.portable_playable {
-            inc(&mut stats.collection_portable_playable_tag);
+// -- server-sent events for every change, so other open clients can refresh
+#[get("/events")]
+async fn events(mut shutdown: rocket::Shutdown, _user: AuthenticatedUser) -> EventStream![] {
+    let mut receiver = CHANGE_EVENTS.subscribe();
+    let release_day = scheduler::todays_release_day_event().await;
 
-            if selectable {
-                inc(&mut stats.selectable_portable_playable_tag);
-            }
+    EventStream! {
+        if let Some(event) = release_day {
+            yield Event::json(&event);
         }
 
-        if game.custom_info.tags.retro {
-            inc(&mut stats.collection_retro_tag);
+        loop {
+            let event = tokio::select! {
+                received = receiver.recv() => match received {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                    // -- a slow client missed some, it will catch up on its next full load
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                },
+                _ = &mut shutdown => break,
+            };
 
-            if selectable {
-                inc(&mut stats.selectable_retro_tag);
-            }
+            yield Event::json(&event);
         }
     }
-
-    Ok(RocketJson(stats))
 }
 
-#[post("/check_logged_in")]
-async fn check_logged_in(_user: AuthenticatedUser) -> Result<(), EErrorResponse> {
-    Ok(())
+#[get("/events", rank = 2)]
+async fn events_no_auth() -> Result<(), SErrorResponse> {
+    Err(core::EError::NotAuthenticated.into())
 }
 
 #[post("/check_logged_in", rank = 2)]
-async fn check_logged_in_no_auth() -> Result<(), EErrorResponse> {
-    Err(EErrorResponse::NotAuthenticated)
+async fn check_logged_in_no_auth() -> Result<(), SErrorResponse> {
+    Err(core::EError::NotAuthenticated.into())
 }
 
 #[post("/login/<secret>")]
-async fn login(secret: &str, cookies: &rocket::http::CookieJar<'_>) -> Result<(), EErrorResponse> {
+async fn login(secret: &str, cookies: &rocket::http::CookieJar<'_>) -> Result<(), SErrorResponse> {
     let cfg : SConfigFile = match confy::load("gamechooser2_server", None) {
         Ok(c) => c,
         Err(_) => {
-            return Err(EErrorResponse::DBError);
+            return Err(core::EError::Storage(String::from("Could not load server config file")).into());
         }
     };
 
@@ -857,7 +848,7 @@ async fn login(secret: &str, cookies: &rocket::http::CookieJar<'_>) -> Result<()
         return Ok(());
     }
 
-    return Err(EErrorResponse::BadRequest(String::from("Incorrect secret")));
+    return Err(core::EError::BadRequest(String::from("Incorrect secret")).into());
 }
 
 #[launch]
@@ -867,6 +858,8 @@ fn rocket() -> _ {
     rocket::build()
         .mount("/static", rocket::fs::FileServer::from("../client/served_files"))
         .mount("/", routes![
+            events,
+            events_no_auth,
             check_logged_in,
             check_logged_in_no_auth,
             login,
@@ -876,8 +869,23 @@ fn rocket() -> _ {
             add_game_no_auth,
             edit_game,
             edit_game_no_auth,
+            get_game,
+            patch_game,
+            patch_game_no_auth,
+            patch_games,
+            patch_games_no_auth,
+            bulk_edit_games,
+            bulk_edit_games_no_auth,
             get_recent_collection_games,
-            update_igdb_games,
+            preview_igdb_refresh,
+            preview_igdb_refresh_no_auth,
+            apply_igdb_refresh,
+            apply_igdb_refresh_no_auth,
+            start_igdb_update,
+            start_igdb_update_no_auth,
+            get_jobs,
+            get_job,
+            get_scheduled_runs,
             search_collection,
             start_session,
             start_session_no_auth,
@@ -886,10 +894,25 @@ fn rocket() -> _ {
             get_sessions,
             get_sessions_no_auth,
             get_games,
-            update_choose_state,
-            update_choose_state_no_auth,
+            explain_filter,
+            get_filter_presets,
+            add_filter_preset,
+            add_filter_preset_no_auth,
+            update_filter_preset,
+            update_filter_preset_no_auth,
+            delete_filter_preset,
+            delete_filter_preset_no_auth,
             reset_choose_state,
             reset_choose_state_no_auth,
+            get_igdb_facet_options,
             simple_stats,
         ])
+        .attach(rocket::fairing::AdHoc::on_liftoff("Scheduler", |_| Box::pin(async {
+            match confy::load::<SConfigFile>("gamechooser2_server", None) {
+                Ok(cfg) => {
+                    tokio::spawn(scheduler::run_scheduler(cfg));
+                },
+                Err(e) => eprintln!("Not starting the scheduler, could not load server config: {:?}", e),
+            }
+        })))
 }
\ No newline at end of file
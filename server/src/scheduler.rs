@@ -0,0 +1,299 @@
+use std::ops::Deref;
+
+use chrono::Timelike;
+use chrono::naive::{NaiveDate, NaiveDateTime};
+use once_cell::sync::Lazy;
+use serde::{Serialize, Deserialize};
+use tokio::sync::RwLock;
+
+use gamechooser_core as core;
+use crate::{CHANGE_EVENTS, MEMORY_DB, SConfigFile, load_file, save_file, start_igdb_update_job};
+
+// -- how many runs are kept in scheduled_runs.json
+const MAX_LOGGED_RUNS: usize = 100;
+
+// -- cron-like expressions (see core::SCronSchedule) for each task, None never runs it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SScheduleConfig {
+    pub igdb_refresh: Option<String>,
+    pub backup_retention: Option<String>,
+    pub integrity_check: Option<String>,
+    pub release_day: Option<String>,
+
+    // -- database backups in bak/ kept by backup_retention, newest first
+    pub backups_to_keep: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EScheduledTask {
+    IGDBRefresh,
+    BackupRetention,
+    IntegrityCheck,
+    ReleaseDay,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SScheduledRun {
+    pub task: EScheduledTask,
+    pub started: NaiveDateTime,
+    pub finished: NaiveDateTime,
+    pub succeeded: bool,
+    pub message: String,
+}
+
+pub static RUN_LOG : Lazy<RwLock<Vec<SScheduledRun>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+// -- the last release day notice, kept in release_day.json so clients that connect later that day
+// -- still get it
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SReleaseDayNotice {
+    pub date: NaiveDate,
+    pub games: Vec<core::SCollectionGame>,
+}
+
+static RELEASE_DAY : Lazy<RwLock<Option<SReleaseDayNotice>>> = Lazy::new(|| RwLock::new(None));
+
+// -- only the read only tasks run unless configured otherwise
+impl Default for SScheduleConfig {
+    fn default() -> Self {
+        Self {
+            igdb_refresh: None,
+            backup_retention: None,
+            integrity_check: Some(String::from("0 3 * * *")),
+            release_day: Some(String::from("0 9 * * *")),
+            backups_to_keep: 50,
+        }
+    }
+}
+
+impl SScheduleConfig {
+    fn schedules(&self) -> Vec<(EScheduledTask, core::SCronSchedule)> {
+        let configured = [
+            (EScheduledTask::IGDBRefresh, &self.igdb_refresh),
+            (EScheduledTask::BackupRetention, &self.backup_retention),
+            (EScheduledTask::IntegrityCheck, &self.integrity_check),
+            (EScheduledTask::ReleaseDay, &self.release_day),
+        ];
+
+        let mut result = Vec::new();
+        for (task, expression) in configured {
+            if let Some(expression) = expression {
+                match core::SCronSchedule::parse(expression.as_str()) {
+                    Ok(schedule) => result.push((task, schedule)),
+                    Err(e) => eprintln!("Not scheduling {:?}: {}", task, e),
+                }
+            }
+        }
+        result
+    }
+}
+
+fn now() -> NaiveDateTime {
+    chrono::offset::Local::now().naive_local()
+}
+
+impl SReleaseDayNotice {
+    fn event_on(&self, date: NaiveDate) -> Option<core::EChangeEvent> {
+        (self.date == date).then(|| core::EChangeEvent::ReleaseDay{ date: self.date, games: self.games.clone() })
+    }
+}
+
+// -- today's release day event, for a client that has just connected
+pub async fn todays_release_day_event() -> Option<core::EChangeEvent> {
+    RELEASE_DAY.read().await.as_ref().and_then(|notice| notice.event_on(now().date()))
+}
+
+// -- writes on the blocking pool so the scheduler doesn't stall the async threads
+async fn save_in_background<T: Serialize + Send + 'static>(cfg: &SConfigFile, file_name: &'static str, data: T) {
+    let cfg = cfg.clone();
+    let saved = tokio::task::spawn_blocking(move || save_file(&cfg, file_name, &data, false)).await;
+    match saved {
+        Ok(Ok(())) => (),
+        Ok(Err(e)) => eprintln!("{}", e),
+        Err(e) => eprintln!("Saving {}.json failed with: {:?}", file_name, e),
+    }
+}
+
+// -- deletes all but the newest backups_to_keep database backups
+fn prune_backups(cfg: &SConfigFile) -> Result<String, String> {
+    let mut backup_path = std::path::PathBuf::new();
+    backup_path.push(&cfg.db_path);
+    backup_path.push("bak");
+
+    if !backup_path.exists() {
+        return Ok(String::from("No backups yet"));
+    }
+
+    let entries = std::fs::read_dir(&backup_path).map_err(|e| format!("Failed to read {} with: {:?}", backup_path.display(), e))?;
+
+    let mut backups = Vec::new();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Some(timestamp) = name.strip_prefix("database_").and_then(|n| n.strip_suffix(".json")) {
+            if let Ok(timestamp) = timestamp.parse::<i64>() {
+                backups.push((timestamp, entry.path()));
+            }
+        }
+    }
+
+    backups.sort_by_key(|b| std::cmp::Reverse(b.0));
+
+    let mut deleted = 0;
+    for (_, path) in backups.iter().skip(cfg.schedule.backups_to_keep) {
+        std::fs::remove_file(path).map_err(|e| format!("Failed to delete {} with: {:?}", path.display(), e))?;
+        deleted += 1;
+    }
+
+    Ok(format!("Deleted {} of {} backups", deleted, backups.len()))
+}
+
+async fn check_integrity() -> Result<String, String> {
+    let db_guard = MEMORY_DB.read().await;
+    let db = db_guard.deref().as_ref().map_err(|e| e.to_string())?;
+
+    let problems = db.check_integrity(now().date());
+    if problems.is_empty() {
+        Ok(String::from("No problems found"))
+    }
+    else {
+        Err(problems.join("\n"))
+    }
+}
+
+async fn notify_release_day(cfg: &SConfigFile) -> Result<String, String> {
+    let today = now().date();
+    let games : Vec<core::SCollectionGame> = {
+        let db_guard = MEMORY_DB.read().await;
+        let db = db_guard.deref().as_ref().map_err(|e| e.to_string())?;
+        db.games_released_on(today).into_iter().cloned().collect()
+    };
+    if games.is_empty() {
+        return Ok(String::from("Nothing releases today"));
+    }
+
+    let titles : Vec<&str> = games.iter().map(|g| g.game_info.title()).collect();
+    let message = format!("Out today: {}", titles.join(", "));
+
+    let notice = SReleaseDayNotice{ date: today, games };
+    *RELEASE_DAY.write().await = Some(notice.clone());
+    let _ = CHANGE_EVENTS.send(core::EChangeEvent::ReleaseDay{ date: today, games: notice.games.clone() });
+    save_in_background(cfg, "release_day", notice).await;
+
+    Ok(message)
+}
+
+async fn run_task(cfg: &SConfigFile, task: EScheduledTask) {
+    let started = now();
+
+    let result = match task {
+        EScheduledTask::IGDBRefresh => start_igdb_update_job().await
+            .map(|job| format!("Started job {} to refresh {} games", job.id, job.total))
+            .map_err(|e| e.to_string()),
+        EScheduledTask::BackupRetention => prune_backups(cfg),
+        EScheduledTask::IntegrityCheck => check_integrity().await,
+        EScheduledTask::ReleaseDay => notify_release_day(cfg).await,
+    };
+
+    let (succeeded, message) = match result {
+        Ok(message) => (true, message),
+        Err(message) => (false, message),
+    };
+    println!("Scheduled {:?} {}: {}", task, if succeeded { "succeeded" } else { "failed" }, message);
+
+    let log = {
+        let mut log = RUN_LOG.write().await;
+        log.push(SScheduledRun {
+            task,
+            started,
+            finished: now(),
+            succeeded,
+            message,
+        });
+
+        let excess = log.len().saturating_sub(MAX_LOGGED_RUNS);
+        log.drain(..excess);
+        log.clone()
+    };
+
+    save_in_background(cfg, "scheduled_runs", log).await;
+}
+
+// -- wakes at the start of every minute and runs whatever is scheduled for it, one task at a time
+pub async fn run_scheduler(cfg: SConfigFile) {
+    match load_file(&cfg, "scheduled_runs", Vec::new()) {
+        Ok(runs) => *RUN_LOG.write().await = runs,
+        Err(e) => eprintln!("{}", e),
+    }
+    match load_file(&cfg, "release_day", None) {
+        Ok(notice) => *RELEASE_DAY.write().await = notice,
+        Err(e) => eprintln!("{}", e),
+    }
+
+    let schedules = cfg.schedule.schedules();
+    if schedules.is_empty() {
+        return;
+    }
+
+    loop {
+        let seconds_left = 60 - now().second() as u64;
+        tokio::time::sleep(std::time::Duration::from_secs(seconds_left)).await;
+
+        let minute = now();
+        for (task, schedule) in &schedules {
+            if schedule.matches(minute) {
+                run_task(&cfg, *task).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bad_schedules_are_skipped() {
+        let config = SScheduleConfig {
+            igdb_refresh: Some(String::from("not a schedule")),
+            backup_retention: Some(String::from("30 4 * * 0")),
+            ..Default::default()
+        };
+
+        let tasks : Vec<EScheduledTask> = config.schedules().into_iter().map(|(task, _)| task).collect();
+        assert_eq!(tasks, vec![EScheduledTask::BackupRetention, EScheduledTask::IntegrityCheck, EScheduledTask::ReleaseDay]);
+    }
+
+    #[test]
+    fn release_day_notice_replays_on_its_day_only() {
+        let date = NaiveDate::from_ymd(2024, 3, 1);
+        let notice = SReleaseDayNotice{ date, games: Vec::new() };
+
+        assert!(matches!(notice.event_on(date), Some(core::EChangeEvent::ReleaseDay{ date: d, .. }) if d == date));
+        assert!(notice.event_on(date.succ_opt().unwrap()).is_none());
+    }
+
+    #[test]
+    fn prunes_oldest_backups() {
+        let db_path = std::env::temp_dir().join(format!("gamechooser_prune_test_{}", std::process::id()));
+        let backup_path = db_path.join("bak");
+        std::fs::create_dir_all(&backup_path).unwrap();
+        for name in ["database_100.json", "database_300.json", "database_200.json", "app_config_50.json"] {
+            std::fs::write(backup_path.join(name), "{}").unwrap();
+        }
+
+        let mut cfg = SConfigFile {
+            db_path: db_path.to_str().unwrap().to_string(),
+            ..Default::default()
+        };
+        cfg.schedule.backups_to_keep = 2;
+
+        assert_eq!(prune_backups(&cfg).unwrap(), "Deleted 1 of 3 backups");
+        assert!(!backup_path.join("database_100.json").exists());
+        assert!(backup_path.join("database_200.json").exists());
+        assert!(backup_path.join("app_config_50.json").exists());
+
+        std::fs::remove_dir_all(&db_path).unwrap();
+    }
+}